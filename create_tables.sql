@@ -21,3 +21,52 @@ CREATE TABLE klines (
     buy_quote REAL NOT NULL,
     sell_quote REAL NOT NULL
 );
+
+CREATE TABLE order_book_snapshots (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    pair TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    side TEXT NOT NULL,
+    level INTEGER NOT NULL,
+    price REAL NOT NULL,
+    quantity REAL NOT NULL
+);
+
+CREATE TABLE tickers (
+    pair TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    open REAL NOT NULL,
+    high REAL NOT NULL,
+    low REAL NOT NULL,
+    close REAL NOT NULL,
+    quantity REAL NOT NULL,
+    amount REAL NOT NULL,
+    trade_count INTEGER NOT NULL,
+    daily_change REAL NOT NULL,
+    PRIMARY KEY (pair, timestamp)
+);
+
+CREATE TABLE symbols (
+    exchange TEXT NOT NULL,
+    pair TEXT NOT NULL,
+    exchange_symbol TEXT NOT NULL,
+    base TEXT NOT NULL,
+    quote TEXT NOT NULL,
+    status TEXT NOT NULL,
+    trading BOOLEAN NOT NULL,
+    price_precision INTEGER NOT NULL,
+    quantity_precision INTEGER NOT NULL,
+    min_quantity REAL NOT NULL,
+    min_amount REAL NOT NULL,
+    PRIMARY KEY (exchange, pair)
+);
+
+CREATE TABLE indicators (
+    pair TEXT NOT NULL,
+    time_frame TEXT NOT NULL,
+    utc_begin INTEGER NOT NULL,
+    indicator TEXT NOT NULL,
+    params TEXT NOT NULL,
+    value REAL NOT NULL,
+    PRIMARY KEY (pair, time_frame, utc_begin, indicator, params)
+);
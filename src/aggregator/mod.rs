@@ -1,13 +1,42 @@
-use crate::{database::save_klines, parser::kline::Kline};
-use once_cell::sync::Lazy;
-use sqlx::{Pool, Sqlite};
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::Mutex;
-use tracing::{debug, error, info, Level};
+mod trades;
+
+pub use trades::TradeCandles;
+
+use crate::{
+    database::{KlineWriteBuffer, TradeStore},
+    error::Error,
+    health::HealthTracker,
+    indicators::IndicatorEngine,
+    live::LiveFeed,
+    metrics::{metrics, unix_millis},
+    parser::{kline::Kline, recent_trade::RecentTrade, GroupedKlines, KlineKey},
+    sinks::{FeedSink, IndicatorSink, KlineSink, SeriesPattern, SinkRoutes, StoreSink},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    sync::Arc,
+};
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, debug_span, error, warn, Instrument};
+
+/// The work a handler has taken from the batch, awaited by the chain
+pub type HandlerTask = Pin<Box<dyn Future<Output = ()> + Send>>;
+/// A chain element: takes its series out of the batch and returns the work to be done with them
+pub type Handler = Arc<dyn Fn(&mut GroupedKlines) -> Option<HandlerTask> + Send + Sync>;
 
 pub struct CandleAggregator {
-    chain: Mutex<FilterChain>, // асинхронный Mutex
-    db_pool: Arc<Option<Pool<Sqlite>>>,
+    chain: RwLock<FilterChain>, // asynchronous RwLock: handlers are built once, batches are processed many times
+    writer: KlineWriteBuffer, // klines are written through the buffer, which coalesces them into transactions
+    feed: Option<LiveFeed>,   // optional, klines and trades are re-published to live subscribers
+    health: Option<HealthTracker>, // optional, watches the latest klines and trades for freshness
+    indicators: Option<IndicatorEngine>, // optional, computes indicators over the klines passing through
+    trade_candles: Option<Mutex<TradeCandles>>, // optional, builds klines from the trades passing through
+    trade_store: Option<Arc<dyn TradeStore>>, // optional, where the trades passing through are saved
+    routes: SinkRoutes, // sinks of particular series; the others go to the writer, the feed and the indicators
+    unexpected: Arc<AtomicU64>, // klines of series nobody built a handler for
 }
 
 /*
@@ -17,110 +46,419 @@ pub struct CandleAggregator {
     It takes the data it needs and performs the necessary actions with it (stores it in the database).
 
     It was designed with the ability to handle mixed data from multiple url's (different pairs and timeframes),
-    sorting each data series by a separate handler, However, this has not been tested....
+    sorting each data series by a separate handler. Whatever no handler took ends up in the fallback handler
+    at the end of the chain, which logs and counts it before handling it like the others.
+
+    Each series goes to its sinks: by default the live feed, the indicators and the write buffer (the
+    database), or those given for it with `with_series_sinks`, e.g. 1m candles stored but 1s ones only
+    published.
+
+    The aggregator is an ordinary value that owns its storage, so several independent instances
+    (e.g. one per exchange) can live side by side. Batches are processed entirely on the caller's runtime.
 */
 impl CandleAggregator {
-    pub fn get_instance() -> &'static Arc<Self> {
-        static INSTANCE: Lazy<Arc<CandleAggregator>> = Lazy::new(|| {
-            Arc::new(CandleAggregator {
-                chain: Mutex::new(FilterChain::new()),
-                db_pool: Arc::new(None),
-            })
-        });
-        &INSTANCE
-    }
-
-    pub async fn build_handlers(
-        self: Arc<Self>, // Pass self as Arc<Self>
-        keys: &[(String, String)],
-        db_pool: Arc<Pool<Sqlite>>,
-    ) {
-        let db_pool = db_pool.clone(); // Cloning so we don't have to keep the link
-        let self_clone = Arc::clone(&self); // Clone self before we pass it to the asynchronous task
-        let handler = Arc::new(move |data: &mut HashMap<(String, String), Vec<Kline>>| {
-            let mut keys_to_remove = Vec::new();
-            for (key, klines) in data.iter() {
-                let key = key.clone();
-                let klines = klines.clone();
-                keys_to_remove.push(key.clone());
-
-                let db_pool = db_pool.clone();
-                let self_clone = Arc::clone(&self_clone); // Clone self for each task
-
-                //Using tokio::task::spawn_blocking to prevent async runtime blocking when saving klines
-                tokio::task::spawn_blocking(move || {
-                    let rt = tokio::runtime::Runtime::new().unwrap(); // Create a temporary runtime
-                    rt.block_on(async {
-                        let chain = self_clone.chain.lock().await;
-                        if let Some(last_kline) = klines.iter().max_by_key(|k| k.utc_begin) {
-                            chain
-                                .update_last_kline(key.clone(), last_kline.clone())
-                                .await;
-                        }
-                        match save_klines(&db_pool, &klines).await {
-                            Ok(_) => debug!("Save klines completed"),
-                            Err(e) => error!("Failed to save klines: {}", e),
-                        }
-                    });
-                });
-            }
+    pub fn new(writer: KlineWriteBuffer) -> Self {
+        CandleAggregator {
+            chain: RwLock::new(FilterChain::new()),
+            writer,
+            feed: None,
+            health: None,
+            indicators: None,
+            trade_candles: None,
+            trade_store: None,
+            routes: SinkRoutes::default(),
+            unexpected: Arc::new(AtomicU64::new(0)),
+        }
+    }
 
-            for key in keys_to_remove {
-                data.remove(&key);
-            }
+    /// Re-publishes everything passing through the aggregator to the feed's subscribers
+    pub fn with_live_feed(mut self, feed: LiveFeed) -> Self {
+        self.feed = Some(feed);
+        self
+    }
 
-            true
-        });
+    /// Reports the freshness of the configured series and of the trades to the tracker
+    pub fn with_health(mut self, health: HealthTracker) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Keeps the engine's indicators up to date with every kline queued for saving
+    pub fn with_indicators(mut self, indicators: IndicatorEngine) -> Self {
+        self.indicators = Some(indicators);
+        self
+    }
+
+    /// Builds klines from the trades and passes them through the chain like fetched ones
+    pub fn with_trade_candles(mut self, candles: TradeCandles) -> Self {
+        self.trade_candles = Some(Mutex::new(candles));
+        self
+    }
+
+    /// Saves the trades passing through, e.g. live ones; replayed trades are stored already
+    pub fn with_trade_store(mut self, store: Arc<dyn TradeStore>) -> Self {
+        self.trade_store = Some(store);
+        self
+    }
 
-        // Clone self when calling add_handler
-        self.chain.lock().await.add_handler(handler);
+    /// Sends the series matching the pattern to these sinks instead of the default ones; the
+    /// first matching pattern wins, no sinks drops the series
+    pub fn with_series_sinks(
+        mut self,
+        series: SeriesPattern,
+        sinks: Vec<Arc<dyn KlineSink>>,
+    ) -> Self {
+        self.routes.add_route(series, sinks);
+        self
     }
 
-    pub async fn http_response_process(
-        &self,
-        mut grouped_kline: HashMap<(String, String), Vec<Kline>>,
-    ) {
-        // We use block_in_place to perform synchronous blocking in an asynchronous context
-        tokio::task::block_in_place(|| {
-            let chain = self.chain.blocking_lock(); // Synchronous access
-            chain.execute(&mut grouped_kline);
+    /// The sinks of the series without sinks of their own
+    fn default_sinks(&self) -> Vec<Arc<dyn KlineSink>> {
+        let mut sinks: Vec<Arc<dyn KlineSink>> = Vec::new();
+        if let Some(feed) = &self.feed {
+            sinks.push(Arc::new(FeedSink(feed.clone())));
+        }
+        if let Some(indicators) = &self.indicators {
+            sinks.push(Arc::new(IndicatorSink(indicators.clone())));
+        }
+        sinks.push(Arc::new(StoreSink(self.writer.clone())));
+        sinks
+    }
+
+    /// Klines that reached the fallback handler: of series missing from the `keys` of `build_handlers`
+    pub fn unexpected_klines(&self) -> u64 {
+        self.unexpected.load(Ordering::Relaxed)
+    }
+
+    /// Builds the chain anew: a handler per key, then the fallback handler
+    pub async fn build_handlers(&self, keys: &[KlineKey]) {
+        let mut routes = self.routes.clone();
+        for sink in self.default_sinks() {
+            routes.add_default(sink);
+        }
+        let mut chain = self.chain.write().await;
+        chain.clear_handlers();
+        let last_klines = chain.last_klines();
+        if let Some(health) = &self.health {
+            health.watch(keys);
+        }
+        // The write buffer reports stored klines; the others count once their sinks took them
+        let health = self.health.clone();
+        let unstored_health = move |sinks: &[Arc<dyn KlineSink>]| {
+            health
+                .clone()
+                .filter(|_| !sinks.iter().any(|sink| sink.name() == "store"))
+        };
+
+        let mut built = HashSet::new();
+        for key in keys.iter().filter(|key| built.insert(*key)) {
+            let key = key.clone();
+            let sinks = routes.route(&key);
+            let health = unstored_health(&sinks);
+            let last_klines = Arc::clone(&last_klines);
+            let handler: Handler = Arc::new(move |data: &mut GroupedKlines| {
+                // Take only this series out of the batch
+                let klines = data.remove(&key)?;
+                Some(send_series(
+                    key.clone(),
+                    klines,
+                    sinks.clone(),
+                    Arc::clone(&last_klines),
+                    health.clone(),
+                ))
+            });
+            chain.add_handler(handler);
+        }
+
+        /* With no keys at all nothing in particular is expected (e.g. a replay): the series are not reported */
+        let report = !keys.is_empty();
+        let unexpected = Arc::clone(&self.unexpected);
+        let reported: Arc<std::sync::Mutex<HashSet<KlineKey>>> = Arc::default();
+        let fallback: Handler = Arc::new(move |data: &mut GroupedKlines| {
+            if data.is_empty() {
+                return None;
+            }
+            let tasks: Vec<HandlerTask> = data
+                .drain()
+                .map(|(key, klines)| {
+                    unexpected.fetch_add(klines.len() as u64, Ordering::Relaxed);
+                    metrics()
+                        .klines_unexpected
+                        .with_label_values(&[key.0.as_str(), key.1.as_str()])
+                        .inc_by(klines.len() as u64);
+                    if report && reported.lock().unwrap().insert(key.clone()) {
+                        warn!(
+                            "Klines of {} {}, which no handler expects, handled by the fallback",
+                            key.0, key.1
+                        );
+                    }
+                    let sinks = routes.route(&key);
+                    let health = unstored_health(&sinks);
+                    send_series(key, klines, sinks, Arc::clone(&last_klines), health)
+                })
+                .collect();
+            Some(Box::pin(async move {
+                for task in tasks {
+                    task.await;
+                }
+            }) as HandlerTask)
         });
+        chain.add_handler(fallback);
     }
+
+    pub async fn http_response_process(&self, mut grouped_kline: GroupedKlines) {
+        let span = debug_span!("aggregate", series = grouped_kline.len());
+        async {
+            let chain = self.chain.read().await;
+            chain.execute(&mut grouped_kline).await;
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Counts, saves and re-publishes the trades, and with `with_trade_candles` turns them into klines
+    pub async fn trades_process(&self, trades: &[RecentTrade]) {
+        let now = unix_millis() as f64 / 1_000.0;
+        for trade in trades {
+            if let Some(health) = &self.health {
+                health.record_trade(&trade.pair);
+            }
+            metrics()
+                .trades_received
+                .with_label_values(&[trade.pair.as_str()])
+                .inc();
+            metrics()
+                .trades_last_received
+                .with_label_values(&[trade.pair.as_str()])
+                .set(now);
+        }
+        if let Some(store) = &self.trade_store {
+            if let Err(err) = store.save_trades(trades).await {
+                error!("Failed to save {} trades: {}", trades.len(), err);
+            }
+        }
+        if let Some(feed) = &self.feed {
+            feed.publish_trades(trades);
+        }
+        if let Some(candles) = &self.trade_candles {
+            let grouped = candles.lock().await.add(trades);
+            if !grouped.is_empty() {
+                self.http_response_process(grouped).await;
+            }
+        }
+    }
+
+    pub async fn get_last_kline(&self, key: &KlineKey) -> Option<Kline> {
+        self.chain.read().await.get_last_kline(key).await
+    }
+
+    /// Waits until everything passed to the aggregator so far is written
+    pub async fn flush(&self) -> Result<(), Error> {
+        self.writer.flush().await
+    }
+}
+
+/// The work of a handler with its series: every sink, then the latest kline (and, with `health`, its freshness) once they all took it
+fn send_series(
+    key: KlineKey,
+    klines: Vec<Kline>,
+    sinks: Vec<Arc<dyn KlineSink>>,
+    last_klines: Arc<Mutex<HashMap<KlineKey, Kline>>>,
+    health: Option<HealthTracker>,
+) -> HandlerTask {
+    let span = debug_span!("queue", pair = %key.0, timeframe = %key.1, rows = klines.len());
+    Box::pin(
+        async move {
+            let mut failed = false;
+            for sink in &sinks {
+                match sink.send(&klines).await {
+                    Ok(()) => debug!("Klines sent to {}", sink.name()),
+                    Err(e) => {
+                        error!("Failed to send klines to {}: {}", sink.name(), e);
+                        failed = true;
+                    }
+                }
+            }
+            if failed {
+                return;
+            }
+            if let Some(health) = &health {
+                health.record_klines(&klines);
+            }
+            if let Some(last_kline) = klines.into_iter().max_by_key(|k| k.utc_begin) {
+                last_klines.lock().await.insert(key, last_kline);
+            }
+        }
+        .instrument(span),
+    )
 }
 
 pub struct FilterChain {
-    handlers: Vec<Arc<dyn Fn(&mut HashMap<(String, String), Vec<Kline>>) -> bool + Send + Sync>>,
-    last_klines: Mutex<HashMap<(String, String), Kline>>, // this is where we keep all the latest Kline
+    handlers: Vec<Handler>,
+    last_klines: Arc<Mutex<HashMap<KlineKey, Kline>>>, // this is where we keep all the latest Kline
+}
+
+impl Default for FilterChain {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FilterChain {
     pub fn new() -> Self {
         FilterChain {
             handlers: Vec::new(),
-            last_klines: Mutex::new(HashMap::new()),
+            last_klines: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
-    pub async fn update_last_kline(&self, key: (String, String), kline: Kline) {
+    /// Shared handle to the latest klines, for handlers that update them from their tasks
+    pub fn last_klines(&self) -> Arc<Mutex<HashMap<KlineKey, Kline>>> {
+        Arc::clone(&self.last_klines)
+    }
+
+    pub async fn update_last_kline(&self, key: KlineKey, kline: Kline) {
         let mut last_klines = self.last_klines.lock().await;
         last_klines.insert(key, kline);
     }
 
-    pub async fn get_last_kline(&self, key: &(String, String)) -> Option<Kline> {
+    pub async fn get_last_kline(&self, key: &KlineKey) -> Option<Kline> {
         let last_klines = self.last_klines.lock().await;
         last_klines.get(key).cloned()
     }
 
-    pub fn add_handler(
-        &mut self,
-        handler: Arc<dyn Fn(&mut HashMap<(String, String), Vec<Kline>>) -> bool + Send + Sync>,
-    ) {
+    pub fn add_handler(&mut self, handler: Handler) {
         self.handlers.push(handler);
     }
 
-    pub fn execute(&self, grouped_kline: &mut HashMap<(String, String), Vec<Kline>>) {
+    pub fn clear_handlers(&mut self) {
+        self.handlers.clear();
+    }
+
+    /// Passes the batch through the handlers in order and waits for the work they have taken on
+    pub async fn execute(&self, grouped_kline: &mut GroupedKlines) {
         for handler in &self.handlers {
-            if handler(grouped_kline) {}
+            if let Some(task) = handler(grouped_kline) {
+                task.await;
+            }
+        }
+    }
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{KlineFilter, KlineStore, MemoryStore, WriteBufferConfig};
+    use crate::sinks::SinkFuture;
+
+    #[tokio::test] // Runs on the current-thread runtime: no nested runtimes or blocking calls
+    async fn test_independent_aggregators() {
+        let store_a = Arc::new(MemoryStore::new());
+        let store_b = Arc::new(MemoryStore::new());
+        let aggregator_a = CandleAggregator::new(KlineWriteBuffer::spawn(
+            store_a.clone(),
+            WriteBufferConfig::default(),
+        ));
+        let aggregator_b = CandleAggregator::new(KlineWriteBuffer::spawn(
+            store_b.clone(),
+            WriteBufferConfig::default(),
+        ));
+        let key = ("BTC_USDT".to_string(), "MINUTE_1".to_string());
+        aggregator_a
+            .build_handlers(std::slice::from_ref(&key))
+            .await;
+        aggregator_b
+            .build_handlers(std::slice::from_ref(&key))
+            .await;
+
+        let mut batch = GroupedKlines::new();
+        batch.insert(
+            key.clone(),
+            vec![
+                Kline::sample("BTC_USDT", "MINUTE_1", 60),
+                Kline::sample("BTC_USDT", "MINUTE_1", 120),
+            ],
+        );
+        aggregator_a.http_response_process(batch).await;
+        aggregator_a.flush().await.unwrap();
+
+        let all = KlineFilter::default();
+        assert_eq!(store_a.load_klines(&all).await.unwrap().len(), 2);
+        assert_eq!(store_b.load_klines(&all).await.unwrap().len(), 0);
+
+        let last = aggregator_a.get_last_kline(&key).await.unwrap();
+        assert_eq!(last.utc_begin, 120);
+        assert!(aggregator_b.get_last_kline(&key).await.is_none());
+    }
+
+    /// Keeps what it is sent
+    #[derive(Default)]
+    struct Recorder(std::sync::Mutex<Vec<Kline>>);
+
+    impl KlineSink for Recorder {
+        fn name(&self) -> &str {
+            "recorder"
+        }
+
+        fn send<'a>(&'a self, klines: &'a [Kline]) -> SinkFuture<'a> {
+            self.0.lock().unwrap().extend_from_slice(klines);
+            Box::pin(async { Ok(()) })
         }
     }
+
+    #[tokio::test]
+    async fn test_series_sinks_and_fallback() {
+        let store = Arc::new(MemoryStore::new());
+        let recorder = Arc::new(Recorder::default());
+        let seconds = SeriesPattern {
+            pair: None,
+            time_frame: Some("SECOND_1".to_string()),
+        };
+        let aggregator = CandleAggregator::new(KlineWriteBuffer::spawn(
+            store.clone(),
+            WriteBufferConfig::default(),
+        ))
+        .with_series_sinks(seconds, vec![recorder.clone()]);
+        let minutes = ("BTC_USDT".to_string(), "MINUTE_1".to_string());
+        let seconds = ("BTC_USDT".to_string(), "SECOND_1".to_string());
+        aggregator
+            .build_handlers(&[minutes.clone(), seconds.clone()])
+            .await;
+
+        let mut batch = GroupedKlines::new();
+        batch.insert(
+            minutes.clone(),
+            vec![Kline::sample("BTC_USDT", "MINUTE_1", 60)],
+        );
+        batch.insert(
+            seconds.clone(),
+            vec![Kline::sample("BTC_USDT", "SECOND_1", 61)],
+        );
+        let unexpected = ("ETH_USDT".to_string(), "MINUTE_1".to_string());
+        batch.insert(
+            unexpected.clone(),
+            vec![Kline::sample("ETH_USDT", "MINUTE_1", 60)],
+        );
+        aggregator.http_response_process(batch).await;
+        aggregator.flush().await.unwrap();
+
+        // 1m candles are stored, 1s ones only go to their sink
+        let stored = store.load_klines(&KlineFilter::default()).await.unwrap();
+        let mut stored: Vec<&str> = stored.iter().map(|k| k.pair.as_str()).collect();
+        stored.sort();
+        assert_eq!(stored, vec!["BTC_USDT", "ETH_USDT"]);
+        let recorded = recorder.0.lock().unwrap().clone();
+        assert_eq!((recorded.len(), recorded[0].utc_begin), (1, 61));
+
+        // The series nobody expected went through the fallback, and is counted
+        assert_eq!(aggregator.unexpected_klines(), 1);
+        assert!(aggregator.get_last_kline(&unexpected).await.is_some());
+        assert_eq!(
+            aggregator.get_last_kline(&seconds).await.unwrap().utc_begin,
+            61
+        );
+    }
 }
@@ -0,0 +1,180 @@
+use std::collections::{HashMap, HashSet};
+
+use tracing::debug;
+
+use crate::parser::{
+    kline::{Kline, VBS},
+    recent_trade::RecentTrade,
+    time_frame_millis, GroupedKlines, KlineKey,
+};
+
+/*
+    Builds klines of the configured time frames from trades, the way the exchange does:
+    a candle starts at a multiple of its length since the epoch, its volumes map onto VBS like
+    the REST fields (taker buys -> buy_*, every trade -> sell_*). Trades must arrive in time
+    order per pair; one older than the open candle of its series is dropped.
+    Weeks and months are not aligned on the epoch and can't be built.
+*/
+#[derive(Debug, Clone)]
+pub struct TradeCandles {
+    time_frames: Vec<(String, i64)>,
+    open: HashMap<KlineKey, Kline>,
+}
+
+impl TradeCandles {
+    pub fn new(time_frames: &[String]) -> Result<Self, String> {
+        let time_frames = time_frames
+            .iter()
+            .map(|time_frame| match time_frame_millis(time_frame) {
+                Some(millis) if Self::can_build(time_frame) => Ok((time_frame.clone(), millis)),
+                _ => Err(format!("can't build {} candles from trades", time_frame)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(TradeCandles {
+            time_frames,
+            open: HashMap::new(),
+        })
+    }
+
+    /// Whether candles of the time frame can be built from trades
+    pub fn can_build(time_frame: &str) -> bool {
+        time_frame_millis(time_frame).is_some() && time_frame != "WEEK_1" && time_frame != "MONTH_1"
+    }
+
+    pub fn time_frames(&self) -> impl Iterator<Item = &str> {
+        self.time_frames
+            .iter()
+            .map(|(time_frame, _)| time_frame.as_str())
+    }
+
+    /// Adds the trades, returns the latest state of every candle they touched
+    pub fn add(&mut self, trades: &[RecentTrade]) -> GroupedKlines {
+        let mut touched: HashSet<(KlineKey, i64)> = HashSet::new();
+        let mut grouped = GroupedKlines::new();
+
+        for trade in trades {
+            let (Ok(price), Ok(quantity)) =
+                (trade.price.parse::<f64>(), trade.amount.parse::<f64>())
+            else {
+                debug!(
+                    "Trade {} of {} is unreadable, skipped",
+                    trade.tid, trade.pair
+                );
+                continue;
+            };
+            let quote = price * quantity;
+            let taker_buy = trade.side.eq_ignore_ascii_case("buy");
+
+            for (time_frame, millis) in &self.time_frames {
+                let key = (trade.pair.clone(), time_frame.clone());
+                let utc_begin = trade.timestamp - trade.timestamp.rem_euclid(*millis);
+                match self.open.get_mut(&key) {
+                    Some(kline) if kline.utc_begin == utc_begin => {
+                        kline.h = kline.h.max(price);
+                        kline.l = kline.l.min(price);
+                        kline.c = price;
+                        kline.volume_bs.sell_base += quantity;
+                        kline.volume_bs.sell_quote += quote;
+                        if taker_buy {
+                            kline.volume_bs.buy_base += quantity;
+                            kline.volume_bs.buy_quote += quote;
+                        }
+                    }
+                    Some(kline) if kline.utc_begin > utc_begin => {
+                        debug!(
+                            "Trade {} is older than the open {} candle, dropped",
+                            trade.tid, time_frame
+                        );
+                        continue;
+                    }
+                    _ => {
+                        let kline = Kline {
+                            pair: trade.pair.clone(),
+                            time_frame: time_frame.clone(),
+                            o: price,
+                            h: price,
+                            l: price,
+                            c: price,
+                            utc_begin,
+                            volume_bs: VBS {
+                                buy_base: if taker_buy { quantity } else { 0.0 },
+                                sell_base: quantity,
+                                buy_quote: if taker_buy { quote } else { 0.0 },
+                                sell_quote: quote,
+                            },
+                        };
+                        // The candle it replaces is complete; its last state is kept if this batch touched it
+                        if let Some(closed) = self.open.insert(key.clone(), kline) {
+                            if touched.remove(&(key.clone(), closed.utc_begin)) {
+                                grouped.entry(key.clone()).or_default().push(closed);
+                            }
+                        }
+                    }
+                }
+                touched.insert((key, utc_begin));
+            }
+        }
+
+        for (key, _) in touched {
+            if let Some(kline) = self.open.get(&key) {
+                grouped.entry(key).or_default().push(kline.clone());
+            }
+        }
+        grouped
+    }
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(tid: &str, price: &str, amount: &str, side: &str, timestamp: i64) -> RecentTrade {
+        RecentTrade {
+            tid: tid.to_string(),
+            pair: "BTC_USDT".to_string(),
+            price: price.to_string(),
+            amount: amount.to_string(),
+            side: side.to_string(),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_trade_candles() {
+        let mut candles =
+            TradeCandles::new(&["MINUTE_1".to_string(), "MINUTE_5".to_string()]).unwrap();
+        assert!(TradeCandles::new(&["WEEK_1".to_string()]).is_err());
+
+        let grouped = candles.add(&[
+            trade("1", "100", "1", "buy", 60_000),
+            trade("2", "110", "2", "sell", 90_000),
+            trade("3", "90", "1", "buy", 120_500),
+            trade("4", "95", "1", "buy", 30_000), // late for the minutes only
+        ]);
+        let minutes = &grouped[&("BTC_USDT".to_string(), "MINUTE_1".to_string())];
+        assert_eq!(minutes.len(), 2);
+        let first = minutes.iter().find(|k| k.utc_begin == 60_000).unwrap();
+        assert_eq!(
+            (first.o, first.h, first.l, first.c),
+            (100.0, 110.0, 100.0, 110.0)
+        );
+        assert_eq!(first.volume_bs.sell_base, 3.0);
+        assert_eq!(first.volume_bs.sell_quote, 320.0);
+        assert_eq!(first.volume_bs.buy_base, 1.0);
+        assert_eq!(first.volume_bs.buy_quote, 100.0);
+
+        let five = &grouped[&("BTC_USDT".to_string(), "MINUTE_5".to_string())];
+        assert_eq!(five.len(), 1);
+        assert_eq!((five[0].utc_begin, five[0].l, five[0].c), (0, 90.0, 95.0));
+        assert_eq!(five[0].volume_bs.sell_base, 5.0);
+
+        // Only the touched candle comes back
+        let grouped = candles.add(&[trade("5", "91", "1", "sell", 150_000)]);
+        let minutes = &grouped[&("BTC_USDT".to_string(), "MINUTE_1".to_string())];
+        assert_eq!(minutes.len(), 1);
+        assert_eq!((minutes[0].utc_begin, minutes[0].c), (120_000, 91.0));
+    }
+}
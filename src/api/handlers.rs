@@ -0,0 +1,124 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use futures_util::{StreamExt, TryStreamExt};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::error;
+
+use super::MAX_ROWS;
+use crate::database::{KlineFilter, Store, TradeFilter};
+use crate::export::records::{KlineRecord, TradeRecord};
+
+type ApiState = State<Arc<dyn Store>>;
+
+/// A storage failure, answered with 500 and the error text
+pub struct ApiError(sqlx::Error);
+
+impl From<sqlx::Error> for ApiError {
+    fn from(err: sqlx::Error) -> Self {
+        ApiError(err)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        error!("Query API storage error: {}", self.0);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": self.0.to_string() })),
+        )
+            .into_response()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct KlineQuery {
+    pair: Option<String>,
+    timeframe: Option<String>,
+    from: Option<i64>,
+    to: Option<i64>,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct TradeQuery {
+    pair: Option<String>,
+    from: Option<i64>,
+    to: Option<i64>,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct TimeFrameQuery {
+    pair: Option<String>,
+}
+
+fn limit(requested: Option<usize>) -> usize {
+    requested.unwrap_or(MAX_ROWS).min(MAX_ROWS)
+}
+
+pub async fn klines(
+    State(store): ApiState,
+    Query(query): Query<KlineQuery>,
+) -> Result<Json<Vec<KlineRecord>>, ApiError> {
+    let filter = KlineFilter {
+        pair: query.pair,
+        time_frame: query.timeframe,
+        from: query.from,
+        to: query.to,
+    };
+    let klines = store
+        .stream_klines(&filter)
+        .take(limit(query.limit))
+        .map_ok(|kline| KlineRecord::from(&kline))
+        .try_collect()
+        .await?;
+    Ok(Json(klines))
+}
+
+pub async fn trades(
+    State(store): ApiState,
+    Query(query): Query<TradeQuery>,
+) -> Result<Json<Vec<TradeRecord>>, ApiError> {
+    let filter = TradeFilter {
+        pair: query.pair,
+        from: query.from,
+        to: query.to,
+    };
+    let trades = store
+        .stream_trades(&filter)
+        .take(limit(query.limit))
+        .map_ok(|trade| TradeRecord::from(&trade))
+        .try_collect()
+        .await?;
+    Ok(Json(trades))
+}
+
+pub async fn pairs(State(store): ApiState) -> Result<Json<Vec<String>>, ApiError> {
+    let pairs: BTreeSet<String> = store
+        .load_series()
+        .await?
+        .into_iter()
+        .map(|(pair, _)| pair)
+        .collect();
+    Ok(Json(pairs.into_iter().collect()))
+}
+
+pub async fn time_frames(
+    State(store): ApiState,
+    Query(query): Query<TimeFrameQuery>,
+) -> Result<Json<Vec<String>>, ApiError> {
+    let time_frames: BTreeSet<String> = store
+        .load_series()
+        .await?
+        .into_iter()
+        .filter(|(pair, _)| query.pair.as_ref().is_none_or(|p| p == pair))
+        .map(|(_, time_frame)| time_frame)
+        .collect();
+    Ok(Json(time_frames.into_iter().collect()))
+}
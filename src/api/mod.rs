@@ -0,0 +1,44 @@
+mod handlers;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::routing::get;
+use axum::Router;
+use tokio::net::TcpListener;
+use tracing::info;
+
+use crate::database::Store;
+use crate::error::Error;
+
+/// Rows returned by `/klines` and `/trades` when the request sets no `limit`, and the most it may ask for
+pub const MAX_ROWS: usize = 10_000;
+
+/*
+    Read-only JSON API over the store, for services that need candles without opening the database:
+        GET /klines?pair=&timeframe=&from=&to=&limit=   klines ordered by utc_begin
+        GET /trades?pair=&from=&to=&limit=              trades ordered by timestamp
+        GET /pairs                                      pairs with stored klines
+        GET /timeframes?pair=                           time frames with stored klines
+    Klines and trades are serialized like the exported rows (`KlineRecord`, `TradeRecord`).
+*/
+pub fn router(store: Arc<dyn Store>) -> Router {
+    Router::new()
+        .route("/klines", get(handlers::klines))
+        .route("/trades", get(handlers::trades))
+        .route("/pairs", get(handlers::pairs))
+        .route("/timeframes", get(handlers::time_frames))
+        .with_state(store)
+}
+
+/// Serves the API on an already bound listener until the task is dropped
+pub async fn serve(listener: TcpListener, store: Arc<dyn Store>) -> Result<(), Error> {
+    info!("Query API listening on {}", listener.local_addr()?);
+    axum::serve(listener, router(store)).await?;
+    Ok(())
+}
+
+/// Binds the address and serves the API
+pub async fn bind_and_serve(addr: SocketAddr, store: Arc<dyn Store>) -> Result<(), Error> {
+    serve(TcpListener::bind(addr).await?, store).await
+}
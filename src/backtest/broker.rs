@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::parser::kline::Kline;
+
+/// Costs applied by the simulated broker to every fill
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BrokerConfig {
+    pub initial_cash: f64,
+    pub fee_rate: f64, // fraction of the filled notional, e.g. 0.001 for 0.1%
+    pub slippage: f64, // fraction of the price moved against the order
+}
+
+impl Default for BrokerConfig {
+    fn default() -> Self {
+        BrokerConfig {
+            initial_cash: 10_000.0,
+            fee_rate: 0.001,
+            slippage: 0.0005,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// An executed order
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Fill {
+    pub pair: String,
+    pub utc_begin: i64, // the kline whose open filled the order
+    pub side: Side,
+    pub quantity: f64,
+    pub price: f64, // after slippage
+    pub fee: f64,
+}
+
+/// A position, or the part of it, that was closed; `pnl` is net of the entry and exit fees
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClosedTrade {
+    pub pair: String,
+    pub entry_time: i64,
+    pub exit_time: i64,
+    pub long: bool,
+    pub quantity: f64,
+    pub entry_price: f64,
+    pub exit_price: f64,
+    pub pnl: f64,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Position {
+    quantity: f64, // negative when short
+    avg_price: f64,
+    opened_at: i64,
+    fees: f64, // entry fees not yet charged to a closed trade
+}
+
+/*
+    Market orders only. Orders submitted while a kline is handled are filled at the open of
+    the pair's next kline, moved against the order by `slippage`, so a strategy never trades
+    on a price it could not have had. Short positions are allowed; cash may go negative,
+    margin isn't modelled.
+*/
+#[derive(Debug, Clone)]
+pub struct SimulatedBroker {
+    config: BrokerConfig,
+    cash: f64,
+    fees_paid: f64,
+    positions: HashMap<String, Position>,
+    pending: HashMap<String, f64>,
+    prices: HashMap<String, f64>,
+    fills: Vec<Fill>,
+    closed: Vec<ClosedTrade>,
+}
+
+impl SimulatedBroker {
+    pub fn new(config: BrokerConfig) -> Self {
+        SimulatedBroker {
+            config,
+            cash: config.initial_cash,
+            fees_paid: 0.0,
+            positions: HashMap::new(),
+            pending: HashMap::new(),
+            prices: HashMap::new(),
+            fills: Vec::new(),
+            closed: Vec::new(),
+        }
+    }
+
+    pub fn config(&self) -> &BrokerConfig {
+        &self.config
+    }
+
+    /// Queues a market order, positive to buy and negative to sell; orders of a pair add up
+    pub fn submit(&mut self, pair: &str, quantity: f64) {
+        *self.pending.entry(pair.to_string()).or_default() += quantity;
+    }
+
+    pub fn buy(&mut self, pair: &str, quantity: f64) {
+        self.submit(pair, quantity.abs());
+    }
+
+    pub fn sell(&mut self, pair: &str, quantity: f64) {
+        self.submit(pair, -quantity.abs());
+    }
+
+    /// Queues the order that brings the position to `quantity`, counting the orders already queued
+    pub fn target(&mut self, pair: &str, quantity: f64) {
+        let planned = self.position(pair) + self.pending.get(pair).copied().unwrap_or_default();
+        self.submit(pair, quantity - planned);
+    }
+
+    pub fn cancel(&mut self, pair: &str) {
+        self.pending.remove(pair);
+    }
+
+    pub fn cash(&self) -> f64 {
+        self.cash
+    }
+
+    pub fn position(&self, pair: &str) -> f64 {
+        self.positions.get(pair).map_or(0.0, |p| p.quantity)
+    }
+
+    /// Last close seen for the pair
+    pub fn price(&self, pair: &str) -> Option<f64> {
+        self.prices.get(pair).copied()
+    }
+
+    /// Cash plus the open positions valued at their last close
+    pub fn equity(&self) -> f64 {
+        self.cash
+            + self
+                .positions
+                .iter()
+                .map(|(pair, p)| p.quantity * self.prices.get(pair).copied().unwrap_or(p.avg_price))
+                .sum::<f64>()
+    }
+
+    pub fn fees_paid(&self) -> f64 {
+        self.fees_paid
+    }
+
+    pub fn fills(&self) -> &[Fill] {
+        &self.fills
+    }
+
+    pub fn closed_trades(&self) -> &[ClosedTrade] {
+        &self.closed
+    }
+
+    /// Fills the pair's queued order at the kline's open, then marks the pair at its close
+    pub(crate) fn on_kline(&mut self, kline: &Kline) {
+        if let Some(quantity) = self.pending.remove(&kline.pair) {
+            if quantity.abs() > f64::EPSILON {
+                self.fill(&kline.pair, kline.utc_begin, quantity, kline.o);
+            }
+        }
+        self.prices.insert(kline.pair.clone(), kline.c);
+    }
+
+    fn fill(&mut self, pair: &str, utc_begin: i64, quantity: f64, open: f64) {
+        let price = open * (1.0 + self.config.slippage * quantity.signum());
+        let fee = quantity.abs() * price * self.config.fee_rate;
+        self.cash -= quantity * price + fee;
+        self.fees_paid += fee;
+        self.fills.push(Fill {
+            pair: pair.to_string(),
+            utc_begin,
+            side: if quantity > 0.0 {
+                Side::Buy
+            } else {
+                Side::Sell
+            },
+            quantity: quantity.abs(),
+            price,
+            fee,
+        });
+
+        let position = self.positions.entry(pair.to_string()).or_default();
+        let held = position.quantity;
+        if held == 0.0 || held.signum() == quantity.signum() {
+            // Opening or adding: the entry price is averaged
+            let total = held.abs() + quantity.abs();
+            position.avg_price = (position.avg_price * held.abs() + price * quantity.abs()) / total;
+            if held == 0.0 {
+                position.opened_at = utc_begin;
+            }
+            position.quantity += quantity;
+            position.fees += fee;
+        } else {
+            // Reducing, closing or reversing
+            let closed = quantity.abs().min(held.abs());
+            let entry_fees = position.fees * closed / held.abs();
+            let exit_fee = fee * closed / quantity.abs();
+            self.closed.push(ClosedTrade {
+                pair: pair.to_string(),
+                entry_time: position.opened_at,
+                exit_time: utc_begin,
+                long: held > 0.0,
+                quantity: closed,
+                entry_price: position.avg_price,
+                exit_price: price,
+                pnl: (price - position.avg_price) * closed * held.signum() - entry_fees - exit_fee,
+            });
+            position.fees -= entry_fees;
+            position.quantity += quantity;
+
+            let reversed = quantity.abs() - closed;
+            if reversed > f64::EPSILON {
+                *position = Position {
+                    quantity: reversed * quantity.signum(),
+                    avg_price: price,
+                    opened_at: utc_begin,
+                    fees: fee - exit_fee,
+                };
+            } else if position.quantity.abs() <= f64::EPSILON {
+                self.positions.remove(pair);
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+pub mod broker;
+pub mod report;
+pub mod strategies;
+
+use futures_util::TryStreamExt;
+
+use crate::database::{KlineFilter, KlineStore};
+use crate::parser::kline::Kline;
+pub use broker::{BrokerConfig, ClosedTrade, Fill, Side, SimulatedBroker};
+pub use report::{BacktestReport, EquityPoint};
+pub use strategies::StrategySpec;
+
+/// Trading logic under test. It sees every kline once, in `utc_begin` order, after the
+/// broker has filled its earlier orders at the kline's open.
+pub trait Strategy: Send {
+    fn on_kline(&mut self, kline: &Kline, broker: &mut SimulatedBroker);
+}
+
+/*
+    Replays klines through a strategy and a simulated broker, recording the equity once per
+    `utc_begin` (several series may share one). Klines must come in `utc_begin` order, as the
+    stores return them; feed them with `step` or let `run_backtest` stream them from a store.
+*/
+pub struct Backtest {
+    broker: SimulatedBroker,
+    klines: u64,
+    peak: f64,
+    equity_curve: Vec<EquityPoint>,
+}
+
+impl Backtest {
+    pub fn new(config: BrokerConfig) -> Self {
+        Backtest {
+            broker: SimulatedBroker::new(config),
+            klines: 0,
+            peak: config.initial_cash,
+            equity_curve: Vec::new(),
+        }
+    }
+
+    pub fn step(&mut self, strategy: &mut dyn Strategy, kline: &Kline) {
+        self.broker.on_kline(kline);
+        strategy.on_kline(kline, &mut self.broker);
+        self.klines += 1;
+
+        let equity = self.broker.equity();
+        self.peak = self.peak.max(equity);
+        let point = EquityPoint {
+            utc_begin: kline.utc_begin,
+            cash: self.broker.cash(),
+            equity,
+            drawdown: if self.peak > 0.0 {
+                (self.peak - equity) / self.peak
+            } else {
+                0.0
+            },
+        };
+        match self.equity_curve.last_mut() {
+            Some(last) if last.utc_begin == kline.utc_begin => *last = point,
+            _ => self.equity_curve.push(point),
+        }
+    }
+
+    /// Orders still queued when the klines run out are never filled
+    pub fn finish(self) -> BacktestReport {
+        BacktestReport::new(self.broker, self.klines, self.equity_curve)
+    }
+}
+
+/// Runs the strategy over the stored klines matching the filter, streamed in `utc_begin` order
+pub async fn run_backtest(
+    store: &dyn KlineStore,
+    filter: &KlineFilter,
+    strategy: &mut dyn Strategy,
+    config: BrokerConfig,
+) -> Result<BacktestReport, sqlx::Error> {
+    let mut backtest = Backtest::new(config);
+    let mut klines = store.stream_klines(filter);
+    while let Some(kline) = klines.try_next().await? {
+        backtest.step(strategy, &kline);
+    }
+    Ok(backtest.finish())
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::MemoryStore;
+    use crate::parser::kline::VBS;
+
+    fn kline(utc_begin: i64, o: f64, c: f64) -> Kline {
+        Kline {
+            pair: "BTC_USDT".to_string(),
+            time_frame: "MINUTE_1".to_string(),
+            o,
+            h: o.max(c),
+            l: o.min(c),
+            c,
+            utc_begin,
+            volume_bs: VBS {
+                buy_base: 1.0,
+                sell_base: 2.0,
+                buy_quote: 0.0,
+                sell_quote: 0.0,
+            },
+        }
+    }
+
+    /// Buys one unit on the first kline, sells it on the third
+    struct Scripted;
+
+    impl Strategy for Scripted {
+        fn on_kline(&mut self, kline: &Kline, broker: &mut SimulatedBroker) {
+            match kline.utc_begin {
+                0 => broker.buy(&kline.pair, 1.0),
+                120_000 => broker.target(&kline.pair, 0.0),
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_backtest_fills_at_next_open_with_costs() {
+        let store = MemoryStore::new();
+        store
+            .upsert_klines(&[
+                kline(0, 100.0, 100.0),
+                kline(60_000, 100.0, 90.0),
+                kline(120_000, 90.0, 120.0),
+                kline(180_000, 110.0, 110.0),
+            ])
+            .await
+            .unwrap();
+        let config = BrokerConfig {
+            initial_cash: 1000.0,
+            fee_rate: 0.01,
+            slippage: 0.1,
+        };
+
+        let report = run_backtest(&store, &KlineFilter::default(), &mut Scripted, config)
+            .await
+            .unwrap();
+        assert_eq!(report.klines, 4);
+        assert_eq!(report.fills.len(), 2);
+        // Bought at the second open plus 10% slippage, sold at the fourth open minus 10%
+        assert!((report.fills[0].price - 110.0).abs() < 1e-9);
+        assert!((report.fills[0].fee - 1.1).abs() < 1e-9);
+        assert!((report.fills[1].price - 99.0).abs() < 1e-9);
+        assert_eq!(report.trades.len(), 1);
+        let pnl = 99.0 - 110.0 - 1.1 - 0.99;
+        assert!((report.trades[0].pnl - pnl).abs() < 1e-9);
+        assert!((report.final_equity - (1000.0 + pnl)).abs() < 1e-9);
+        assert_eq!(report.win_rate, 0.0);
+        // From the peak after the third kline (888.9 cash + 120) to the end
+        assert!((report.max_drawdown - (1008.9 - 986.91) / 1008.9).abs() < 1e-9);
+        assert_eq!(report.equity_curve.len(), 4);
+
+        let mut csv = Vec::new();
+        report.write_equity_csv(&mut csv).unwrap();
+        assert!(String::from_utf8(csv)
+            .unwrap()
+            .starts_with("utc_begin,cash,equity,drawdown\n0,1000.0,1000.0,0.0\n"));
+    }
+}
@@ -0,0 +1,88 @@
+use std::io::Write;
+
+use serde::Serialize;
+
+use super::broker::{BrokerConfig, ClosedTrade, Fill, SimulatedBroker};
+use crate::error::Error;
+
+/// Equity after the klines of one `utc_begin`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EquityPoint {
+    pub utc_begin: i64,
+    pub cash: f64,
+    pub equity: f64,
+    pub drawdown: f64, // fraction below the highest equity so far
+}
+
+/// Outcome of a backtest; ratios are fractions (0.05 = 5%)
+#[derive(Debug, Clone, Serialize)]
+pub struct BacktestReport {
+    pub config: BrokerConfig,
+    pub klines: u64,
+    pub final_equity: f64,
+    pub total_return: f64,
+    pub max_drawdown: f64,
+    pub win_rate: f64, // winning closed trades over closed trades, 0 without any
+    pub fees_paid: f64,
+    pub equity_curve: Vec<EquityPoint>,
+    pub trades: Vec<ClosedTrade>,
+    pub fills: Vec<Fill>,
+}
+
+impl BacktestReport {
+    pub(crate) fn new(
+        broker: SimulatedBroker,
+        klines: u64,
+        equity_curve: Vec<EquityPoint>,
+    ) -> Self {
+        let config = *broker.config();
+        let final_equity = broker.equity();
+        let trades = broker.closed_trades().to_vec();
+        let wins = trades.iter().filter(|t| t.pnl > 0.0).count();
+        BacktestReport {
+            config,
+            klines,
+            final_equity,
+            total_return: if config.initial_cash != 0.0 {
+                final_equity / config.initial_cash - 1.0
+            } else {
+                0.0
+            },
+            max_drawdown: equity_curve.iter().map(|p| p.drawdown).fold(0.0, f64::max),
+            win_rate: if trades.is_empty() {
+                0.0
+            } else {
+                wins as f64 / trades.len() as f64
+            },
+            fees_paid: broker.fees_paid(),
+            equity_curve,
+            trades,
+            fills: broker.fills().to_vec(),
+        }
+    }
+
+    /// The whole report as one JSON document
+    pub fn write_json<W: Write>(&self, out: W) -> Result<(), Error> {
+        serde_json::to_writer_pretty(out, self)?;
+        Ok(())
+    }
+
+    /// The equity curve as CSV: utc_begin, cash, equity, drawdown
+    pub fn write_equity_csv<W: Write>(&self, out: W) -> Result<(), Error> {
+        write_csv(out, &self.equity_curve)
+    }
+
+    /// The closed trades as CSV
+    pub fn write_trades_csv<W: Write>(&self, out: W) -> Result<(), Error> {
+        write_csv(out, &self.trades)
+    }
+}
+
+fn write_csv<W: Write, R: Serialize>(out: W, rows: &[R]) -> Result<(), Error> {
+    let mut csv = csv::Writer::from_writer(out);
+    for row in rows {
+        csv.serialize(row)?;
+    }
+    csv.flush()?;
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use super::broker::SimulatedBroker;
+use super::Strategy;
+use crate::indicators::formulas::Sma;
+use crate::parser::kline::Kline;
+
+/// The built-in strategies, as named on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrategySpec {
+    SmaCross { fast: usize, slow: usize },
+    BuyAndHold,
+}
+
+impl StrategySpec {
+    pub fn build(&self) -> Box<dyn Strategy> {
+        match *self {
+            StrategySpec::SmaCross { fast, slow } => Box::new(SmaCross::new(fast, slow)),
+            StrategySpec::BuyAndHold => Box::new(BuyAndHold::default()),
+        }
+    }
+}
+
+impl FromStr for StrategySpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, params) = s.split_once(':').unwrap_or((s, ""));
+        let periods: Vec<usize> = params
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(|p| p.parse().ok().filter(|n| *n > 0))
+            .collect::<Option<_>>()
+            .ok_or_else(|| format!("'{}' needs positive periods", s))?;
+        match (name.to_lowercase().as_str(), periods.as_slice()) {
+            ("sma-cross", [fast, slow]) if fast < slow => Ok(StrategySpec::SmaCross {
+                fast: *fast,
+                slow: *slow,
+            }),
+            ("sma-cross", _) => Err(format!(
+                "'{}' needs a fast and a slower period, e.g. sma-cross:10,30",
+                s
+            )),
+            ("buy-and-hold", []) => Ok(StrategySpec::BuyAndHold),
+            ("buy-and-hold", _) => Err("buy-and-hold takes no parameters".to_string()),
+            (other, _) => Err(format!(
+                "unknown strategy '{}', expected sma-cross or buy-and-hold",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for StrategySpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StrategySpec::SmaCross { fast, slow } => write!(f, "sma-cross:{},{}", fast, slow),
+            StrategySpec::BuyAndHold => write!(f, "buy-and-hold"),
+        }
+    }
+}
+
+/// What an all-in order may buy with the cash, leaving room for the fee and slippage
+fn affordable(broker: &SimulatedBroker, price: f64) -> f64 {
+    let config = broker.config();
+    (broker.cash() / (price * (1.0 + config.fee_rate + config.slippage))).max(0.0)
+}
+
+/// Long with all the cash while the fast SMA of the closes is above the slow one, flat otherwise
+#[derive(Debug, Clone)]
+pub struct SmaCross {
+    fast: usize,
+    slow: usize,
+    series: HashMap<String, (Sma, Sma)>,
+}
+
+impl SmaCross {
+    pub fn new(fast: usize, slow: usize) -> Self {
+        SmaCross {
+            fast,
+            slow,
+            series: HashMap::new(),
+        }
+    }
+}
+
+impl Strategy for SmaCross {
+    fn on_kline(&mut self, kline: &Kline, broker: &mut SimulatedBroker) {
+        let (fast, slow) = self
+            .series
+            .entry(kline.pair.clone())
+            .or_insert_with(|| (Sma::new(self.fast), Sma::new(self.slow)));
+        let (Some(fast), Some(slow)) = (fast.push(kline.c), slow.push(kline.c)) else {
+            return;
+        };
+        let held = broker.position(&kline.pair);
+        if fast > slow && held <= 0.0 {
+            let quantity = affordable(broker, kline.c);
+            broker.target(&kline.pair, quantity);
+        } else if fast < slow && held > 0.0 {
+            broker.target(&kline.pair, 0.0);
+        }
+    }
+}
+
+/// Buys with all the cash on the first kline and holds; the benchmark for the other strategies
+#[derive(Debug, Clone, Default)]
+pub struct BuyAndHold {
+    bought: bool,
+}
+
+impl Strategy for BuyAndHold {
+    fn on_kline(&mut self, kline: &Kline, broker: &mut SimulatedBroker) {
+        if !self.bought {
+            let quantity = affordable(broker, kline.c);
+            broker.buy(&kline.pair, quantity);
+            self.bought = true;
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::time::{self, Instant, MissedTickBehavior};
+use tracing::{error, info};
+
+use crate::database::Store;
+use crate::error::Error;
+use crate::metrics::unix_millis;
+use crate::parser::utc_date;
+
+/// Prefix of the backup directories; anything else in the backup directory is left alone
+const PREFIX: &str = "backup-";
+
+/// Where backups go and how many are kept
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    pub dir: PathBuf,
+    pub keep: usize,    // the latest backups kept, 0 keeps every one
+    pub compress: bool, // zstd the copies
+    pub zstd_level: i32,
+}
+
+impl BackupConfig {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        BackupConfig {
+            dir: dir.into(),
+            keep: 7,
+            compress: true,
+            zstd_level: 3,
+        }
+    }
+
+    pub fn with_keep(mut self, keep: usize) -> Self {
+        self.keep = keep;
+        self
+    }
+
+    pub fn with_compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+}
+
+/// A backup written
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupReport {
+    pub path: PathBuf,         // the backup directory
+    pub files: Vec<PathBuf>,   // the copies in it
+    pub bytes: u64,            // written, after compression
+    pub removed: Vec<PathBuf>, // older backups deleted to keep the latest `keep`
+}
+
+/// `backup-2025-01-24T09-13-11.000Z` for a backup started at `ts` (ms); names sort by time
+fn backup_name(ts: i64) -> String {
+    let (year, month, day) = utc_date(ts);
+    let ms = ts.rem_euclid(86_400_000);
+    format!(
+        "{}{:04}-{:02}-{:02}T{:02}-{:02}-{:02}.{:03}Z",
+        PREFIX,
+        year,
+        month,
+        day,
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1_000 % 60,
+        ms % 1_000
+    )
+}
+
+/// Replaces `path` by `{path}.zst`
+fn compress_file(path: &Path, level: i32) -> io::Result<PathBuf> {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".zst");
+    let compressed = PathBuf::from(name);
+    let mut input = File::open(path)?;
+    let mut encoder = zstd::Encoder::new(File::create(&compressed)?, level)?;
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    fs::remove_file(path)?;
+    Ok(compressed)
+}
+
+/*
+    Creates `{name}.partial` for the first of `{name}`, `{name}-1`, `{name}-2`... that neither
+    exists already nor is being written, so backups started in the same millisecond (or by
+    another collector sharing the directory) never end up renamed onto each other.
+    Returns the final path and the partial one.
+*/
+fn create_partial(dir: &Path, ts: i64) -> io::Result<(PathBuf, PathBuf)> {
+    fs::create_dir_all(dir)?;
+    let base = backup_name(ts);
+    for n in 0.. {
+        let name = match n {
+            0 => base.clone(),
+            n => format!("{}-{}", base, n),
+        };
+        let path = dir.join(&name);
+        if path.exists() {
+            continue;
+        }
+        let partial = dir.join(format!("{}.partial", name));
+        match fs::create_dir(&partial) {
+            Ok(()) => return Ok((path, partial)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    unreachable!("every backup name is taken")
+}
+
+/// Runs blocking file system work off the runtime threads
+async fn blocking<T, F>(f: F) -> io::Result<T>
+where
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(io::Error::other)?
+}
+
+/// A backup directory being written, deleted unless it was completed
+struct Partial(Option<PathBuf>);
+
+impl Partial {
+    /// Renames the directory to `path` and keeps it
+    fn complete(mut self, path: &Path) -> io::Result<()> {
+        if let Some(partial) = &self.0 {
+            fs::rename(partial, path)?;
+        }
+        self.0 = None;
+        Ok(())
+    }
+}
+
+impl Drop for Partial {
+    fn drop(&mut self) {
+        if let Some(partial) = self.0.take() {
+            let _ = fs::remove_dir_all(partial);
+        }
+    }
+}
+
+/// The backups in `dir`, oldest first
+pub fn list_backups(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_dir()
+                && path.file_name().is_some_and(|name| {
+                    let name = name.to_string_lossy();
+                    name.starts_with(PREFIX) && !name.ends_with(".partial")
+                })
+        })
+        .collect();
+    backups.sort();
+    Ok(backups)
+}
+
+/// Deletes the backups in `dir` beyond the latest `keep`, returning them
+fn remove_old_backups(dir: &Path, keep: usize) -> io::Result<Vec<PathBuf>> {
+    let backups = list_backups(dir)?;
+    let excess = backups.len().saturating_sub(keep);
+    let mut removed = Vec::new();
+    for old in backups.into_iter().take(excess) {
+        fs::remove_dir_all(&old)?;
+        info!("Removed the backup {}", old.display());
+        removed.push(old);
+    }
+    Ok(removed)
+}
+
+/*
+    Writes a consistent copy of the database while the collector keeps writing: SQLite copies
+    with `VACUUM INTO` from a read transaction, so the copy is the database as of its start,
+    compacted. Each backup is a directory `backup-{UTC time}` in `config.dir` holding the copy
+    named like `db_name` (and the partitions of a partitioned database), each file zstd compressed
+    with `compress`. It is written as `{name}.partial`, deleted on failure and renamed once
+    complete, so a backup cut short is never taken for one. Then the backups beyond the latest
+    `keep` are deleted. Each file of a partitioned database is copied in a transaction of its own:
+    every copy is consistent, but rows written meanwhile may be in a later file and not an earlier one.
+*/
+pub async fn backup(
+    store: &dyn Store,
+    db_name: &str,
+    config: &BackupConfig,
+) -> Result<BackupReport, Error> {
+    let dir = config.dir.clone();
+    let ts = unix_millis();
+    let (path, partial) = blocking(move || create_partial(&dir, ts)).await?;
+    let guard = Partial(Some(partial.clone()));
+
+    let copies = store.backup(&partial.join(db_name)).await?;
+    let mut files = Vec::new();
+    let mut bytes = 0;
+    for copy in copies {
+        let level = config.compress.then_some(config.zstd_level);
+        let (file, len) = blocking(move || {
+            let file = match level {
+                Some(level) => compress_file(&copy, level)?,
+                None => copy,
+            };
+            let len = fs::metadata(&file)?.len();
+            Ok((file, len))
+        })
+        .await?;
+        bytes += len;
+        let relative = file.strip_prefix(&partial).unwrap_or(&file).to_path_buf();
+        files.push(path.join(relative));
+    }
+    let target = path.clone();
+    blocking(move || guard.complete(&target)).await?;
+    info!(
+        "Backed up the database into {} ({} bytes)",
+        path.display(),
+        bytes
+    );
+
+    let removed = if config.keep > 0 {
+        let (dir, keep) = (config.dir.clone(), config.keep);
+        blocking(move || remove_old_backups(&dir, keep)).await?
+    } else {
+        Vec::new()
+    };
+    Ok(BackupReport {
+        path,
+        files,
+        bytes,
+        removed,
+    })
+}
+
+/*
+    Backs up every `interval`, the first time one interval after the start, so restarts don't
+    pile up backups. Runs until the task is dropped; a failed backup is logged and the next
+    one tries again.
+*/
+pub async fn run_backups(
+    store: Arc<dyn Store>,
+    db_name: String,
+    config: BackupConfig,
+    interval: Duration,
+) {
+    let mut timer = time::interval_at(Instant::now() + interval, interval);
+    timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        timer.tick().await;
+        if let Err(err) = backup(store.as_ref(), &db_name, &config).await {
+            error!("Backup failed: {}", err);
+        }
+    }
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{open_store, KlineFilter, SqliteTuning};
+    use crate::parser::kline::Kline;
+
+    #[tokio::test]
+    async fn test_backup_compress_and_keep() {
+        let dir = std::env::temp_dir().join(format!("backup-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("db.sqlite");
+        let store = open_store(&db.to_string_lossy(), &SqliteTuning::default())
+            .await
+            .unwrap();
+        let kline = Kline::sample("BTC_USDT", "MINUTE_1", 60_000);
+        store.save_klines(&[kline]).await.unwrap();
+
+        let config = BackupConfig::new(dir.join("backups")).with_keep(2);
+        let mut reports = Vec::new();
+        for _ in 0..3 {
+            reports.push(backup(store.as_ref(), "db.sqlite", &config).await.unwrap());
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+        // The first one went to keep the latest two
+        let backups = list_backups(&config.dir).unwrap();
+        assert_eq!(
+            backups,
+            vec![reports[1].path.clone(), reports[2].path.clone()]
+        );
+        assert_eq!(reports[2].removed, vec![reports[0].path.clone()]);
+
+        // The compressed copy is a database with the kline
+        let file = &reports[2].files[0];
+        assert!(file.ends_with("db.sqlite.zst"));
+        let copy = zstd::decode_all(File::open(file).unwrap()).unwrap();
+        let restored = dir.join("restored.sqlite");
+        fs::write(&restored, copy).unwrap();
+        let restored = open_store(&restored.to_string_lossy(), &SqliteTuning::default())
+            .await
+            .unwrap();
+        let klines = restored.load_klines(&KlineFilter::default()).await.unwrap();
+        assert_eq!((klines.len(), klines[0].utc_begin), (1, 60_000));
+
+        assert_eq!(
+            backup_name(1737709991123),
+            "backup-2025-01-24T09-13-11.123Z"
+        );
+
+        // Backups started in the same millisecond get names of their own
+        let (first, _) = create_partial(&dir, 1737709991123).unwrap();
+        let (second, _) = create_partial(&dir, 1737709991123).unwrap();
+        assert!(first.ends_with("backup-2025-01-24T09-13-11.123Z"));
+        assert!(second.ends_with("backup-2025-01-24T09-13-11.123Z-1"));
+
+        // A failed backup leaves nothing behind
+        let memory = crate::database::MemoryStore::new();
+        assert!(backup(&memory, "db.sqlite", &config).await.is_err());
+        assert_eq!(fs::read_dir(&config.dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
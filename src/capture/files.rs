@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+
+use tracing::{debug, error};
+
+use super::{CaptureCommand, CaptureConfig, CaptureSource};
+use crate::parser::utc_date;
+
+const HOUR_MILLIS: i64 = 3_600_000;
+
+/// `2025-01-24T09` for the UTC hour starting at `hour * HOUR_MILLIS`
+fn hour_name(hour: i64) -> String {
+    let (year, month, day) = utc_date(hour * HOUR_MILLIS);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}",
+        year,
+        month,
+        day,
+        hour.rem_euclid(24)
+    )
+}
+
+/// The file records of one source go to: a new one every UTC hour and whenever it grows too big
+struct RotatingFile {
+    source: CaptureSource,
+    hour: i64,
+    index: u32,
+    written: u64, // compressed bytes in the current file
+    file: Option<File>,
+    pending: Vec<u8>, // JSON lines not compressed yet
+}
+
+impl RotatingFile {
+    fn new(source: CaptureSource) -> Self {
+        RotatingFile {
+            source,
+            hour: i64::MIN,
+            index: 0,
+            written: 0,
+            file: None,
+            pending: Vec::new(),
+        }
+    }
+
+    fn path(&self, config: &CaptureConfig) -> PathBuf {
+        config.dir.join(format!(
+            "{}-{}-{}.jsonl.zst",
+            self.source,
+            hour_name(self.hour),
+            self.index
+        ))
+    }
+
+    /// Writes the pending lines as one zstd frame; frames appended to a file decode as one stream
+    fn write_pending(&mut self, config: &CaptureConfig) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        if self.file.is_none() || self.written >= config.max_file_bytes {
+            if self.file.is_some() {
+                self.index += 1;
+            }
+            let path = self.path(config);
+            debug!("Capturing into {}", path.display());
+            self.file = Some(OpenOptions::new().create(true).append(true).open(path)?);
+            self.written = 0;
+        }
+        let frame = zstd::bulk::compress(&self.pending, config.zstd_level)?;
+        self.pending.clear();
+        if let Some(file) = &mut self.file {
+            file.write_all(&frame)?;
+        }
+        self.written += frame.len() as u64;
+        Ok(())
+    }
+
+    fn push(&mut self, config: &CaptureConfig, ts: i64, line: &[u8]) -> io::Result<()> {
+        let hour = ts.div_euclid(HOUR_MILLIS);
+        if hour != self.hour {
+            self.write_pending(config)?;
+            self.hour = hour;
+            self.index = 0;
+            self.file = None;
+        }
+        self.pending.extend_from_slice(line);
+        self.pending.push(b'\n');
+        if self.pending.len() >= config.frame_bytes {
+            self.write_pending(config)?;
+        }
+        Ok(())
+    }
+}
+
+/// The writer thread: records are compressed every `flush_interval`, on `flush`, and at the end
+pub(super) fn run_writer(config: CaptureConfig, receiver: Receiver<CaptureCommand>) {
+    if let Err(err) = fs::create_dir_all(&config.dir) {
+        error!(
+            "Can't create the capture directory {}: {}",
+            config.dir.display(),
+            err
+        );
+    }
+    let mut files: HashMap<CaptureSource, RotatingFile> = HashMap::new();
+    let write_all = |files: &mut HashMap<CaptureSource, RotatingFile>| -> io::Result<()> {
+        for file in files.values_mut() {
+            file.write_pending(&config)?;
+        }
+        Ok(())
+    };
+
+    loop {
+        let result = match receiver.recv_timeout(config.flush_interval) {
+            Ok(CaptureCommand::Record(record)) => serde_json::to_vec(&record)
+                .map_err(io::Error::from)
+                .and_then(|line| {
+                    files
+                        .entry(record.source)
+                        .or_insert_with(|| RotatingFile::new(record.source))
+                        .push(&config, record.ts, &line)
+                }),
+            Ok(CaptureCommand::Flush(reply)) => {
+                let result = write_all(&mut files);
+                let _ = reply.send(result.as_ref().map(|_| ()).map_err(|e| e.to_string()));
+                result
+            }
+            Err(RecvTimeoutError::Timeout) => write_all(&mut files),
+            Err(RecvTimeoutError::Disconnected) => {
+                if let Err(err) = write_all(&mut files) {
+                    error!("Failed to write the capture: {}", err);
+                }
+                break;
+            }
+        };
+        if let Err(err) = result {
+            error!("Failed to write the capture: {}", err);
+        }
+    }
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hour_name() {
+        assert_eq!(hour_name(0), "1970-01-01T00");
+        assert_eq!(hour_name(1737709991000 / HOUR_MILLIS), "2025-01-24T09");
+        assert_eq!(hour_name(951_825_600_000 / HOUR_MILLIS), "2000-02-29T12");
+    }
+}
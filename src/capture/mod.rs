@@ -0,0 +1,327 @@
+mod files;
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::database::KlineStore;
+use crate::error::Error;
+use crate::http_client::http_client::{RestClient, RestResponse};
+use crate::metrics::unix_millis;
+use crate::parser::{kline::Kline, KlineParser};
+
+/// Where a raw message came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureSource {
+    Rest,
+    Ws,
+}
+
+impl fmt::Display for CaptureSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureSource::Rest => write!(f, "rest"),
+            CaptureSource::Ws => write!(f, "ws"),
+        }
+    }
+}
+
+/// One raw message as received, a line of a capture file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureRecord {
+    pub ts: i64, // ms, when it was received
+    pub source: CaptureSource,
+    pub url: String, // the REST request, or the WebSocket it came through
+    pub data: String,
+}
+
+impl CaptureRecord {
+    /// The record of a capture line; None for anything else, e.g. a bare WebSocket message
+    pub fn parse(line: &str) -> Option<CaptureRecord> {
+        serde_json::from_str(line).ok()
+    }
+}
+
+/// Where and how captures are written
+#[derive(Debug, Clone)]
+pub struct CaptureConfig {
+    pub dir: PathBuf,
+    pub max_file_bytes: u64, // compressed; a file also ends with its UTC hour
+    pub zstd_level: i32,     // 1 (fastest) to 22, 0 is zstd's default
+    pub frame_bytes: usize,  // uncompressed lines compressed together
+    pub flush_interval: Duration, // longest time a record waits before being compressed
+    pub queue: usize,        // records waiting for the writer before new ones are dropped
+}
+
+impl CaptureConfig {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        CaptureConfig {
+            dir: dir.into(),
+            max_file_bytes: 256 * 1024 * 1024,
+            zstd_level: 3,
+            frame_bytes: 1024 * 1024,
+            flush_interval: Duration::from_secs(1),
+            queue: 100_000,
+        }
+    }
+
+    pub fn with_max_file_bytes(mut self, max_file_bytes: u64) -> Self {
+        self.max_file_bytes = max_file_bytes;
+        self
+    }
+
+    pub fn with_zstd_level(mut self, zstd_level: i32) -> Self {
+        self.zstd_level = zstd_level;
+        self
+    }
+}
+
+enum CaptureCommand {
+    Record(CaptureRecord),
+    Flush(mpsc::Sender<Result<(), String>>),
+}
+
+/*
+    Archives raw REST responses and WebSocket frames, so what the exchange sent can be looked at
+    and parsed again. Files are `{source}-{YYYY-MM-DD}T{HH}-{n}.jsonl.zst` in the directory: JSON
+    Lines of `CaptureRecord`, compressed with zstd in independent frames, a new file every UTC
+    hour and whenever one reaches `max_file_bytes`. A file cut short (a crash) loses its last
+    frame at most.
+    Compression and writes happen on a thread of their own; recording never waits for them:
+    when the queue is full, records are dropped and counted.
+*/
+#[derive(Clone)]
+pub struct RawCapture {
+    sender: SyncSender<CaptureCommand>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl RawCapture {
+    /// Starts the writer; it stops once every clone of the handle is dropped, after writing everything
+    pub fn spawn(config: CaptureConfig) -> Result<Self, Error> {
+        let (sender, receiver) = mpsc::sync_channel(config.queue);
+        info!("Capturing raw messages into {}", config.dir.display());
+        thread::Builder::new()
+            .name("raw-capture".to_string())
+            .spawn(move || files::run_writer(config, receiver))?;
+        Ok(RawCapture {
+            sender,
+            dropped: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    pub fn record(&self, source: CaptureSource, url: &str, data: &str) {
+        let record = CaptureRecord {
+            ts: unix_millis(),
+            source,
+            url: url.to_string(),
+            data: data.to_string(),
+        };
+        match self.sender.try_send(CaptureCommand::Record(record)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                if dropped == 1 || dropped.is_multiple_of(10_000) {
+                    warn!("Capture queue full, {} records dropped so far", dropped);
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => warn!("Capture writer stopped, record dropped"),
+        }
+    }
+
+    /// Records dropped because the writer couldn't keep up
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Waits until every record so far is written
+    pub async fn flush(&self) -> Result<(), Error> {
+        let sender = self.sender.clone();
+        tokio::task::spawn_blocking(move || {
+            let (reply, done) = mpsc::channel();
+            sender
+                .send(CaptureCommand::Flush(reply))
+                .map_err(|_| io::Error::other("the capture writer stopped"))?;
+            done.recv()
+                .map_err(|_| io::Error::other("the capture writer stopped"))?
+                .map_err(io::Error::other)
+        })
+        .await
+        .map_err(io::Error::other)??;
+        Ok(())
+    }
+}
+
+/// A REST client that captures every response of the client it wraps
+pub struct CapturingClient {
+    inner: Box<dyn RestClient>,
+    capture: RawCapture,
+}
+
+impl CapturingClient {
+    pub fn new(inner: Box<dyn RestClient>, capture: RawCapture) -> Self {
+        CapturingClient { inner, capture }
+    }
+}
+
+impl RestClient for CapturingClient {
+    fn get<'a>(&'a self, url: &'a str) -> RestResponse<'a> {
+        Box::pin(async move {
+            let body = self.inner.get(url).await?;
+            self.capture.record(CaptureSource::Rest, url, &body);
+            Ok(body)
+        })
+    }
+}
+
+/// Reads a capture, or any JSON Lines file, decompressing it when its name ends with `.zst`
+pub fn open_capture(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let file = File::open(path)?;
+    if path.extension().is_some_and(|ext| ext == "zst") {
+        Ok(Box::new(BufReader::new(zstd::Decoder::new(file)?)))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+/// What re-parsing a capture went through
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ReparseReport {
+    pub responses: u64,  // captured candle responses
+    pub klines: u64,     // klines parsed and upserted
+    pub unreadable: u64, // lines or responses that could not be parsed
+}
+
+/// The pair of a candles request such as `{base_url}/markets/BTC_USDT/candles?...`
+fn candles_pair(url: &str) -> Option<&str> {
+    let (_, rest) = url.split_once("/markets/")?;
+    let (pair, rest) = rest.split_once('/')?;
+    rest.starts_with("candles").then_some(pair)
+}
+
+/*
+    Parses the captured REST candle responses again with the current `KlineParser`, e.g. after
+    a parser fix, and upserts the klines so they replace what the old parser stored. Other
+    records are skipped; trades captured from the WebSocket are rebuilt with `replay` instead.
+*/
+pub async fn reparse_candles<R: BufRead>(
+    reader: R,
+    store: &dyn KlineStore,
+) -> Result<ReparseReport, Error> {
+    let parser = KlineParser::new();
+    let mut report = ReparseReport::default();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some(record) = CaptureRecord::parse(&line) else {
+            warn!("Capture line {} is not a record", index + 1);
+            report.unreadable += 1;
+            continue;
+        };
+        let Some(pair) = candles_pair(&record.url).filter(|_| record.source == CaptureSource::Rest)
+        else {
+            continue;
+        };
+        report.responses += 1;
+        match parser.parse(&record.data, pair) {
+            Ok(grouped) => {
+                let klines: Vec<Kline> = grouped.into_values().flatten().collect();
+                store.upsert_klines(&klines).await?;
+                report.klines += klines.len() as u64;
+            }
+            Err(err) => {
+                warn!("Capture line {}: {}", index + 1, err);
+                report.unreadable += 1;
+            }
+        }
+    }
+    info!(
+        "Re-parsed {} candle responses into {} klines ({} unreadable)",
+        report.responses, report.klines, report.unreadable
+    );
+    Ok(report)
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{KlineFilter, MemoryStore};
+
+    struct Fixed(&'static str);
+
+    impl RestClient for Fixed {
+        fn get<'a>(&'a self, _url: &'a str) -> RestResponse<'a> {
+            Box::pin(async move { Ok(self.0.to_string()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_capture_and_reparse() {
+        let dir = std::env::temp_dir().join(format!("capture-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let capture = RawCapture::spawn(CaptureConfig::new(&dir)).unwrap();
+
+        let candles = r#"[["99","110","100","105","1000","10","400","4",7,119999,"100","MINUTE_1",60000,119999]]"#;
+        let client = CapturingClient::new(Box::new(Fixed(candles)), capture.clone());
+        let url = "https://rest/markets/BTC_USDT/candles?interval=MINUTE_1&limit=3";
+        assert_eq!(client.get(url).await.unwrap(), candles);
+        capture.record(CaptureSource::Ws, "wss://ws", r#"{"event":"pong"}"#);
+        capture.flush().await.unwrap();
+
+        // One file per source, written and read back through zstd
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        paths.sort();
+        assert_eq!(paths.len(), 2);
+        let name = paths[0].file_name().unwrap().to_string_lossy().to_string();
+        assert!(name.starts_with("rest-") && name.ends_with("-0.jsonl.zst"));
+
+        let store = MemoryStore::new();
+        let report = reparse_candles(open_capture(&paths[0]).unwrap(), &store)
+            .await
+            .unwrap();
+        assert_eq!(
+            (report.responses, report.klines, report.unreadable),
+            (1, 1, 0)
+        );
+        let klines = store.load_klines(&KlineFilter::default()).await.unwrap();
+        assert_eq!(
+            (klines[0].pair.as_str(), klines[0].utc_begin),
+            ("BTC_USDT", 60_000)
+        );
+
+        let ws: Vec<String> = open_capture(&paths[1])
+            .unwrap()
+            .lines()
+            .map(Result::unwrap)
+            .collect();
+        let record = CaptureRecord::parse(&ws[0]).unwrap();
+        assert_eq!(
+            (record.source, record.data.as_str()),
+            (CaptureSource::Ws, r#"{"event":"pong"}"#)
+        );
+        assert_eq!(
+            candles_pair("https://rest/markets/BTC_USDT/ticker24h"),
+            None
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,252 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rust_kline_ws::backtest::StrategySpec;
+use rust_kline_ws::export::ExportFormat;
+use rust_kline_ws::import::ImportFormat;
+use rust_kline_ws::replay::ReplayPace;
+
+/// Command line of the binary; without a command the collector runs, as before
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Collect klines from the exchange (the default)
+    Run,
+    /// Export stored klines or trades to CSV, JSON Lines or Parquet
+    Export(ExportArgs),
+    /// Import klines from Poloniex/Binance CSV or ZIP files
+    Import(ImportArgs),
+    /// Ask a running collector whether its data is fresh; exits with 69 when it isn't
+    Status(StatusArgs),
+    /// Compute indicators over stored klines and store them
+    Indicators(IndicatorsArgs),
+    /// Replay stored klines through a strategy and report its equity, drawdown and trades
+    Backtest(BacktestArgs),
+    /// Rebuild candles from recorded trades through the aggregator and export them
+    Replay(ReplayArgs),
+    /// Compare local candles with the exchange's; exits with 65 when discrepancies remain
+    Reconcile(ReconcileArgs),
+    /// Parse captured REST candle responses again and upsert the klines
+    Reparse(ReparseArgs),
+    /// Apply the RETENTION rules once: downsample, delete and vacuum
+    Retention,
+    /// List, merge or archive the files of a partitioned SQLite database
+    Partitions(PartitionsArgs),
+    /// Back up the SQLite database, even while the collector writes it
+    Backup(BackupArgs),
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportTable {
+    Klines,
+    Trades,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Which table to export
+    #[arg(value_enum)]
+    pub table: ExportTable,
+    /// csv, jsonl or parquet
+    #[arg(long, short)]
+    pub format: ExportFormat,
+    /// File to write, `-` for stdout
+    #[arg(long, short)]
+    pub output: PathBuf,
+    #[arg(long)]
+    pub pair: Option<String>,
+    /// Klines only
+    #[arg(long)]
+    pub timeframe: Option<String>,
+    /// Lower bound of `utc_begin` / `timestamp`, inclusive
+    #[arg(long)]
+    pub from: Option<i64>,
+    /// Upper bound of `utc_begin` / `timestamp`, inclusive
+    #[arg(long)]
+    pub to: Option<i64>,
+}
+
+#[derive(Args)]
+pub struct ImportArgs {
+    /// CSV or ZIP files
+    #[arg(required = true)]
+    pub files: Vec<PathBuf>,
+    /// poloniex or binance
+    #[arg(long, short)]
+    pub format: ImportFormat,
+    /// Pair to store the klines under, when the file doesn't tell
+    #[arg(long)]
+    pub pair: Option<String>,
+    /// Time frame to store the klines under, when the file doesn't tell
+    #[arg(long)]
+    pub timeframe: Option<String>,
+}
+
+#[derive(Args)]
+pub struct StatusArgs {
+    /// Base URL of the collector's health checks, `http://` + HEALTH_ADDR by default
+    #[arg(long)]
+    pub url: Option<String>,
+}
+
+#[derive(Args)]
+pub struct IndicatorsArgs {
+    /// `;`-separated list such as `sma:20;rsi:14;macd:12,26,9`, INDICATORS by default
+    #[arg(long, short)]
+    pub indicators: Option<String>,
+    #[arg(long)]
+    pub pair: Option<String>,
+    #[arg(long)]
+    pub timeframe: Option<String>,
+    /// Lower bound of `utc_begin`, inclusive; earlier klines don't warm the indicators up
+    #[arg(long)]
+    pub from: Option<i64>,
+    /// Upper bound of `utc_begin`, inclusive
+    #[arg(long)]
+    pub to: Option<i64>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ReportFormat {
+    /// The whole report
+    Json,
+    /// The equity curve
+    Csv,
+}
+
+#[derive(Args)]
+pub struct BacktestArgs {
+    /// `sma-cross:FAST,SLOW` or `buy-and-hold`
+    #[arg(long, short)]
+    pub strategy: StrategySpec,
+    #[arg(long)]
+    pub pair: Option<String>,
+    #[arg(long)]
+    pub timeframe: Option<String>,
+    /// Lower bound of `utc_begin`, inclusive
+    #[arg(long)]
+    pub from: Option<i64>,
+    /// Upper bound of `utc_begin`, inclusive
+    #[arg(long)]
+    pub to: Option<i64>,
+    #[arg(long, default_value_t = 10_000.0)]
+    pub cash: f64,
+    /// Fee as a fraction of the filled notional
+    #[arg(long, default_value_t = 0.001)]
+    pub fee: f64,
+    /// Price move against every order, as a fraction
+    #[arg(long, default_value_t = 0.0005)]
+    pub slippage: f64,
+    #[arg(long, short, value_enum, default_value = "json")]
+    pub format: ReportFormat,
+    /// File to write the report to, `-` for stdout
+    #[arg(long, short, default_value = "-")]
+    pub output: PathBuf,
+    /// Also write the closed trades as CSV to this file
+    #[arg(long)]
+    pub trades: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct ReplayArgs {
+    /// JSON Lines capture of raw WebSocket messages or capture records, `.zst` compressed or not;
+    /// the stored `recent_trades` rows by default
+    #[arg(long)]
+    pub capture: Option<PathBuf>,
+    #[arg(long)]
+    pub pair: Option<String>,
+    /// Lower bound of the trade timestamps, inclusive
+    #[arg(long)]
+    pub from: Option<i64>,
+    /// Upper bound of the trade timestamps, inclusive
+    #[arg(long)]
+    pub to: Option<i64>,
+    /// Time frames to build, comma-separated
+    #[arg(long, value_delimiter = ',', default_value = "MINUTE_1")]
+    pub timeframes: Vec<String>,
+    /// `realtime`, `max` or a speed such as `10x`
+    #[arg(long, default_value = "max")]
+    pub pace: ReplayPace,
+    /// csv, jsonl or parquet
+    #[arg(long, short, default_value = "jsonl")]
+    pub format: ExportFormat,
+    /// File to write the rebuilt klines to, `-` for stdout
+    #[arg(long, short, default_value = "-")]
+    pub output: PathBuf,
+}
+
+#[derive(Args)]
+pub struct ReconcileArgs {
+    #[arg(long)]
+    pub pair: String,
+    #[arg(long)]
+    pub timeframe: String,
+    /// Lower bound of `utc_begin`, inclusive
+    #[arg(long)]
+    pub from: i64,
+    /// Upper bound of `utc_begin`, inclusive; now by default
+    #[arg(long)]
+    pub to: Option<i64>,
+    /// Largest relative difference of a field that still matches
+    #[arg(long, default_value_t = 0.0001)]
+    pub tolerance: f64,
+    /// Compare candles rebuilt from the stored trades instead of the stored candles
+    #[arg(long)]
+    pub rebuild: bool,
+    /// Store the exchange version of every missing or mismatching candle
+    #[arg(long)]
+    pub replace: bool,
+    /// Poloniex REST base URL, POLONIEX_REST_URL_BASE by default
+    #[arg(long)]
+    pub rest_url: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ReparseArgs {
+    /// Capture files (`rest-*.jsonl.zst` under CAPTURE_DIR), in the order to apply them
+    #[arg(required = true)]
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct PartitionsArgs {
+    #[command(subcommand)]
+    pub action: PartitionsAction,
+}
+
+#[derive(Subcommand)]
+pub enum PartitionsAction {
+    /// Print every partition with its size, row counts and time range
+    List,
+    /// Move the rows of partitions into the main file and delete them
+    Merge {
+        #[arg(required = true)]
+        keys: Vec<String>,
+    },
+    /// Compress partitions into a directory and delete them; they are no longer queried
+    Archive {
+        #[arg(required = true)]
+        keys: Vec<String>,
+        #[arg(long)]
+        dir: PathBuf,
+    },
+}
+
+#[derive(Args)]
+pub struct BackupArgs {
+    /// Directory of the backups, BACKUP_DIR by default
+    #[arg(long)]
+    pub dir: Option<PathBuf>,
+    /// The latest backups kept, BACKUP_KEEP by default; 0 keeps every one
+    #[arg(long)]
+    pub keep: Option<usize>,
+    /// Don't compress the copies, whatever BACKUP_COMPRESS says
+    #[arg(long)]
+    pub no_compress: bool,
+}
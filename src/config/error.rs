@@ -0,0 +1,14 @@
+use thiserror::Error;
+
+/// Problems with the environment configuration
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("{0} must be set")]
+    Missing(&'static str),
+    #[error("{name} has an invalid value '{value}': {reason}")]
+    Invalid {
+        name: &'static str,
+        value: String,
+        reason: String,
+    },
+}
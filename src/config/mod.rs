@@ -1 +1,3 @@
+pub mod error;
 pub mod settings;
+pub use error::ConfigError;
@@ -1,5 +1,11 @@
 use dotenvy::dotenv;
-use std::env;
+use std::{env, fmt, net::SocketAddr, path::PathBuf, str::FromStr};
+
+use super::ConfigError;
+use crate::database::VacuumMode;
+use crate::indicators::{parse_specs, IndicatorSpec};
+use crate::retention::{parse_rules, RetentionRule};
+use crate::sinks::{parse_sink_rules, SinkRule};
 
 pub struct Settings {
     pub exchange: String,
@@ -8,39 +14,338 @@ pub struct Settings {
     pub poloniex_ws_url: String,
     pub binance_rest_url: String,
     pub binance_ws_url: String,
-    pub db_url: String,
-    pub symbols: Vec<String>,
+    pub symbols: Vec<String>, // pairs and patterns (`*_USDT`, `top:10`), expanded at startup
+    pub symbols_sync: bool,   // optional, fetch the markets metadata at startup (on by default)
+    pub trades_ws: bool,      // optional TRADES_WS, live trades from the `trades` WebSocket channel
     pub timeframes: Vec<String>,
+    pub poll_interval_secs: u64, // optional POLL_INTERVAL_SECS, how often klines are fetched again while streaming
+    pub storage: StorageSettings,
+    pub api_addr: Option<SocketAddr>, // optional, the query API is served only when set
+    pub live_addr: Option<SocketAddr>, // optional, the live WebSocket feed is served only when set
+    pub metrics_addr: Option<SocketAddr>, // optional, `/metrics` is served only when set
+    pub health: HealthSettings,
+    pub order_book: OrderBookSettings,
+    pub tickers: TickerSettings,
+    pub capture: CaptureSettings,
+    pub retention: RetentionSettings,
+    pub backup: BackupSettings,
+    pub indicators: Vec<IndicatorSpec>, // optional INDICATORS, computed over the collected klines and stored
+    pub kline_sinks: Vec<SinkRule>, // optional KLINE_SINKS, where particular series go instead of everywhere
+    pub sinks: SinkSettings,
+}
+
+/// 24h tickers, polled over REST and/or received from the `ticker` WebSocket channel
+pub struct TickerSettings {
+    pub rest: bool,            // optional TICKERS_REST, off by default
+    pub ws: bool,              // optional TICKERS_WS, off by default
+    pub rest_endpoint: String, // optional, with {base_url} and {symbol} like the candles endpoint
+    pub interval_ms: u64,      // optional, how often tickers are polled and stored
+}
+
+impl TickerSettings {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        dotenv().ok(); // Loading variables from .env
+
+        Ok(TickerSettings {
+            rest: optional("TICKERS_REST", false)?,
+            ws: optional("TICKERS_WS", false)?,
+            rest_endpoint: optional(
+                "POLONIEX_REST_TICKER_ENDPOINT",
+                "{base_url}/markets/{symbol}/ticker24h".to_string(),
+            )?,
+            interval_ms: optional("TICKERS_INTERVAL_MS", 10_000)?,
+        })
+    }
+}
+
+/// Archival of the raw REST responses and WebSocket frames
+pub struct CaptureSettings {
+    pub dir: Option<PathBuf>, // optional CAPTURE_DIR, raw messages are captured only when set
+    pub max_file_mb: u64,     // optional, size (compressed) at which a capture file is rotated
+    pub zstd_level: i32,      // optional, compression level
+}
+
+impl CaptureSettings {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        dotenv().ok(); // Loading variables from .env
+
+        Ok(CaptureSettings {
+            dir: maybe("CAPTURE_DIR")?,
+            max_file_mb: optional("CAPTURE_MAX_FILE_MB", 256)?,
+            zstd_level: optional("CAPTURE_ZSTD_LEVEL", 3)?,
+        })
+    }
+}
+
+/// What is deleted from the database as it ages, and how often
+pub struct RetentionSettings {
+    pub rules: Vec<RetentionRule>, // optional RETENTION, nothing is deleted without it
+    pub interval_secs: u64,        // optional, how often the rules are applied
+    pub vacuum: VacuumMode, // optional, how the freed space is given back, incremental by default
+}
+
+impl RetentionSettings {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        dotenv().ok(); // Loading variables from .env
+
+        Ok(RetentionSettings {
+            rules: list("RETENTION", parse_rules)?,
+            interval_secs: optional("RETENTION_INTERVAL_SECS", 3_600)?,
+            vacuum: optional("RETENTION_VACUUM", VacuumMode::Incremental)?,
+        })
+    }
+}
+
+/// The sinks KLINE_SINKS may send series to, besides the database, the live feed and the indicators
+pub struct SinkSettings {
+    pub jsonl_dir: Option<PathBuf>, // optional SINK_JSONL_DIR, needed by the `jsonl` sink
+    pub jsonl_max_file_mb: u64,     // optional, size at which a JSON Lines file is rotated
+    pub redis_url: Option<String>,  // optional SINK_REDIS_URL, needed by the `redis` sink
+    pub redis_channel: String,      // optional, with {pair} and {timeframe}
+}
+
+impl SinkSettings {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        dotenv().ok(); // Loading variables from .env
+
+        Ok(SinkSettings {
+            jsonl_dir: maybe("SINK_JSONL_DIR")?,
+            jsonl_max_file_mb: optional("SINK_JSONL_MAX_FILE_MB", 64)?,
+            redis_url: maybe("SINK_REDIS_URL")?,
+            redis_channel: optional(
+                "SINK_REDIS_CHANNEL",
+                "klines.{pair}.{timeframe}".to_string(),
+            )?,
+        })
+    }
+}
+
+/// Copies of the SQLite database taken while it is written
+pub struct BackupSettings {
+    pub dir: Option<PathBuf>, // optional BACKUP_DIR, the collector backs up only when set
+    pub interval_secs: u64,   // optional, how often the collector backs up
+    pub keep: usize,          // optional, the latest backups kept, 0 keeps every one
+    pub compress: bool,       // optional, zstd the copies, on by default
+}
+
+impl BackupSettings {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        dotenv().ok(); // Loading variables from .env
+
+        Ok(BackupSettings {
+            dir: maybe("BACKUP_DIR")?,
+            interval_secs: optional("BACKUP_INTERVAL_SECS", 86_400)?,
+            keep: optional("BACKUP_KEEP", 7)?,
+            compress: optional("BACKUP_COMPRESS", true)?,
+        })
+    }
+}
+
+/// Local order books kept from the `book_lv2` WebSocket channel
+pub struct OrderBookSettings {
+    pub enabled: bool,             // optional ORDER_BOOK, off by default
+    pub depth: usize,              // optional, levels per side in a stored snapshot
+    pub snapshot_interval_ms: u64, // optional, how often the books are stored
+}
+
+impl OrderBookSettings {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        dotenv().ok(); // Loading variables from .env
+
+        Ok(OrderBookSettings {
+            enabled: optional("ORDER_BOOK", false)?,
+            depth: optional("ORDER_BOOK_DEPTH", 20)?,
+            snapshot_interval_ms: optional("ORDER_BOOK_SNAPSHOT_INTERVAL_MS", 10_000)?,
+        })
+    }
+}
+
+/// Health checks of the collector (the `status` command only needs the address)
+pub struct HealthSettings {
+    pub addr: Option<SocketAddr>, // optional, `/health` and `/ready` are served only when set
+    pub kline_staleness_secs: u64, // optional, how late the next candle of a series may be
+    pub trade_staleness_secs: u64, // optional, how long a pair may go without trades
+}
+
+impl HealthSettings {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        dotenv().ok(); // Loading variables from .env
+
+        Ok(HealthSettings {
+            addr: maybe("HEALTH_ADDR")?,
+            kline_staleness_secs: optional("HEALTH_KLINE_STALENESS_SECS", 300)?,
+            trade_staleness_secs: optional("HEALTH_TRADE_STALENESS_SECS", 60)?,
+        })
+    }
+}
+
+/// The part of the configuration needed to open the store (enough for the offline commands)
+pub struct StorageSettings {
+    pub db_url: String,
+    pub write_buffer_max_rows: usize, // optional, klines waiting before the write buffer flushes
+    pub write_buffer_max_delay_ms: u64, // optional, longest time klines wait in the write buffer
+    pub sqlite_journal_mode: String,  // optional, WAL by default
+    pub sqlite_synchronous: String,   // optional, NORMAL by default
+    pub sqlite_busy_timeout_ms: u64,  // optional, how long to wait for a locked database
+    pub sqlite_read_connections: u32, // optional, size of the read-only pool
+    pub sqlite_partition_by: String,  // optional, none (one file), pair or month
+}
+
+/// Log line format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json, // one JSON object per line, with the span fields, for log shippers
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("unknown format '{}', expected text or json", other)),
+        }
+    }
+}
+
+/// Logging, read before everything else so that later configuration errors are logged
+pub struct LogSettings {
+    pub filter: String, // RUST_LOG or LOG_LEVEL (info by default), e.g. `info,sqlx=warn`
+    pub format: LogFormat, // optional LOG_FORMAT, text by default
+}
+
+impl LogSettings {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        dotenv().ok(); // Loading variables from .env
+
+        let filter = match env::var("RUST_LOG") {
+            Ok(filter) => filter,
+            Err(_) => optional("LOG_LEVEL", "info".to_string())?,
+        };
+        Ok(LogSettings {
+            filter,
+            format: optional("LOG_FORMAT", LogFormat::Text)?,
+        })
+    }
 }
 
 impl Settings {
-    pub fn from_env() -> Self {
+    pub fn from_env() -> Result<Self, ConfigError> {
         dotenv().ok(); // Loading variables from .env
 
-        let symbols = env::var("SYMBOLS")
-            .expect("SYMBOLS must be set")
+        let symbols = required("SYMBOLS")?
             .split(',')
             .map(|s| s.trim().to_string())
             .collect();
 
-        let timeframes = env::var("TIMEFRAMES")
-            .expect("TIMEFRAMES must be set")
+        let timeframes = required("TIMEFRAMES")?
             .split(',')
             .map(|t| t.trim().to_string())
             .collect();
 
-        Settings {
-            exchange: env::var("EXCHANGE").expect("EXCHANGE must be set"),
-            poloniex_rest_url_base: env::var("POLONIEX_REST_URL_BASE")
-                .expect("POLONIEX_REST_URL must be set"),
-            poloniex_rest_url_endpoint: env::var("POLONIEX_REST_URL_ENDPOINT")
-                .expect("POLONIEX_REST_URL_ENDPOINT must be set"),
-            poloniex_ws_url: env::var("POLONIEX_WS_URL").expect("POLONIEX_WS_URL must be set"),
-            binance_rest_url: env::var("BINANCE_REST_URL").expect("BINANCE_REST_URL must be set"),
-            binance_ws_url: env::var("BINANCE_WS_URL").expect("BINANCE_WS_URL must be set"),
-            db_url: env::var("DB_URL").expect("DB_URL must be set"),
+        Ok(Settings {
+            exchange: required("EXCHANGE")?,
+            poloniex_rest_url_base: required("POLONIEX_REST_URL_BASE")?,
+            poloniex_rest_url_endpoint: required("POLONIEX_REST_URL_ENDPOINT")?,
+            poloniex_ws_url: required("POLONIEX_WS_URL")?,
+            binance_rest_url: required("BINANCE_REST_URL")?,
+            binance_ws_url: required("BINANCE_WS_URL")?,
             symbols,
+            symbols_sync: optional("SYMBOLS_SYNC", true)?,
+            trades_ws: optional("TRADES_WS", false)?,
             timeframes,
-        }
+            poll_interval_secs: optional("POLL_INTERVAL_SECS", 60)?,
+            storage: StorageSettings::from_env()?,
+            api_addr: maybe("API_ADDR")?,
+            live_addr: maybe("LIVE_ADDR")?,
+            metrics_addr: maybe("METRICS_ADDR")?,
+            health: HealthSettings::from_env()?,
+            order_book: OrderBookSettings::from_env()?,
+            tickers: TickerSettings::from_env()?,
+            capture: CaptureSettings::from_env()?,
+            retention: RetentionSettings::from_env()?,
+            backup: BackupSettings::from_env()?,
+            indicators: list("INDICATORS", parse_specs)?,
+            kline_sinks: list("KLINE_SINKS", parse_sink_rules)?,
+            sinks: SinkSettings::from_env()?,
+        })
     }
 }
+
+impl StorageSettings {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        dotenv().ok(); // Loading variables from .env
+
+        Ok(StorageSettings {
+            db_url: required("DB_URL")?,
+            write_buffer_max_rows: optional("WRITE_BUFFER_MAX_ROWS", 5_000)?,
+            write_buffer_max_delay_ms: optional("WRITE_BUFFER_MAX_DELAY_MS", 500)?,
+            sqlite_journal_mode: optional("SQLITE_JOURNAL_MODE", "WAL".to_string())?,
+            sqlite_synchronous: optional("SQLITE_SYNCHRONOUS", "NORMAL".to_string())?,
+            sqlite_busy_timeout_ms: optional("SQLITE_BUSY_TIMEOUT_MS", 5_000)?,
+            sqlite_read_connections: optional("SQLITE_READ_CONNECTIONS", 4)?,
+            sqlite_partition_by: optional("SQLITE_PARTITION_BY", "none".to_string())?,
+        })
+    }
+}
+
+/// A variable that must be present
+fn required(name: &'static str) -> Result<String, ConfigError> {
+    env::var(name).map_err(|_| ConfigError::Missing(name))
+}
+
+/// A variable that falls back to a default when absent, but must be valid when present
+fn optional<T>(name: &'static str, default: T) -> Result<T, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match env::var(name) {
+        Ok(value) => parse(name, value),
+        Err(_) => Ok(default),
+    }
+}
+
+/// A variable without a default: absent is None, but it must be valid when present
+fn maybe<T>(name: &'static str) -> Result<Option<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match env::var(name) {
+        Ok(value) => parse(name, value).map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+/// A `;`-separated list such as `sma:20;macd:12,26,9` or `*:SECOND_1=live`, empty when absent
+fn list<T>(
+    name: &'static str,
+    parse: fn(&str) -> Result<Vec<T>, String>,
+) -> Result<Vec<T>, ConfigError> {
+    match env::var(name) {
+        Ok(value) => parse(&value).map_err(|reason| ConfigError::Invalid {
+            name,
+            value,
+            reason,
+        }),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+fn parse<T>(name: &'static str, value: String) -> Result<T, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|e: T::Err| ConfigError::Invalid {
+            name,
+            reason: e.to_string(),
+            value,
+        })
+}
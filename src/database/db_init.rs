@@ -1,9 +1,9 @@
-use sqlx::SqlitePool;
+use sqlx::{PgPool, SqlitePool};
 
 /*
     Database initialization (creating tables and indexes)
 */
-pub async fn initialize_database(pool: &SqlitePool) {
+pub async fn initialize_database(pool: &SqlitePool) -> Result<(), sqlx::Error> {
     sqlx::query(
         r#"
         -- Создание таблицы recent_trades
@@ -32,12 +32,158 @@ pub async fn initialize_database(pool: &SqlitePool) {
             sell_quote REAL NOT NULL
         );
 
+        -- Top levels of the order books, one row per level
+        CREATE TABLE IF NOT EXISTS order_book_snapshots (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            pair TEXT NOT NULL,
+            timestamp INTEGER NOT NULL,
+            side TEXT NOT NULL,
+            level INTEGER NOT NULL,
+            price REAL NOT NULL,
+            quantity REAL NOT NULL
+        );
+
+        -- 24h statistics of the pairs, from the REST ticker and the ticker channel
+        CREATE TABLE IF NOT EXISTS tickers (
+            pair TEXT NOT NULL,
+            timestamp INTEGER NOT NULL,
+            open REAL NOT NULL,
+            high REAL NOT NULL,
+            low REAL NOT NULL,
+            close REAL NOT NULL,
+            quantity REAL NOT NULL,
+            amount REAL NOT NULL,
+            trade_count INTEGER NOT NULL,
+            daily_change REAL NOT NULL,
+            PRIMARY KEY (pair, timestamp)
+        );
+
+        -- Markets metadata of the exchanges, replaced by every sync
+        CREATE TABLE IF NOT EXISTS symbols (
+            exchange TEXT NOT NULL,
+            pair TEXT NOT NULL,
+            exchange_symbol TEXT NOT NULL,
+            base TEXT NOT NULL,
+            quote TEXT NOT NULL,
+            status TEXT NOT NULL,
+            trading BOOLEAN NOT NULL,
+            price_precision INTEGER NOT NULL,
+            quantity_precision INTEGER NOT NULL,
+            min_quantity REAL NOT NULL,
+            min_amount REAL NOT NULL,
+            PRIMARY KEY (exchange, pair)
+        );
+
+        -- Computed indicator series, one row per output (`macd.signal`, `bb.upper`, ...)
+        CREATE TABLE IF NOT EXISTS indicators (
+            pair TEXT NOT NULL,
+            time_frame TEXT NOT NULL,
+            utc_begin INTEGER NOT NULL,
+            indicator TEXT NOT NULL,
+            params TEXT NOT NULL,
+            value REAL NOT NULL,
+            PRIMARY KEY (pair, time_frame, utc_begin, indicator, params)
+        );
+
         -- For quick retrieval of data in the right time order
         CREATE INDEX IF NOT EXISTS idx_recent_trades_timestamp ON recent_trades (timestamp);
         CREATE INDEX IF NOT EXISTS idx_klines_utc_begin ON klines (utc_begin);
+        -- For lookups of one series (upserts, filtered queries)
+        CREATE INDEX IF NOT EXISTS idx_klines_series ON klines (pair, time_frame, utc_begin);
+        CREATE INDEX IF NOT EXISTS idx_order_book_snapshots_pair ON order_book_snapshots (pair, timestamp);
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/*
+    The same schema for PostgreSQL
+*/
+pub async fn initialize_postgres_database(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::raw_sql(
+        r#"
+        CREATE TABLE IF NOT EXISTS recent_trades (
+            tid TEXT PRIMARY KEY,
+            pair TEXT NOT NULL,
+            price TEXT NOT NULL,
+            amount TEXT NOT NULL,
+            side TEXT NOT NULL,
+            timestamp BIGINT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS klines (
+            id BIGSERIAL PRIMARY KEY,
+            pair TEXT NOT NULL,
+            time_frame TEXT NOT NULL,
+            o DOUBLE PRECISION NOT NULL,
+            h DOUBLE PRECISION NOT NULL,
+            l DOUBLE PRECISION NOT NULL,
+            c DOUBLE PRECISION NOT NULL,
+            utc_begin BIGINT NOT NULL,
+            buy_base DOUBLE PRECISION NOT NULL,
+            sell_base DOUBLE PRECISION NOT NULL,
+            buy_quote DOUBLE PRECISION NOT NULL,
+            sell_quote DOUBLE PRECISION NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS order_book_snapshots (
+            id BIGSERIAL PRIMARY KEY,
+            pair TEXT NOT NULL,
+            timestamp BIGINT NOT NULL,
+            side TEXT NOT NULL,
+            level BIGINT NOT NULL,
+            price DOUBLE PRECISION NOT NULL,
+            quantity DOUBLE PRECISION NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS tickers (
+            pair TEXT NOT NULL,
+            timestamp BIGINT NOT NULL,
+            open DOUBLE PRECISION NOT NULL,
+            high DOUBLE PRECISION NOT NULL,
+            low DOUBLE PRECISION NOT NULL,
+            close DOUBLE PRECISION NOT NULL,
+            quantity DOUBLE PRECISION NOT NULL,
+            amount DOUBLE PRECISION NOT NULL,
+            trade_count BIGINT NOT NULL,
+            daily_change DOUBLE PRECISION NOT NULL,
+            PRIMARY KEY (pair, timestamp)
+        );
+
+        CREATE TABLE IF NOT EXISTS symbols (
+            exchange TEXT NOT NULL,
+            pair TEXT NOT NULL,
+            exchange_symbol TEXT NOT NULL,
+            base TEXT NOT NULL,
+            quote TEXT NOT NULL,
+            status TEXT NOT NULL,
+            trading BOOLEAN NOT NULL,
+            price_precision BIGINT NOT NULL,
+            quantity_precision BIGINT NOT NULL,
+            min_quantity DOUBLE PRECISION NOT NULL,
+            min_amount DOUBLE PRECISION NOT NULL,
+            PRIMARY KEY (exchange, pair)
+        );
+
+        CREATE TABLE IF NOT EXISTS indicators (
+            pair TEXT NOT NULL,
+            time_frame TEXT NOT NULL,
+            utc_begin BIGINT NOT NULL,
+            indicator TEXT NOT NULL,
+            params TEXT NOT NULL,
+            value DOUBLE PRECISION NOT NULL,
+            PRIMARY KEY (pair, time_frame, utc_begin, indicator, params)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_recent_trades_timestamp ON recent_trades (timestamp);
+        CREATE INDEX IF NOT EXISTS idx_klines_utc_begin ON klines (utc_begin);
+        CREATE INDEX IF NOT EXISTS idx_klines_series ON klines (pair, time_frame, utc_begin);
+        CREATE INDEX IF NOT EXISTS idx_order_book_snapshots_pair ON order_book_snapshots (pair, timestamp);
         "#,
     )
     .execute(pool)
-    .await
-    .expect("Failed to initialize database");
+    .await?;
+    Ok(())
 }
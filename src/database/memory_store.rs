@@ -0,0 +1,292 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use futures_util::stream;
+
+use super::store::{
+    IndicatorFilter, IndicatorStore, KlineFilter, KlineStore, MaintenanceStore, OrderBookStore,
+    SnapshotFilter, StoreFuture, StoreStream, SymbolStore, TickerFilter, TickerStore, TradeFilter,
+    TradeStore, VacuumMode,
+};
+use crate::indicators::IndicatorPoint;
+use crate::parser::{
+    kline::Kline, order_book::OrderBookSnapshot, recent_trade::RecentTrade, symbol::SymbolInfo,
+    ticker::Ticker, KlineKey,
+};
+
+/// In-memory backend for tests and dry runs; nothing survives the process
+#[derive(Default)]
+pub struct MemoryStore {
+    klines: Mutex<Vec<Kline>>,
+    trades: Mutex<HashMap<String, RecentTrade>>, // keyed by tid, like the table's primary key
+    order_books: Mutex<Vec<OrderBookSnapshot>>,
+    tickers: Mutex<HashMap<(String, i64), Ticker>>, // keyed by (pair, timestamp), like the table
+    symbols: Mutex<BTreeMap<(String, String), SymbolInfo>>, // keyed by (exchange, pair)
+    indicators: Mutex<HashMap<IndicatorKey, IndicatorPoint>>,
+}
+
+/// (pair, time_frame, utc_begin, indicator, params), the key of the `indicators` table
+type IndicatorKey = (String, String, i64, String, String);
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KlineStore for MemoryStore {
+    fn save_klines<'a>(&'a self, klines: &'a [Kline]) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            self.klines.lock().unwrap().extend_from_slice(klines);
+            Ok(())
+        })
+    }
+
+    fn upsert_klines<'a>(&'a self, klines: &'a [Kline]) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let mut stored = self.klines.lock().unwrap();
+            stored.retain(|s| {
+                !klines.iter().any(|k| {
+                    k.pair == s.pair && k.time_frame == s.time_frame && k.utc_begin == s.utc_begin
+                })
+            });
+            stored.extend_from_slice(klines);
+            Ok(())
+        })
+    }
+
+    fn load_series(&self) -> StoreFuture<'_, Vec<KlineKey>> {
+        Box::pin(async move {
+            let series: BTreeSet<KlineKey> = self
+                .klines
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|k| (k.pair.clone(), k.time_frame.clone()))
+                .collect();
+            Ok(series.into_iter().collect())
+        })
+    }
+
+    fn delete_klines<'a>(&'a self, filter: &'a KlineFilter) -> StoreFuture<'a, u64> {
+        Box::pin(async move {
+            let mut stored = self.klines.lock().unwrap();
+            let before = stored.len();
+            stored.retain(|k| !filter.matches(k));
+            Ok((before - stored.len()) as u64)
+        })
+    }
+
+    fn stream_klines<'a>(&'a self, filter: &'a KlineFilter) -> StoreStream<'a, Kline> {
+        // A snapshot of the matching klines, there is nothing to stream from
+        let mut klines: Vec<Kline> = self
+            .klines
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|k| filter.matches(k))
+            .cloned()
+            .collect();
+        klines.sort_by_key(|k| k.utc_begin); // stable, so insertion order is kept within a candle
+        Box::pin(stream::iter(klines.into_iter().map(Ok)))
+    }
+}
+
+impl TradeStore for MemoryStore {
+    fn save_trades<'a>(&'a self, trades: &'a [RecentTrade]) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let mut stored = self.trades.lock().unwrap();
+            for trade in trades {
+                stored
+                    .entry(trade.tid.clone())
+                    .or_insert_with(|| trade.clone());
+            }
+            Ok(())
+        })
+    }
+
+    fn stream_trades<'a>(&'a self, filter: &'a TradeFilter) -> StoreStream<'a, RecentTrade> {
+        let mut trades: Vec<RecentTrade> = self
+            .trades
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|t| filter.matches(t))
+            .cloned()
+            .collect();
+        trades.sort_by(|a, b| {
+            a.timestamp
+                .cmp(&b.timestamp)
+                .then_with(|| a.tid.cmp(&b.tid))
+        });
+        Box::pin(stream::iter(trades.into_iter().map(Ok)))
+    }
+
+    fn delete_trades<'a>(&'a self, filter: &'a TradeFilter) -> StoreFuture<'a, u64> {
+        Box::pin(async move {
+            let mut stored = self.trades.lock().unwrap();
+            let before = stored.len();
+            stored.retain(|_, t| !filter.matches(t));
+            Ok((before - stored.len()) as u64)
+        })
+    }
+}
+
+impl OrderBookStore for MemoryStore {
+    fn save_order_book_snapshots<'a>(
+        &'a self,
+        snapshots: &'a [OrderBookSnapshot],
+    ) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            self.order_books
+                .lock()
+                .unwrap()
+                .extend_from_slice(snapshots);
+            Ok(())
+        })
+    }
+
+    fn load_order_book_snapshots<'a>(
+        &'a self,
+        filter: &'a SnapshotFilter,
+    ) -> StoreFuture<'a, Vec<OrderBookSnapshot>> {
+        Box::pin(async move {
+            let mut snapshots: Vec<OrderBookSnapshot> = self
+                .order_books
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|s| filter.matches(s))
+                .cloned()
+                .collect();
+            snapshots.sort_by(|a, b| {
+                a.timestamp
+                    .cmp(&b.timestamp)
+                    .then_with(|| a.pair.cmp(&b.pair))
+            });
+            Ok(snapshots)
+        })
+    }
+}
+
+impl TickerStore for MemoryStore {
+    fn save_tickers<'a>(&'a self, tickers: &'a [Ticker]) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let mut stored = self.tickers.lock().unwrap();
+            for ticker in tickers {
+                stored
+                    .entry((ticker.pair.clone(), ticker.timestamp))
+                    .or_insert_with(|| ticker.clone());
+            }
+            Ok(())
+        })
+    }
+
+    fn load_tickers<'a>(&'a self, filter: &'a TickerFilter) -> StoreFuture<'a, Vec<Ticker>> {
+        Box::pin(async move {
+            let mut tickers: Vec<Ticker> = self
+                .tickers
+                .lock()
+                .unwrap()
+                .values()
+                .filter(|t| filter.matches(t))
+                .cloned()
+                .collect();
+            tickers.sort_by(|a, b| {
+                a.timestamp
+                    .cmp(&b.timestamp)
+                    .then_with(|| a.pair.cmp(&b.pair))
+            });
+            Ok(tickers)
+        })
+    }
+}
+
+impl SymbolStore for MemoryStore {
+    fn save_symbols<'a>(&'a self, symbols: &'a [SymbolInfo]) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let mut stored = self.symbols.lock().unwrap();
+            for symbol in symbols {
+                stored.insert(
+                    (symbol.exchange.clone(), symbol.pair.clone()),
+                    symbol.clone(),
+                );
+            }
+            Ok(())
+        })
+    }
+
+    fn load_symbols<'a>(&'a self, exchange: &'a str) -> StoreFuture<'a, Vec<SymbolInfo>> {
+        Box::pin(async move {
+            Ok(self
+                .symbols
+                .lock()
+                .unwrap()
+                .values()
+                .filter(|s| s.exchange == exchange)
+                .cloned()
+                .collect())
+        })
+    }
+}
+
+impl IndicatorStore for MemoryStore {
+    fn save_indicators<'a>(&'a self, points: &'a [IndicatorPoint]) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let mut stored = self.indicators.lock().unwrap();
+            for point in points {
+                let key = (
+                    point.pair.clone(),
+                    point.time_frame.clone(),
+                    point.utc_begin,
+                    point.indicator.clone(),
+                    point.params.clone(),
+                );
+                stored.insert(key, point.clone());
+            }
+            Ok(())
+        })
+    }
+
+    fn load_indicators<'a>(
+        &'a self,
+        filter: &'a IndicatorFilter,
+    ) -> StoreFuture<'a, Vec<IndicatorPoint>> {
+        Box::pin(async move {
+            let mut points: Vec<IndicatorPoint> = self
+                .indicators
+                .lock()
+                .unwrap()
+                .values()
+                .filter(|p| filter.matches(p))
+                .cloned()
+                .collect();
+            points.sort_by(|a, b| {
+                (a.utc_begin, &a.pair, &a.time_frame, &a.indicator, &a.params).cmp(&(
+                    b.utc_begin,
+                    &b.pair,
+                    &b.time_frame,
+                    &b.indicator,
+                    &b.params,
+                ))
+            });
+            Ok(points)
+        })
+    }
+}
+
+impl MaintenanceStore for MemoryStore {
+    /// Deleted rows are gone from memory already
+    fn reclaim_space(&self, _mode: VacuumMode) -> StoreFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn backup<'a>(&'a self, _target: &'a Path) -> StoreFuture<'a, Vec<PathBuf>> {
+        Box::pin(async {
+            Err(sqlx::Error::Configuration(
+                "an in-memory store can't be backed up".into(),
+            ))
+        })
+    }
+}
@@ -1,66 +1,212 @@
 mod db_init;
+pub mod memory_store;
+pub mod partitions;
+pub mod postgres_store;
+pub mod sqlite_store;
+pub mod store;
+pub mod write_buffer;
 
 use db_init::initialize_database;
+use std::str::FromStr;
+use std::time::Duration;
+
 use sqlx::sqlite::SqlitePool;
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
-use sqlx::{query, Pool, Sqlite};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
+use sqlx::{Pool, QueryBuilder, Sqlite, SqliteConnection};
 use tracing::debug;
 
+use crate::config::{settings::StorageSettings, ConfigError};
 use crate::parser::kline::Kline;
+pub use memory_store::MemoryStore;
+pub use partitions::{PartitionInfo, PartitionScheme, PartitionedStore};
+pub use postgres_store::PostgresStore;
+pub use sqlite_store::SqliteStore;
+pub use store::{
+    open_store, sqlite_file, IndicatorFilter, IndicatorStore, KlineFilter, KlineStore,
+    MaintenanceStore, OrderBookStore, SnapshotFilter, Store, SymbolStore, TickerFilter,
+    TickerStore, TradeFilter, TradeStore, VacuumMode,
+};
+pub use write_buffer::{KlineWriteBuffer, WriteBufferConfig};
+
+/// SQLite connection settings
+#[derive(Debug, Clone)]
+pub struct SqliteTuning {
+    pub journal_mode: SqliteJournalMode,
+    pub synchronous: SqliteSynchronous,
+    pub busy_timeout: Duration, // how long a connection waits for a lock before `database is locked`
+    pub read_connections: u32, // size of the read-only pool, the writer is always a single connection
+    pub partition_by: Option<PartitionScheme>, // klines and trades in a file per pair or month
+}
+
+impl Default for SqliteTuning {
+    fn default() -> Self {
+        SqliteTuning {
+            journal_mode: SqliteJournalMode::Wal,
+            synchronous: SqliteSynchronous::Normal, // safe with WAL, fsync only at checkpoints
+            busy_timeout: Duration::from_secs(5),
+            read_connections: 4,
+            partition_by: None,
+        }
+    }
+}
+
+impl SqliteTuning {
+    pub fn from_settings(settings: &StorageSettings) -> Result<Self, ConfigError> {
+        Ok(SqliteTuning {
+            journal_mode: SqliteJournalMode::from_str(&settings.sqlite_journal_mode).map_err(
+                |e| ConfigError::Invalid {
+                    name: "SQLITE_JOURNAL_MODE",
+                    value: settings.sqlite_journal_mode.clone(),
+                    reason: e.to_string(),
+                },
+            )?,
+            synchronous: SqliteSynchronous::from_str(&settings.sqlite_synchronous).map_err(
+                |e| ConfigError::Invalid {
+                    name: "SQLITE_SYNCHRONOUS",
+                    value: settings.sqlite_synchronous.clone(),
+                    reason: e.to_string(),
+                },
+            )?,
+            busy_timeout: Duration::from_millis(settings.sqlite_busy_timeout_ms),
+            read_connections: settings.sqlite_read_connections.max(1),
+            partition_by: match settings.sqlite_partition_by.trim() {
+                "" | "none" => None,
+                scheme => Some(scheme.parse().map_err(|reason| ConfigError::Invalid {
+                    name: "SQLITE_PARTITION_BY",
+                    value: settings.sqlite_partition_by.clone(),
+                    reason,
+                })?),
+            },
+        })
+    }
+}
+
+/// The dedicated writer connection and the read-only pool of one SQLite file
+#[derive(Clone)]
+pub struct SqlitePools {
+    pub writer: Pool<Sqlite>,
+    pub reader: Pool<Sqlite>,
+}
 
 /*
-    Sets up a connection to a SQLite database
+    Sets up connections to a SQLite database (a partitioned one is a `PartitionedStore`, which
+    connects to each of its files through here or `connect_options`).
+    All writes go through a single connection, so writers of this process queue up in the pool
+    instead of fighting for the file lock; reads use a separate read-only pool.
 */
-pub async fn establish_connection(filename: &str) -> Pool<Sqlite> {
-    let options = SqliteConnectOptions::new()
-        .filename(filename)
-        .create_if_missing(true); // Creates a file if it does not exist
+pub async fn establish_connection(
+    filename: &str,
+    tuning: &SqliteTuning,
+) -> Result<SqlitePools, sqlx::Error> {
+    let options = connect_options(filename, tuning);
 
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5) // Pool - maximum of 5 connections
-        .connect_with(options) // Establishing a connection
-        .await
-        .expect("Database connection error");
+    let writer = SqlitePoolOptions::new()
+        .max_connections(1) // The only connection that writes
+        .connect_with(options.clone()) // Establishing a connection
+        .await?;
+
+    // Database initialization (before the readers, which can't create anything)
+    initialize_database(&writer).await?;
+
+    let reader = SqlitePoolOptions::new()
+        .max_connections(tuning.read_connections)
+        .connect_with(options.read_only(true).create_if_missing(false))
+        .await?;
 
-    // Database initialization
-    initialize_database(&pool).await;
     debug!("Database connection established!");
-    pool
+    Ok(SqlitePools { writer, reader })
+}
+
+/// Options of the connections to one SQLite file
+pub(crate) fn connect_options(filename: &str, tuning: &SqliteTuning) -> SqliteConnectOptions {
+    SqliteConnectOptions::new()
+        .filename(filename)
+        .create_if_missing(true) // Creates a file if it does not exist
+        .journal_mode(tuning.journal_mode)
+        .synchronous(tuning.synchronous)
+        .busy_timeout(tuning.busy_timeout)
 }
 
 /// Creates a test database in memory
+/// (a single connection, since every in-memory connection is a separate database)
 #[allow(dead_code)]
 pub async fn get_test_database_sqlite_pool() -> SqlitePool {
-    SqlitePool::connect("sqlite::memory:")
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("Error connecting to the test database");
+    initialize_database(&pool)
         .await
-        .expect("Error connecting to the test database")
+        .expect("Failed to initialize the test database");
+    pool
 }
 
+/// Columns of `klines` in the order `Kline` fields are bound and read
+pub(crate) const KLINE_COLUMNS: &str =
+    "pair, time_frame, o, h, l, c, utc_begin, buy_base, sell_base, buy_quote, sell_quote";
+/// Columns of `recent_trades` in the order `RecentTrade` fields are bound and read
+pub(crate) const TRADE_COLUMNS: &str = "tid, pair, price, amount, side, timestamp";
+
+/// Rows per multi-row INSERT: 11 bound values per kline keeps a statement well below SQLite's variable limit
+const KLINES_PER_INSERT: usize = 500;
+
+/*
+    Saves klines in a single transaction using multi-row INSERT statements.
+    Statements of full chunks have the same text, so sqlx reuses the prepared statement.
+*/
 pub async fn save_klines(db_pool: &Pool<Sqlite>, klines: &[Kline]) -> Result<(), sqlx::Error> {
-    // let mut tx = db_pool.begin().await?;
+    if klines.is_empty() {
+        return Ok(());
+    }
+    let mut tx = db_pool.begin().await?;
+    insert_klines(&mut tx, klines).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/*
+    Replaces the stored klines of the same (pair, time_frame, utc_begin) and inserts the rest,
+    in one transaction. The table has no unique key (older databases may hold duplicates),
+    so the matching rows are deleted first rather than relying on ON CONFLICT.
+*/
+pub async fn upsert_klines(db_pool: &Pool<Sqlite>, klines: &[Kline]) -> Result<(), sqlx::Error> {
+    if klines.is_empty() {
+        return Ok(());
+    }
+    let mut tx = db_pool.begin().await?;
     for kline in klines {
-        sqlx::query(
-            r#"
-            INSERT INTO klines (pair, time_frame, o, h, l, c, utc_begin, buy_base, sell_base,buy_quote, sell_quote)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#,
-        )
+        sqlx::query("DELETE FROM klines WHERE pair = ? AND time_frame = ? AND utc_begin = ?")
+            .bind(&kline.pair)
+            .bind(&kline.time_frame)
+            .bind(kline.utc_begin)
+            .execute(&mut *tx)
+            .await?;
+    }
+    insert_klines(&mut tx, klines).await?;
+    tx.commit().await?;
+    Ok(())
+}
 
-        .bind(&kline.pair)
-        .bind(&kline.time_frame)
-        .bind(kline.o)
-        .bind(kline.h)
-        .bind(kline.l)
-        .bind(kline.c)
-        .bind(kline.utc_begin)
-        .bind(kline.volume_bs.buy_base)
-        .bind(kline.volume_bs.sell_base)
-        .bind(kline.volume_bs.buy_quote)
-        .bind(kline.volume_bs.sell_quote)
-        .execute(db_pool) // or `&mut *tx` for a transaction
-        .await?;
+async fn insert_klines(conn: &mut SqliteConnection, klines: &[Kline]) -> Result<(), sqlx::Error> {
+    for chunk in klines.chunks(KLINES_PER_INSERT) {
+        let mut builder =
+            QueryBuilder::<Sqlite>::new(format!("INSERT INTO klines ({KLINE_COLUMNS}) "));
+        builder.push_values(chunk, |mut row, kline| {
+            row.push_bind(&kline.pair)
+                .push_bind(&kline.time_frame)
+                .push_bind(kline.o)
+                .push_bind(kline.h)
+                .push_bind(kline.l)
+                .push_bind(kline.c)
+                .push_bind(kline.utc_begin)
+                .push_bind(kline.volume_bs.buy_base)
+                .push_bind(kline.volume_bs.sell_base)
+                .push_bind(kline.volume_bs.buy_quote)
+                .push_bind(kline.volume_bs.sell_quote);
+        });
+        builder.build().persistent(true).execute(&mut *conn).await?;
     }
-    // tx.commit().await?;
     Ok(())
 }
 
@@ -78,7 +224,9 @@ mod tests {
         let pool = get_test_database_sqlite_pool().await;
 
         // Database initialization
-        initialize_database(&pool).await;
+        initialize_database(&pool)
+            .await
+            .expect("Failed to initialize database");
 
         // 3. Insert test data into recent_trades
         query(
@@ -186,4 +334,192 @@ mod tests {
         assert_eq!(buy_quote, 15000.0);
         assert_eq!(sell_quote, 9000.0);
     }
+
+    fn bench_klines(count: usize) -> Vec<Kline> {
+        (0..count)
+            .map(|i| Kline {
+                pair: "BTC_USDT".to_string(),
+                time_frame: "MINUTE_1".to_string(),
+                o: 30000.0,
+                h: 30100.0,
+                l: 29900.0,
+                c: 30050.0,
+                utc_begin: 60_000 * i as i64,
+                volume_bs: crate::parser::kline::VBS {
+                    buy_base: 0.5,
+                    sell_base: 0.3,
+                    buy_quote: 15000.0,
+                    sell_quote: 9000.0,
+                },
+            })
+            .collect()
+    }
+
+    async fn count_klines(pool: &SqlitePool) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM klines")
+            .fetch_one(pool)
+            .await
+            .expect("Failed to count klines")
+    }
+
+    /*
+        Benchmark: 100k klines into a temp-file database.
+        The old row-by-row path (one autocommit INSERT, i.e. one fsync, per kline) is measured
+        on a 1k sample, it is too slow for the full set. Run with `cargo test -- --ignored`.
+    */
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "benchmark, run with --ignored"]
+    async fn bench_save_klines_100k() {
+        const TOTAL: usize = 100_000;
+        const ROW_BY_ROW_SAMPLE: usize = 1_000;
+        let path = std::env::temp_dir().join(format!("klines_bench_{}.db", uuid::Uuid::new_v4()));
+        // SQLite defaults (rollback journal, synchronous FULL) - the setting the old code ran with
+        let legacy = SqliteTuning {
+            journal_mode: SqliteJournalMode::Delete,
+            synchronous: SqliteSynchronous::Full,
+            ..SqliteTuning::default()
+        };
+        let pool = establish_connection(path.to_str().unwrap(), &legacy)
+            .await
+            .expect("Database connection error")
+            .writer;
+        let klines = bench_klines(TOTAL);
+
+        // 1. Row by row, outside a transaction
+        let started = std::time::Instant::now();
+        for kline in &klines[..ROW_BY_ROW_SAMPLE] {
+            query(
+                r#"
+                INSERT INTO klines (pair, time_frame, o, h, l, c, utc_begin, buy_base, sell_base, buy_quote, sell_quote)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&kline.pair)
+            .bind(&kline.time_frame)
+            .bind(kline.o)
+            .bind(kline.h)
+            .bind(kline.l)
+            .bind(kline.c)
+            .bind(kline.utc_begin)
+            .bind(kline.volume_bs.buy_base)
+            .bind(kline.volume_bs.sell_base)
+            .bind(kline.volume_bs.buy_quote)
+            .bind(kline.volume_bs.sell_quote)
+            .execute(&pool)
+            .await
+            .expect("Failed to insert kline");
+        }
+        let row_by_row = ROW_BY_ROW_SAMPLE as f64 / started.elapsed().as_secs_f64();
+        query("DELETE FROM klines").execute(&pool).await.unwrap();
+
+        // 2. One transaction, multi-row INSERTs
+        let started = std::time::Instant::now();
+        save_klines(&pool, &klines)
+            .await
+            .expect("Failed to save klines");
+        let batched = TOTAL as f64 / started.elapsed().as_secs_f64();
+        assert_eq!(count_klines(&pool).await, TOTAL as i64);
+        query("DELETE FROM klines").execute(&pool).await.unwrap();
+
+        // 3. Through the write buffer, in handler-sized pieces of 100 klines
+        let started = std::time::Instant::now();
+        let buffer = KlineWriteBuffer::spawn(
+            std::sync::Arc::new(SqliteStore::from_pool(pool.clone())),
+            WriteBufferConfig::default(),
+        );
+        for piece in klines.chunks(100) {
+            buffer.push(piece.to_vec()).await.unwrap();
+        }
+        buffer.flush().await.unwrap();
+        let buffered = TOTAL as f64 / started.elapsed().as_secs_f64();
+        assert_eq!(count_klines(&pool).await, TOTAL as i64);
+
+        // klines/s: batching has to beat one transaction per row by far
+        assert!(
+            batched > 10.0 * row_by_row,
+            "save_klines {batched:.0}/s vs row by row {row_by_row:.0}/s"
+        );
+        assert!(
+            buffered > 10.0 * row_by_row,
+            "write buffer {buffered:.0}/s vs row by row {row_by_row:.0}/s"
+        );
+
+        pool.close().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /*
+        Stress test: many simulated handlers write concurrently while readers query the same file.
+        Two stores are opened on the file, so their writer connections also compete for the
+        SQLite lock, which the busy timeout has to absorb.
+    */
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn stress_concurrent_writes() {
+        const HANDLERS: usize = 32;
+        const BATCHES: usize = 25;
+        const BATCH_SIZE: usize = 40;
+        let path = std::env::temp_dir().join(format!("klines_stress_{}.db", uuid::Uuid::new_v4()));
+        let filename = path.to_str().unwrap();
+        let tuning = SqliteTuning::default();
+        let stores = [
+            std::sync::Arc::new(SqliteStore::new(
+                establish_connection(filename, &tuning).await.unwrap(),
+            )),
+            std::sync::Arc::new(SqliteStore::new(
+                establish_connection(filename, &tuning).await.unwrap(),
+            )),
+        ];
+
+        let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(stores[0].writer())
+            .await
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for handler in 0..HANDLERS {
+            let store = stores[handler % stores.len()].clone();
+            tasks.spawn(async move {
+                let klines = bench_klines(BATCH_SIZE);
+                for _ in 0..BATCHES {
+                    store.save_klines(&klines).await?;
+                }
+                Ok::<(), sqlx::Error>(())
+            });
+        }
+        for _ in 0..8 {
+            let store = stores[0].clone();
+            tasks.spawn(async move {
+                for _ in 0..BATCHES {
+                    store
+                        .load_klines(&KlineFilter {
+                            from: Some(0),
+                            to: Some(60_000),
+                            ..KlineFilter::default()
+                        })
+                        .await?;
+                }
+                Ok(())
+            });
+        }
+        while let Some(result) = tasks.join_next().await {
+            result
+                .unwrap()
+                .expect("Concurrent access failed (database is locked?)");
+        }
+
+        let saved = stores[1]
+            .load_klines(&KlineFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(saved.len(), HANDLERS * BATCHES * BATCH_SIZE);
+
+        for store in &stores {
+            store.writer().close().await;
+            store.reader().close().await;
+        }
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", filename, suffix));
+        }
+    }
 }
@@ -0,0 +1,834 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use async_stream::try_stream;
+use futures_util::TryStreamExt;
+use serde::Serialize;
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::{Connection, QueryBuilder, Sqlite, SqliteConnection};
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+
+use super::db_init::initialize_database;
+use super::sqlite_store::{kline_from_row, trade_from_row, SqliteStore};
+use super::store::{
+    IndicatorFilter, IndicatorStore, KlineFilter, KlineStore, MaintenanceStore, OrderBookStore,
+    SnapshotFilter, StoreFuture, StoreStream, SymbolStore, TickerFilter, TickerStore, TradeFilter,
+    TradeStore, VacuumMode,
+};
+use super::{connect_options, establish_connection, SqliteTuning, KLINE_COLUMNS, TRADE_COLUMNS};
+use crate::indicators::IndicatorPoint;
+use crate::parser::{
+    kline::Kline, order_book::OrderBookSnapshot, recent_trade::RecentTrade, symbol::SymbolInfo,
+    ticker::Ticker, utc_date, KlineKey,
+};
+
+/// SQLite attaches at most 10 databases to a connection
+const MAX_ATTACHED: usize = 10;
+
+/// How klines and trades are split between files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionScheme {
+    /// One file per pair, e.g. `BTC_USDT.sqlite`
+    Pair,
+    /// One file per UTC month of `utc_begin`/`timestamp`, e.g. `2025-01.sqlite`
+    Month,
+}
+
+impl FromStr for PartitionScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "pair" => Ok(PartitionScheme::Pair),
+            "month" => Ok(PartitionScheme::Month),
+            other => Err(format!(
+                "unknown partitioning '{}', expected none, pair or month",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for PartitionScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PartitionScheme::Pair => write!(f, "pair"),
+            PartitionScheme::Month => write!(f, "month"),
+        }
+    }
+}
+
+fn month_key(timestamp: i64) -> String {
+    let (year, month, _) = utc_date(timestamp);
+    format!("{:04}-{:02}", year, month)
+}
+
+impl PartitionScheme {
+    /// The partition of a row; anything but letters, digits, `-` and `_` is replaced in a file name
+    pub fn key(&self, pair: &str, timestamp: i64) -> String {
+        match self {
+            PartitionScheme::Pair => pair
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect(),
+            PartitionScheme::Month => month_key(timestamp),
+        }
+    }
+
+    /// Whether a partition may hold rows of the pair and range; both bounds are inclusive
+    fn may_hold(&self, key: &str, pair: Option<&str>, from: Option<i64>, to: Option<i64>) -> bool {
+        match self {
+            PartitionScheme::Pair => pair.is_none_or(|pair| self.key(pair, 0) == key),
+            PartitionScheme::Month => {
+                from.is_none_or(|from| key >= month_key(from).as_str())
+                    && to.is_none_or(|to| key <= month_key(to).as_str())
+            }
+        }
+    }
+}
+
+/// A partition file, as `partitions list` shows it
+#[derive(Debug, Clone, Serialize)]
+pub struct PartitionInfo {
+    pub key: String,
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub klines: i64,
+    pub trades: i64,
+    pub first: Option<i64>, // earliest `utc_begin` or trade `timestamp`
+    pub last: Option<i64>,
+}
+
+/*
+    SQLite storage split into files: klines and trades go to one file per pair or per month in
+    `{name}.partitions/` beside the main file, which keeps every other table (and the klines and
+    trades written before partitioning, which are still read and deleted, never added to).
+    Reads attach the main file and the partitions the filter may touch to a connection of their
+    own and query them with one `UNION ALL`; more partitions than SQLite can attach at once are
+    read over several connections and merged in order. Each partition written to has its own
+    writer, kept open, so a batch spanning partitions is not written in one transaction. Deletes
+    and maintenance of the other partitions go through a connection opened for the occasion.
+*/
+pub struct PartitionedStore {
+    main: SqliteStore,
+    main_file: PathBuf,
+    dir: PathBuf,
+    scheme: PartitionScheme,
+    tuning: SqliteTuning,
+    partitions: Mutex<BTreeMap<String, SqliteStore>>, // the writers opened so far
+}
+
+/// A partition for occasional work: its writer when it is being written, else a pool of its own
+struct Borrowed {
+    store: SqliteStore,
+    own: bool, // closed by `release`
+}
+
+impl Borrowed {
+    async fn release(self) {
+        if self.own {
+            self.store.writer().close().await;
+        }
+    }
+}
+
+/// `db.sqlite` -> `db.partitions`
+fn partitions_dir(main_file: &Path) -> PathBuf {
+    let stem = main_file
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "db".to_string());
+    main_file.with_file_name(format!("{}.partitions", stem))
+}
+
+fn remove_database_files(path: &Path) -> io::Result<()> {
+    fs::remove_file(path)?;
+    for suffix in ["-wal", "-shm"] {
+        let mut side = path.as_os_str().to_owned();
+        side.push(suffix);
+        match fs::remove_file(PathBuf::from(side)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Yields the items of streams each ordered by `key`, in that order; ties go to the earlier stream
+fn merge_ordered<'a, T, K, F>(mut streams: Vec<StoreStream<'a, T>>, key: F) -> StoreStream<'a, T>
+where
+    T: Send + 'a,
+    K: Ord,
+    F: Fn(&T) -> K + Send + 'a,
+{
+    if streams.len() == 1 {
+        return streams.remove(0);
+    }
+    Box::pin(try_stream! {
+        let mut heads: Vec<Option<T>> = Vec::with_capacity(streams.len());
+        for stream in streams.iter_mut() {
+            heads.push(stream.try_next().await?);
+        }
+        loop {
+            let next = heads
+                .iter()
+                .enumerate()
+                .filter_map(|(i, head)| head.as_ref().map(|item| (key(item), i)))
+                .min()
+                .map(|(_, i)| i);
+            let Some(i) = next else {
+                break;
+            };
+            let item = heads[i].take().unwrap();
+            heads[i] = streams[i].try_next().await?;
+            yield item;
+        }
+    })
+}
+
+impl PartitionedStore {
+    /// Opens the main file and finds the partitions beside it
+    pub async fn open(
+        filename: &str,
+        scheme: PartitionScheme,
+        tuning: &SqliteTuning,
+    ) -> Result<Self, sqlx::Error> {
+        let main = SqliteStore::new(establish_connection(filename, tuning).await?);
+        let main_file = PathBuf::from(filename);
+        let dir = partitions_dir(&main_file);
+        fs::create_dir_all(&dir)?;
+        info!(
+            "Partitioning klines and trades by {} in {}",
+            scheme,
+            dir.display()
+        );
+        Ok(PartitionedStore {
+            main,
+            main_file,
+            dir,
+            scheme,
+            tuning: tuning.clone(),
+            partitions: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    pub fn scheme(&self) -> PartitionScheme {
+        self.scheme
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.sqlite", key))
+    }
+
+    /// The keys of the partition files on disk, sorted
+    pub fn keys(&self) -> Result<Vec<String>, sqlx::Error> {
+        let mut keys = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "sqlite") {
+                if let Some(stem) = path.file_stem() {
+                    keys.push(stem.to_string_lossy().to_string());
+                }
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// The writer of a partition, creating the file if needed
+    async fn partition(&self, key: &str) -> Result<SqliteStore, sqlx::Error> {
+        let mut partitions = self.partitions.lock().await;
+        if let Some(store) = partitions.get(key) {
+            return Ok(store.clone());
+        }
+        let path = self.path(key);
+        debug!("Opening partition {}", path.display());
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(connect_options(&path.to_string_lossy(), &self.tuning))
+            .await?;
+        initialize_database(&pool).await?;
+        let store = SqliteStore::from_pool(pool);
+        partitions.insert(key.to_string(), store.clone());
+        Ok(store)
+    }
+
+    /// An existing partition for deletes and maintenance, without keeping it open afterwards
+    async fn borrow(&self, key: &str) -> Result<Borrowed, sqlx::Error> {
+        if let Some(store) = self.partitions.lock().await.get(key) {
+            return Ok(Borrowed {
+                store: store.clone(),
+                own: false,
+            });
+        }
+        let options = connect_options(&self.path(key).to_string_lossy(), &self.tuning)
+            .create_if_missing(false);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+        Ok(Borrowed {
+            store: SqliteStore::from_pool(pool),
+            own: true,
+        })
+    }
+
+    /// Closes the writer of a partition, if open, so its file can be moved
+    async fn close(&self, key: &str) {
+        if let Some(store) = self.partitions.lock().await.remove(key) {
+            store.writer().close().await;
+        }
+    }
+
+    fn matching(
+        &self,
+        pair: Option<&str>,
+        from: Option<i64>,
+        to: Option<i64>,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        Ok(self
+            .keys()?
+            .into_iter()
+            .filter(|key| self.scheme.may_hold(key, pair, from, to))
+            .collect())
+    }
+
+    /// A read-only connection to the main file with the partitions attached as `p0`, `p1`, ...
+    async fn attach(&self, keys: &[String]) -> Result<SqliteConnection, sqlx::Error> {
+        let options = connect_options(&self.main_file.to_string_lossy(), &self.tuning)
+            .read_only(true)
+            .create_if_missing(false);
+        let mut conn = SqliteConnection::connect_with(&options).await?;
+        for (i, key) in keys.iter().enumerate() {
+            sqlx::query(&format!("ATTACH DATABASE ? AS p{}", i))
+                .bind(self.path(key).to_string_lossy().to_string())
+                .execute(&mut conn)
+                .await?;
+        }
+        Ok(conn)
+    }
+
+    /// The partitions to read in groups a connection can attach, the main file read with the first
+    fn chunks(keys: &[String]) -> Vec<Vec<String>> {
+        if keys.is_empty() {
+            return vec![Vec::new()];
+        }
+        keys.chunks(MAX_ATTACHED).map(<[String]>::to_vec).collect()
+    }
+
+    /// Rows of one connection: the main file (`part` 0) if `main`, then each attached partition
+    fn union<'a>(
+        table: &str,
+        columns: &str,
+        partitions: usize,
+        main: bool,
+        push_where: impl Fn(&mut QueryBuilder<'a, Sqlite>),
+        order: &str,
+    ) -> QueryBuilder<'a, Sqlite> {
+        let mut builder = QueryBuilder::new(format!("SELECT {} FROM (", columns));
+        let sources = main
+            .then(|| "main".to_string())
+            .into_iter()
+            .chain((0..partitions).map(|i| format!("p{}", i)));
+        for (part, source) in sources.enumerate() {
+            if part > 0 {
+                builder.push(" UNION ALL ");
+            }
+            builder.push(format!(
+                "SELECT {} AS part, rowid AS row, {} FROM {}.{}",
+                part, columns, source, table
+            ));
+            push_where(&mut builder);
+        }
+        builder.push(format!(") ORDER BY {}, part, row", order));
+        builder
+    }
+
+    /// Every partition file with its size, row counts and time range
+    pub async fn list(&self) -> Result<Vec<PartitionInfo>, sqlx::Error> {
+        let mut infos = Vec::new();
+        for key in self.keys()? {
+            let path = self.path(&key);
+            let mut conn = self.attach(std::slice::from_ref(&key)).await?;
+            let (klines, first_kline, last_kline): (i64, Option<i64>, Option<i64>) =
+                sqlx::query_as("SELECT COUNT(*), MIN(utc_begin), MAX(utc_begin) FROM p0.klines")
+                    .fetch_one(&mut conn)
+                    .await?;
+            let (trades, first_trade, last_trade): (i64, Option<i64>, Option<i64>) =
+                sqlx::query_as(
+                    "SELECT COUNT(*), MIN(timestamp), MAX(timestamp) FROM p0.recent_trades",
+                )
+                .fetch_one(&mut conn)
+                .await?;
+            infos.push(PartitionInfo {
+                bytes: fs::metadata(&path)?.len(),
+                key,
+                path,
+                klines,
+                trades,
+                first: first_kline.into_iter().chain(first_trade).min(),
+                last: last_kline.into_iter().chain(last_trade).max(),
+            });
+        }
+        Ok(infos)
+    }
+
+    /*
+        Moves the rows of partitions into the main file and deletes the partition files, e.g. to
+        go back to a single file. The partitions must not be written meanwhile (by a collector
+        of another process), or what it writes after the copy is lost. Returns the rows moved.
+    */
+    pub async fn merge(&self, keys: &[String]) -> Result<u64, sqlx::Error> {
+        let mut moved = 0;
+        for key in keys {
+            let path = self.path(key);
+            if !path.exists() {
+                return Err(sqlx::Error::Configuration(
+                    format!("no partition {}", key).into(),
+                ));
+            }
+            self.close(key).await;
+            let mut conn = self.main.writer().acquire().await?;
+            sqlx::query("ATTACH DATABASE ? AS part")
+                .bind(path.to_string_lossy().to_string())
+                .execute(&mut *conn)
+                .await?;
+            let copied = async {
+                let mut tx = conn.begin().await?;
+                let klines = sqlx::query(&format!(
+                    "INSERT INTO main.klines ({0}) SELECT {0} FROM part.klines ORDER BY id",
+                    KLINE_COLUMNS
+                ))
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+                let trades = sqlx::query(&format!(
+                    "INSERT OR IGNORE INTO main.recent_trades ({0}) SELECT {0} FROM part.recent_trades",
+                    TRADE_COLUMNS
+                ))
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+                tx.commit().await?;
+                Ok::<u64, sqlx::Error>(klines + trades)
+            }
+            .await;
+            sqlx::query("DETACH DATABASE part")
+                .execute(&mut *conn)
+                .await?;
+            moved += copied?;
+            remove_database_files(&path)?;
+            info!("Merged partition {} into {}", key, self.main_file.display());
+        }
+        Ok(moved)
+    }
+
+    /*
+        Compresses partitions into `{dir}/{key}.sqlite.zst` and deletes them: their rows are no
+        longer queried. Decompressing one back into the partitions directory restores it.
+    */
+    pub async fn archive(&self, keys: &[String], dir: &Path) -> Result<Vec<PathBuf>, sqlx::Error> {
+        fs::create_dir_all(dir)?;
+        let mut archives = Vec::new();
+        for key in keys {
+            let path = self.path(key);
+            if !path.exists() {
+                return Err(sqlx::Error::Configuration(
+                    format!("no partition {}", key).into(),
+                ));
+            }
+            self.close(key).await;
+            // Everything into the file itself before it is copied
+            let mut conn = SqliteConnection::connect_with(&connect_options(
+                &path.to_string_lossy(),
+                &self.tuning,
+            ))
+            .await?;
+            sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+                .execute(&mut conn)
+                .await?;
+            conn.close().await?;
+
+            let archive = dir.join(format!("{}.sqlite.zst", key));
+            let mut input = File::open(&path)?;
+            let output = File::create(&archive)?;
+            let mut encoder = zstd::Encoder::new(output, 3)?;
+            io::copy(&mut input, &mut encoder)?;
+            encoder.finish()?.sync_all()?;
+            remove_database_files(&path)?;
+            info!("Archived partition {} into {}", key, archive.display());
+            archives.push(archive);
+        }
+        Ok(archives)
+    }
+
+    /// Deletes the rows of the main file with the same (pair, time_frame, utc_begin)
+    async fn delete_from_main(&self, klines: &[Kline]) -> Result<(), sqlx::Error> {
+        let mut tx = self.main.writer().begin().await?;
+        for kline in klines {
+            sqlx::query("DELETE FROM klines WHERE pair = ? AND time_frame = ? AND utc_begin = ?")
+                .bind(&kline.pair)
+                .bind(&kline.time_frame)
+                .bind(kline.utc_begin)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+}
+
+/// Rows grouped by partition, in their order
+fn group_by_key<T: Clone>(rows: &[T], key: impl Fn(&T) -> String) -> BTreeMap<String, Vec<T>> {
+    let mut groups: BTreeMap<String, Vec<T>> = BTreeMap::new();
+    for row in rows {
+        groups.entry(key(row)).or_default().push(row.clone());
+    }
+    groups
+}
+
+impl KlineStore for PartitionedStore {
+    fn save_klines<'a>(&'a self, klines: &'a [Kline]) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let groups = group_by_key(klines, |k| self.scheme.key(&k.pair, k.utc_begin));
+            for (key, klines) in groups {
+                self.partition(&key).await?.save_klines(&klines).await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn upsert_klines<'a>(&'a self, klines: &'a [Kline]) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            if klines.is_empty() {
+                return Ok(());
+            }
+            self.delete_from_main(klines).await?;
+            let groups = group_by_key(klines, |k| self.scheme.key(&k.pair, k.utc_begin));
+            for (key, klines) in groups {
+                self.partition(&key).await?.upsert_klines(&klines).await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn load_series(&self) -> StoreFuture<'_, Vec<KlineKey>> {
+        Box::pin(async move {
+            let mut series: BTreeSet<KlineKey> =
+                self.main.load_series().await?.into_iter().collect();
+            for chunk in self.keys()?.chunks(MAX_ATTACHED) {
+                let mut conn = self.attach(chunk).await?;
+                for i in 0..chunk.len() {
+                    let rows: Vec<KlineKey> = sqlx::query_as(&format!(
+                        "SELECT DISTINCT pair, time_frame FROM p{}.klines",
+                        i
+                    ))
+                    .fetch_all(&mut conn)
+                    .await?;
+                    series.extend(rows);
+                }
+                conn.close().await?;
+            }
+            Ok(series.into_iter().collect())
+        })
+    }
+
+    fn delete_klines<'a>(&'a self, filter: &'a KlineFilter) -> StoreFuture<'a, u64> {
+        Box::pin(async move {
+            let mut deleted = self.main.delete_klines(filter).await?;
+            for key in self.matching(filter.pair.as_deref(), filter.from, filter.to)? {
+                let partition = self.borrow(&key).await?;
+                let result = partition.store.delete_klines(filter).await;
+                partition.release().await;
+                deleted += result?;
+            }
+            Ok(deleted)
+        })
+    }
+
+    fn stream_klines<'a>(&'a self, filter: &'a KlineFilter) -> StoreStream<'a, Kline> {
+        Box::pin(try_stream! {
+            let keys = self.matching(filter.pair.as_deref(), filter.from, filter.to)?;
+            let mut streams: Vec<StoreStream<'a, Kline>> = Vec::new();
+            for (i, chunk) in Self::chunks(&keys).into_iter().enumerate() {
+                let mut conn = self.attach(&chunk).await?;
+                streams.push(Box::pin(try_stream! {
+                    let mut builder = Self::union(
+                        "klines",
+                        KLINE_COLUMNS,
+                        chunk.len(),
+                        i == 0,
+                        |builder| filter.push_where(builder),
+                        "utc_begin",
+                    );
+                    let mut rows = builder.build().fetch(&mut conn);
+                    while let Some(row) = rows.try_next().await? {
+                        yield kline_from_row(&row);
+                    }
+                }));
+            }
+            let mut klines = merge_ordered(streams, |k: &Kline| k.utc_begin);
+            while let Some(kline) = klines.try_next().await? {
+                yield kline;
+            }
+        })
+    }
+}
+
+impl TradeStore for PartitionedStore {
+    fn save_trades<'a>(&'a self, trades: &'a [RecentTrade]) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let groups = group_by_key(trades, |t| self.scheme.key(&t.pair, t.timestamp));
+            for (key, trades) in groups {
+                self.partition(&key).await?.save_trades(&trades).await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn stream_trades<'a>(&'a self, filter: &'a TradeFilter) -> StoreStream<'a, RecentTrade> {
+        Box::pin(try_stream! {
+            let keys = self.matching(filter.pair.as_deref(), filter.from, filter.to)?;
+            let mut streams: Vec<StoreStream<'a, RecentTrade>> = Vec::new();
+            for (i, chunk) in Self::chunks(&keys).into_iter().enumerate() {
+                let mut conn = self.attach(&chunk).await?;
+                streams.push(Box::pin(try_stream! {
+                    let mut builder = Self::union(
+                        "recent_trades",
+                        TRADE_COLUMNS,
+                        chunk.len(),
+                        i == 0,
+                        |builder| filter.push_where(builder),
+                        "timestamp, tid",
+                    );
+                    let mut rows = builder.build().fetch(&mut conn);
+                    while let Some(row) = rows.try_next().await? {
+                        yield trade_from_row(&row);
+                    }
+                }));
+            }
+            let mut trades = merge_ordered(streams, |t: &RecentTrade| (t.timestamp, t.tid.clone()));
+            while let Some(trade) = trades.try_next().await? {
+                yield trade;
+            }
+        })
+    }
+
+    fn delete_trades<'a>(&'a self, filter: &'a TradeFilter) -> StoreFuture<'a, u64> {
+        Box::pin(async move {
+            let mut deleted = self.main.delete_trades(filter).await?;
+            for key in self.matching(filter.pair.as_deref(), filter.from, filter.to)? {
+                let partition = self.borrow(&key).await?;
+                let result = partition.store.delete_trades(filter).await;
+                partition.release().await;
+                deleted += result?;
+            }
+            Ok(deleted)
+        })
+    }
+}
+
+impl OrderBookStore for PartitionedStore {
+    fn save_order_book_snapshots<'a>(
+        &'a self,
+        snapshots: &'a [OrderBookSnapshot],
+    ) -> StoreFuture<'a, ()> {
+        self.main.save_order_book_snapshots(snapshots)
+    }
+
+    fn load_order_book_snapshots<'a>(
+        &'a self,
+        filter: &'a SnapshotFilter,
+    ) -> StoreFuture<'a, Vec<OrderBookSnapshot>> {
+        self.main.load_order_book_snapshots(filter)
+    }
+}
+
+impl TickerStore for PartitionedStore {
+    fn save_tickers<'a>(&'a self, tickers: &'a [Ticker]) -> StoreFuture<'a, ()> {
+        self.main.save_tickers(tickers)
+    }
+
+    fn load_tickers<'a>(&'a self, filter: &'a TickerFilter) -> StoreFuture<'a, Vec<Ticker>> {
+        self.main.load_tickers(filter)
+    }
+}
+
+impl SymbolStore for PartitionedStore {
+    fn save_symbols<'a>(&'a self, symbols: &'a [SymbolInfo]) -> StoreFuture<'a, ()> {
+        self.main.save_symbols(symbols)
+    }
+
+    fn load_symbols<'a>(&'a self, exchange: &'a str) -> StoreFuture<'a, Vec<SymbolInfo>> {
+        self.main.load_symbols(exchange)
+    }
+}
+
+impl IndicatorStore for PartitionedStore {
+    fn save_indicators<'a>(&'a self, points: &'a [IndicatorPoint]) -> StoreFuture<'a, ()> {
+        self.main.save_indicators(points)
+    }
+
+    fn load_indicators<'a>(
+        &'a self,
+        filter: &'a IndicatorFilter,
+    ) -> StoreFuture<'a, Vec<IndicatorPoint>> {
+        self.main.load_indicators(filter)
+    }
+}
+
+impl MaintenanceStore for PartitionedStore {
+    fn reclaim_space(&self, mode: VacuumMode) -> StoreFuture<'_, ()> {
+        Box::pin(async move {
+            self.main.reclaim_space(mode).await?;
+            for key in self.keys()? {
+                let partition = self.borrow(&key).await?;
+                let result = partition.store.reclaim_space(mode).await;
+                partition.release().await;
+                result?;
+            }
+            Ok(())
+        })
+    }
+
+    /// The main file to `target`, the partitions into the partitions directory of `target`
+    fn backup<'a>(&'a self, target: &'a Path) -> StoreFuture<'a, Vec<PathBuf>> {
+        Box::pin(async move {
+            let mut files = self.main.backup(target).await?;
+            let dir = partitions_dir(target);
+            fs::create_dir_all(&dir)?;
+            for key in self.keys()? {
+                let copy = dir.join(format!("{}.sqlite", key));
+                let partition = self.borrow(&key).await?;
+                let result = partition.store.backup(&copy).await;
+                partition.release().await;
+                files.extend(result?);
+            }
+            Ok(files)
+        })
+    }
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::store::tests::check_store;
+
+    fn kline(pair: &str, utc_begin: i64, c: f64) -> Kline {
+        Kline {
+            c,
+            ..Kline::sample(pair, "DAY_1", utc_begin)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_partitioned_store() {
+        let dir = std::env::temp_dir().join(format!("partitions_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let main = dir.join("db.sqlite").to_string_lossy().to_string();
+        let tuning = SqliteTuning::default();
+
+        // The common scenario, with a file per month
+        let store = PartitionedStore::open(&main, PartitionScheme::Month, &tuning)
+            .await
+            .unwrap();
+        check_store(&store).await;
+        assert_eq!(store.keys().unwrap(), vec!["1970-01".to_string()]);
+
+        // A file per pair, more of them than a connection can attach
+        let main = dir.join("pairs.sqlite").to_string_lossy().to_string();
+        let store = PartitionedStore::open(&main, PartitionScheme::Pair, &tuning)
+            .await
+            .unwrap();
+        let day = 86_400_000;
+        let klines: Vec<Kline> = (0..12)
+            .map(|i| kline(&format!("P{:02}_USDT", i), (12 - i) * day, i as f64))
+            .collect();
+        store.save_klines(&klines).await.unwrap();
+        assert_eq!(store.keys().unwrap().len(), 12);
+        let loaded = store.load_klines(&KlineFilter::default()).await.unwrap();
+        let begins: Vec<i64> = loaded.iter().map(|k| k.utc_begin / day).collect();
+        assert_eq!(begins, (1..=12).collect::<Vec<i64>>());
+        let filter = KlineFilter {
+            pair: Some("P03_USDT".to_string()),
+            ..KlineFilter::default()
+        };
+        assert_eq!(store.load_klines(&filter).await.unwrap()[0].c, 3.0);
+
+        // The partitions are listed, merged back into the main file and archived
+        let list = store.list().await.unwrap();
+        assert_eq!((list[0].key.as_str(), list[0].klines), ("P00_USDT", 1));
+        let merged = store
+            .merge(&["P00_USDT".to_string(), "P01_USDT".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(merged, 2);
+        assert_eq!(store.keys().unwrap().len(), 10);
+        assert_eq!(
+            store
+                .load_klines(&KlineFilter::default())
+                .await
+                .unwrap()
+                .len(),
+            12
+        );
+
+        let archives = store
+            .archive(&["P02_USDT".to_string()], &dir.join("archive"))
+            .await
+            .unwrap();
+        assert!(archives[0].exists());
+        assert_eq!(
+            store
+                .load_klines(&KlineFilter::default())
+                .await
+                .unwrap()
+                .len(),
+            11
+        );
+
+        // An upsert replaces the row left in the main file
+        store
+            .upsert_klines(&[kline("P00_USDT", 12 * day, 7.0)])
+            .await
+            .unwrap();
+        let filter = KlineFilter {
+            pair: Some("P00_USDT".to_string()),
+            ..KlineFilter::default()
+        };
+        let p00 = store.load_klines(&filter).await.unwrap();
+        assert_eq!((p00.len(), p00[0].c), (1, 7.0));
+
+        // Reads, deletes and maintenance leave no writer open
+        drop(store);
+        let store = PartitionedStore::open(&main, PartitionScheme::Pair, &tuning)
+            .await
+            .unwrap();
+        assert_eq!(store.load_series().await.unwrap().len(), 11); // all but the archived pair
+        let filter = KlineFilter {
+            pair: Some("P05_USDT".to_string()),
+            ..KlineFilter::default()
+        };
+        assert_eq!(store.delete_klines(&filter).await.unwrap(), 1);
+        store.reclaim_space(VacuumMode::Full).await.unwrap();
+        let copies = store.backup(&dir.join("copy.sqlite")).await.unwrap();
+        assert_eq!(copies.len(), 1 + store.keys().unwrap().len());
+        assert!(store.partitions.lock().await.is_empty());
+
+        drop(store);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
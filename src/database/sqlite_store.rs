@@ -0,0 +1,442 @@
+use std::path::{Path, PathBuf};
+
+use async_stream::try_stream;
+use futures_util::TryStreamExt;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Pool, QueryBuilder, Row, Sqlite};
+
+use super::store::{
+    snapshot_rows, snapshots_from_rows, IndicatorFilter, IndicatorStore, KlineFilter, KlineStore,
+    MaintenanceStore, OrderBookStore, SnapshotFilter, SnapshotRow, StoreFuture, StoreStream,
+    SymbolStore, TickerFilter, TickerStore, TradeFilter, TradeStore, VacuumMode, INDICATORS_UPSERT,
+    SYMBOLS_UPSERT,
+};
+use super::{save_klines, upsert_klines, SqlitePools, KLINE_COLUMNS, TRADE_COLUMNS};
+use crate::indicators::IndicatorPoint;
+use crate::parser::{
+    kline::{Kline, VBS},
+    order_book::OrderBookSnapshot,
+    recent_trade::RecentTrade,
+    symbol::SymbolInfo,
+    ticker::Ticker,
+    KlineKey,
+};
+
+/// Rows per multi-row INSERT (SQLite allows 32766 bound values per statement)
+const ROWS_PER_INSERT: usize = 500;
+
+/// The SQLite backend: writes go through the single writer connection, queries through the read pool
+#[derive(Clone)]
+pub struct SqliteStore {
+    writer: Pool<Sqlite>,
+    reader: Pool<Sqlite>,
+}
+
+impl SqliteStore {
+    pub fn new(pools: SqlitePools) -> Self {
+        SqliteStore {
+            writer: pools.writer,
+            reader: pools.reader,
+        }
+    }
+
+    /// A store that reads and writes through the same pool (e.g. an in-memory test database)
+    pub fn from_pool(db_pool: Pool<Sqlite>) -> Self {
+        SqliteStore {
+            writer: db_pool.clone(),
+            reader: db_pool,
+        }
+    }
+
+    pub fn writer(&self) -> &Pool<Sqlite> {
+        &self.writer
+    }
+
+    pub fn reader(&self) -> &Pool<Sqlite> {
+        &self.reader
+    }
+}
+
+pub(crate) fn kline_from_row(row: &SqliteRow) -> Kline {
+    Kline {
+        pair: row.get("pair"),
+        time_frame: row.get("time_frame"),
+        o: row.get("o"),
+        h: row.get("h"),
+        l: row.get("l"),
+        c: row.get("c"),
+        utc_begin: row.get("utc_begin"),
+        volume_bs: VBS {
+            buy_base: row.get("buy_base"),
+            sell_base: row.get("sell_base"),
+            buy_quote: row.get("buy_quote"),
+            sell_quote: row.get("sell_quote"),
+        },
+    }
+}
+
+pub(crate) fn trade_from_row(row: &SqliteRow) -> RecentTrade {
+    RecentTrade {
+        tid: row.get("tid"),
+        pair: row.get("pair"),
+        price: row.get("price"),
+        amount: row.get("amount"),
+        side: row.get("side"),
+        timestamp: row.get("timestamp"),
+    }
+}
+
+pub(crate) fn ticker_from_row(row: &SqliteRow) -> Ticker {
+    Ticker {
+        pair: row.get("pair"),
+        timestamp: row.get("timestamp"),
+        open: row.get("open"),
+        high: row.get("high"),
+        low: row.get("low"),
+        close: row.get("close"),
+        quantity: row.get("quantity"),
+        amount: row.get("amount"),
+        trade_count: row.get("trade_count"),
+        daily_change: row.get("daily_change"),
+    }
+}
+
+pub(crate) fn symbol_from_row(row: &SqliteRow) -> SymbolInfo {
+    SymbolInfo {
+        exchange: row.get("exchange"),
+        pair: row.get("pair"),
+        exchange_symbol: row.get("exchange_symbol"),
+        base: row.get("base"),
+        quote: row.get("quote"),
+        status: row.get("status"),
+        trading: row.get("trading"),
+        price_precision: row.get("price_precision"),
+        quantity_precision: row.get("quantity_precision"),
+        min_quantity: row.get("min_quantity"),
+        min_amount: row.get("min_amount"),
+    }
+}
+
+pub(crate) fn indicator_from_row(row: &SqliteRow) -> IndicatorPoint {
+    IndicatorPoint {
+        pair: row.get("pair"),
+        time_frame: row.get("time_frame"),
+        utc_begin: row.get("utc_begin"),
+        indicator: row.get("indicator"),
+        params: row.get("params"),
+        value: row.get("value"),
+    }
+}
+
+impl KlineStore for SqliteStore {
+    fn save_klines<'a>(&'a self, klines: &'a [Kline]) -> StoreFuture<'a, ()> {
+        Box::pin(save_klines(&self.writer, klines))
+    }
+
+    fn upsert_klines<'a>(&'a self, klines: &'a [Kline]) -> StoreFuture<'a, ()> {
+        Box::pin(upsert_klines(&self.writer, klines))
+    }
+
+    fn load_series(&self) -> StoreFuture<'_, Vec<KlineKey>> {
+        Box::pin(
+            sqlx::query_as(
+                "SELECT DISTINCT pair, time_frame FROM klines ORDER BY pair, time_frame",
+            )
+            .fetch_all(&self.reader),
+        )
+    }
+
+    fn delete_klines<'a>(&'a self, filter: &'a KlineFilter) -> StoreFuture<'a, u64> {
+        Box::pin(async move {
+            let mut builder = QueryBuilder::<Sqlite>::new("DELETE FROM klines");
+            filter.push_where(&mut builder);
+            Ok(builder.build().execute(&self.writer).await?.rows_affected())
+        })
+    }
+
+    fn stream_klines<'a>(&'a self, filter: &'a KlineFilter) -> StoreStream<'a, Kline> {
+        Box::pin(try_stream! {
+            let mut builder = QueryBuilder::<Sqlite>::new(
+                format!("SELECT {KLINE_COLUMNS} FROM klines"),
+            );
+            filter.push_where(&mut builder);
+            builder.push(" ORDER BY utc_begin, id");
+            let mut rows = builder.build().fetch(&self.reader);
+            while let Some(row) = rows.try_next().await? {
+                yield kline_from_row(&row);
+            }
+        })
+    }
+}
+
+impl TradeStore for SqliteStore {
+    fn save_trades<'a>(&'a self, trades: &'a [RecentTrade]) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            if trades.is_empty() {
+                return Ok(());
+            }
+            let mut tx = self.writer.begin().await?;
+            for chunk in trades.chunks(ROWS_PER_INSERT) {
+                let mut builder = QueryBuilder::<Sqlite>::new(format!(
+                    "INSERT OR IGNORE INTO recent_trades ({TRADE_COLUMNS}) "
+                ));
+                builder.push_values(chunk, |mut row, trade| {
+                    row.push_bind(&trade.tid)
+                        .push_bind(&trade.pair)
+                        .push_bind(&trade.price)
+                        .push_bind(&trade.amount)
+                        .push_bind(&trade.side)
+                        .push_bind(trade.timestamp);
+                });
+                builder.build().execute(&mut *tx).await?;
+            }
+            tx.commit().await
+        })
+    }
+
+    fn stream_trades<'a>(&'a self, filter: &'a TradeFilter) -> StoreStream<'a, RecentTrade> {
+        Box::pin(try_stream! {
+            let mut builder = QueryBuilder::<Sqlite>::new(
+                format!("SELECT {TRADE_COLUMNS} FROM recent_trades"),
+            );
+            filter.push_where(&mut builder);
+            builder.push(" ORDER BY timestamp, tid");
+            let mut rows = builder.build().fetch(&self.reader);
+            while let Some(row) = rows.try_next().await? {
+                yield trade_from_row(&row);
+            }
+        })
+    }
+
+    fn delete_trades<'a>(&'a self, filter: &'a TradeFilter) -> StoreFuture<'a, u64> {
+        Box::pin(async move {
+            let mut builder = QueryBuilder::<Sqlite>::new("DELETE FROM recent_trades");
+            filter.push_where(&mut builder);
+            Ok(builder.build().execute(&self.writer).await?.rows_affected())
+        })
+    }
+}
+
+impl OrderBookStore for SqliteStore {
+    fn save_order_book_snapshots<'a>(
+        &'a self,
+        snapshots: &'a [OrderBookSnapshot],
+    ) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let rows = snapshot_rows(snapshots);
+            if rows.is_empty() {
+                return Ok(());
+            }
+            let mut tx = self.writer.begin().await?;
+            for chunk in rows.chunks(ROWS_PER_INSERT) {
+                let mut builder = QueryBuilder::<Sqlite>::new(
+                    "INSERT INTO order_book_snapshots (pair, timestamp, side, level, price, quantity) ",
+                );
+                builder.push_values(
+                    chunk,
+                    |mut row, (pair, timestamp, side, level, price, quantity)| {
+                        row.push_bind(pair)
+                            .push_bind(timestamp)
+                            .push_bind(side)
+                            .push_bind(level)
+                            .push_bind(price)
+                            .push_bind(quantity);
+                    },
+                );
+                builder.build().execute(&mut *tx).await?;
+            }
+            tx.commit().await
+        })
+    }
+
+    fn load_order_book_snapshots<'a>(
+        &'a self,
+        filter: &'a SnapshotFilter,
+    ) -> StoreFuture<'a, Vec<OrderBookSnapshot>> {
+        Box::pin(async move {
+            let mut builder = QueryBuilder::<Sqlite>::new(
+                "SELECT pair, timestamp, side, level, price, quantity FROM order_book_snapshots",
+            );
+            filter.push_where(&mut builder);
+            builder.push(" ORDER BY timestamp, pair, side, level");
+            let rows: Vec<SnapshotRow> = builder.build_query_as().fetch_all(&self.reader).await?;
+            Ok(snapshots_from_rows(rows))
+        })
+    }
+}
+
+impl TickerStore for SqliteStore {
+    fn save_tickers<'a>(&'a self, tickers: &'a [Ticker]) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            if tickers.is_empty() {
+                return Ok(());
+            }
+            let mut tx = self.writer.begin().await?;
+            for chunk in tickers.chunks(ROWS_PER_INSERT) {
+                let mut builder = QueryBuilder::<Sqlite>::new(
+                    "INSERT OR IGNORE INTO tickers (pair, timestamp, open, high, low, close, quantity, amount, trade_count, daily_change) ",
+                );
+                builder.push_values(chunk, |mut row, ticker| {
+                    row.push_bind(&ticker.pair)
+                        .push_bind(ticker.timestamp)
+                        .push_bind(ticker.open)
+                        .push_bind(ticker.high)
+                        .push_bind(ticker.low)
+                        .push_bind(ticker.close)
+                        .push_bind(ticker.quantity)
+                        .push_bind(ticker.amount)
+                        .push_bind(ticker.trade_count)
+                        .push_bind(ticker.daily_change);
+                });
+                builder.build().execute(&mut *tx).await?;
+            }
+            tx.commit().await
+        })
+    }
+
+    fn load_tickers<'a>(&'a self, filter: &'a TickerFilter) -> StoreFuture<'a, Vec<Ticker>> {
+        Box::pin(async move {
+            let mut builder = QueryBuilder::<Sqlite>::new(
+                "SELECT pair, timestamp, open, high, low, close, quantity, amount, trade_count, daily_change FROM tickers",
+            );
+            filter.push_where(&mut builder);
+            builder.push(" ORDER BY timestamp, pair");
+            let rows = builder.build().fetch_all(&self.reader).await?;
+            Ok(rows.iter().map(ticker_from_row).collect())
+        })
+    }
+}
+
+impl SymbolStore for SqliteStore {
+    fn save_symbols<'a>(&'a self, symbols: &'a [SymbolInfo]) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            if symbols.is_empty() {
+                return Ok(());
+            }
+            let mut tx = self.writer.begin().await?;
+            for chunk in symbols.chunks(ROWS_PER_INSERT) {
+                let mut builder = QueryBuilder::<Sqlite>::new(
+                    "INSERT INTO symbols (exchange, pair, exchange_symbol, base, quote, status, trading, price_precision, quantity_precision, min_quantity, min_amount) ",
+                );
+                builder.push_values(chunk, |mut row, symbol| {
+                    row.push_bind(&symbol.exchange)
+                        .push_bind(&symbol.pair)
+                        .push_bind(&symbol.exchange_symbol)
+                        .push_bind(&symbol.base)
+                        .push_bind(&symbol.quote)
+                        .push_bind(&symbol.status)
+                        .push_bind(symbol.trading)
+                        .push_bind(symbol.price_precision)
+                        .push_bind(symbol.quantity_precision)
+                        .push_bind(symbol.min_quantity)
+                        .push_bind(symbol.min_amount);
+                });
+                builder.push(SYMBOLS_UPSERT);
+                builder.build().execute(&mut *tx).await?;
+            }
+            tx.commit().await
+        })
+    }
+
+    fn load_symbols<'a>(&'a self, exchange: &'a str) -> StoreFuture<'a, Vec<SymbolInfo>> {
+        Box::pin(async move {
+            let rows = sqlx::query(
+                "SELECT exchange, pair, exchange_symbol, base, quote, status, trading, price_precision, quantity_precision, min_quantity, min_amount FROM symbols WHERE exchange = ? ORDER BY pair",
+            )
+            .bind(exchange)
+            .fetch_all(&self.reader)
+            .await?;
+            Ok(rows.iter().map(symbol_from_row).collect())
+        })
+    }
+}
+
+impl IndicatorStore for SqliteStore {
+    fn save_indicators<'a>(&'a self, points: &'a [IndicatorPoint]) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            if points.is_empty() {
+                return Ok(());
+            }
+            let mut tx = self.writer.begin().await?;
+            for chunk in points.chunks(ROWS_PER_INSERT) {
+                let mut builder = QueryBuilder::<Sqlite>::new(
+                    "INSERT INTO indicators (pair, time_frame, utc_begin, indicator, params, value) ",
+                );
+                builder.push_values(chunk, |mut row, point| {
+                    row.push_bind(&point.pair)
+                        .push_bind(&point.time_frame)
+                        .push_bind(point.utc_begin)
+                        .push_bind(&point.indicator)
+                        .push_bind(&point.params)
+                        .push_bind(point.value);
+                });
+                builder.push(INDICATORS_UPSERT);
+                builder.build().execute(&mut *tx).await?;
+            }
+            tx.commit().await
+        })
+    }
+
+    fn load_indicators<'a>(
+        &'a self,
+        filter: &'a IndicatorFilter,
+    ) -> StoreFuture<'a, Vec<IndicatorPoint>> {
+        Box::pin(async move {
+            let mut builder = QueryBuilder::<Sqlite>::new(
+                "SELECT pair, time_frame, utc_begin, indicator, params, value FROM indicators",
+            );
+            filter.push_where(&mut builder);
+            builder.push(" ORDER BY utc_begin, pair, time_frame, indicator, params");
+            let rows = builder.build().fetch_all(&self.reader).await?;
+            Ok(rows.iter().map(indicator_from_row).collect())
+        })
+    }
+}
+
+impl MaintenanceStore for SqliteStore {
+    /*
+        Incremental vacuuming needs `auto_vacuum = INCREMENTAL`, which only takes effect with a
+        `VACUUM`: a database created without it is rewritten once, the first time. Afterwards the
+        WAL is truncated, since the vacuum went through it.
+    */
+    fn reclaim_space(&self, mode: VacuumMode) -> StoreFuture<'_, ()> {
+        Box::pin(async move {
+            match mode {
+                VacuumMode::None => return Ok(()),
+                VacuumMode::Full => {
+                    sqlx::query("VACUUM").execute(&self.writer).await?;
+                }
+                VacuumMode::Incremental => {
+                    let auto_vacuum: i64 = sqlx::query_scalar("PRAGMA auto_vacuum")
+                        .fetch_one(&self.writer)
+                        .await?;
+                    if auto_vacuum != 2 {
+                        let mut conn = self.writer.acquire().await?;
+                        sqlx::query("PRAGMA auto_vacuum = INCREMENTAL")
+                            .execute(&mut *conn)
+                            .await?;
+                        sqlx::query("VACUUM").execute(&mut *conn).await?;
+                    }
+                    sqlx::query("PRAGMA incremental_vacuum")
+                        .execute(&self.writer)
+                        .await?;
+                }
+            }
+            sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+                .execute(&self.writer)
+                .await?;
+            Ok(())
+        })
+    }
+
+    /// `VACUUM INTO` a read connection: writers go on meanwhile, the copy is compacted
+    fn backup<'a>(&'a self, target: &'a Path) -> StoreFuture<'a, Vec<PathBuf>> {
+        Box::pin(async move {
+            sqlx::query("VACUUM INTO ?")
+                .bind(target.to_string_lossy().to_string())
+                .execute(&self.reader)
+                .await?;
+            Ok(vec![target.to_path_buf()])
+        })
+    }
+}
@@ -0,0 +1,682 @@
+use std::{fmt, future::Future, path::Path, path::PathBuf, pin::Pin, str::FromStr, sync::Arc};
+
+use futures_util::{Stream, TryStreamExt};
+use sqlx::{Database, QueryBuilder};
+use tracing::info;
+
+use super::{
+    establish_connection, memory_store::MemoryStore, partitions::PartitionedStore,
+    postgres_store::PostgresStore, sqlite_store::SqliteStore, SqliteTuning,
+};
+use crate::indicators::IndicatorPoint;
+use crate::parser::{
+    kline::Kline,
+    order_book::{OrderBookSnapshot, PriceLevel},
+    recent_trade::RecentTrade,
+    symbol::SymbolInfo,
+    ticker::Ticker,
+    KlineKey,
+};
+
+/// Result of a storage operation
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, sqlx::Error>> + Send + 'a>>;
+/// Rows read one by one, so large tables never have to fit in memory
+pub type StoreStream<'a, T> = Pin<Box<dyn Stream<Item = Result<T, sqlx::Error>> + Send + 'a>>;
+
+/// Selects klines; every unset field matches everything, `from`/`to` bound `utc_begin` inclusively
+#[derive(Debug, Clone, Default)]
+pub struct KlineFilter {
+    pub pair: Option<String>,
+    pub time_frame: Option<String>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+impl KlineFilter {
+    pub fn matches(&self, kline: &Kline) -> bool {
+        equals(&self.pair, &kline.pair)
+            && equals(&self.time_frame, &kline.time_frame)
+            && within(kline.utc_begin, self.from, self.to)
+    }
+
+    /// Appends the WHERE clause for the `klines` table
+    pub(crate) fn push_where<'a, DB>(&'a self, builder: &mut QueryBuilder<'a, DB>)
+    where
+        DB: Database,
+        String: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+        i64: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+    {
+        builder.push(" WHERE 1 = 1");
+        push_equals(builder, "pair", &self.pair);
+        push_equals(builder, "time_frame", &self.time_frame);
+        push_within(builder, "utc_begin", self.from, self.to);
+    }
+}
+
+/// Selects trades; `from`/`to` bound `timestamp` inclusively
+#[derive(Debug, Clone, Default)]
+pub struct TradeFilter {
+    pub pair: Option<String>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+impl TradeFilter {
+    pub fn matches(&self, trade: &RecentTrade) -> bool {
+        equals(&self.pair, &trade.pair) && within(trade.timestamp, self.from, self.to)
+    }
+
+    /// Appends the WHERE clause for the `recent_trades` table
+    pub(crate) fn push_where<'a, DB>(&'a self, builder: &mut QueryBuilder<'a, DB>)
+    where
+        DB: Database,
+        String: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+        i64: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+    {
+        builder.push(" WHERE 1 = 1");
+        push_equals(builder, "pair", &self.pair);
+        push_within(builder, "timestamp", self.from, self.to);
+    }
+}
+
+/// Selects order book snapshots; `from`/`to` bound `timestamp` inclusively
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotFilter {
+    pub pair: Option<String>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+impl SnapshotFilter {
+    pub fn matches(&self, snapshot: &OrderBookSnapshot) -> bool {
+        equals(&self.pair, &snapshot.pair) && within(snapshot.timestamp, self.from, self.to)
+    }
+
+    /// Appends the WHERE clause for the `order_book_snapshots` table
+    pub(crate) fn push_where<'a, DB>(&'a self, builder: &mut QueryBuilder<'a, DB>)
+    where
+        DB: Database,
+        String: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+        i64: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+    {
+        builder.push(" WHERE 1 = 1");
+        push_equals(builder, "pair", &self.pair);
+        push_within(builder, "timestamp", self.from, self.to);
+    }
+}
+
+/// Selects tickers; `from`/`to` bound `timestamp` inclusively
+#[derive(Debug, Clone, Default)]
+pub struct TickerFilter {
+    pub pair: Option<String>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+impl TickerFilter {
+    pub fn matches(&self, ticker: &Ticker) -> bool {
+        equals(&self.pair, &ticker.pair) && within(ticker.timestamp, self.from, self.to)
+    }
+
+    /// Appends the WHERE clause for the `tickers` table
+    pub(crate) fn push_where<'a, DB>(&'a self, builder: &mut QueryBuilder<'a, DB>)
+    where
+        DB: Database,
+        String: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+        i64: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+    {
+        builder.push(" WHERE 1 = 1");
+        push_equals(builder, "pair", &self.pair);
+        push_within(builder, "timestamp", self.from, self.to);
+    }
+}
+
+/// Selects indicator values; `from`/`to` bound `utc_begin` inclusively
+#[derive(Debug, Clone, Default)]
+pub struct IndicatorFilter {
+    pub pair: Option<String>,
+    pub time_frame: Option<String>,
+    pub indicator: Option<String>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+impl IndicatorFilter {
+    pub fn matches(&self, point: &IndicatorPoint) -> bool {
+        equals(&self.pair, &point.pair)
+            && equals(&self.time_frame, &point.time_frame)
+            && equals(&self.indicator, &point.indicator)
+            && within(point.utc_begin, self.from, self.to)
+    }
+
+    /// Appends the WHERE clause for the `indicators` table
+    pub(crate) fn push_where<'a, DB>(&'a self, builder: &mut QueryBuilder<'a, DB>)
+    where
+        DB: Database,
+        String: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+        i64: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+    {
+        builder.push(" WHERE 1 = 1");
+        push_equals(builder, "pair", &self.pair);
+        push_equals(builder, "time_frame", &self.time_frame);
+        push_equals(builder, "indicator", &self.indicator);
+        push_within(builder, "utc_begin", self.from, self.to);
+    }
+}
+
+/*
+    Clauses shared by the filters above: an unset value matches everything,
+    `from` and `to` are both inclusive
+*/
+fn equals(expected: &Option<String>, value: &str) -> bool {
+    expected.as_deref().is_none_or(|e| e == value)
+}
+
+fn within(value: i64, from: Option<i64>, to: Option<i64>) -> bool {
+    from.is_none_or(|from| value >= from) && to.is_none_or(|to| value <= to)
+}
+
+fn push_equals<'a, DB>(builder: &mut QueryBuilder<'a, DB>, column: &str, value: &Option<String>)
+where
+    DB: Database,
+    String: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+{
+    if let Some(value) = value {
+        builder
+            .push(format!(" AND {column} = "))
+            .push_bind(value.clone());
+    }
+}
+
+fn push_within<'a, DB>(
+    builder: &mut QueryBuilder<'a, DB>,
+    column: &str,
+    from: Option<i64>,
+    to: Option<i64>,
+) where
+    DB: Database,
+    i64: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+{
+    if let Some(from) = from {
+        builder.push(format!(" AND {column} >= ")).push_bind(from);
+    }
+    if let Some(to) = to {
+        builder.push(format!(" AND {column} <= ")).push_bind(to);
+    }
+}
+
+/// A row of `order_book_snapshots`: pair, timestamp, side, level, price, quantity
+pub(crate) type SnapshotRow = (String, i64, String, i64, f64, f64);
+
+/// One row per level, `level` 0 being the best price of the side
+pub(crate) fn snapshot_rows(snapshots: &[OrderBookSnapshot]) -> Vec<SnapshotRow> {
+    let mut rows = Vec::new();
+    for snapshot in snapshots {
+        for (side, levels) in [("bid", &snapshot.bids), ("ask", &snapshot.asks)] {
+            for (level, l) in levels.iter().enumerate() {
+                rows.push((
+                    snapshot.pair.clone(),
+                    snapshot.timestamp,
+                    side.to_string(),
+                    level as i64,
+                    l.price,
+                    l.quantity,
+                ));
+            }
+        }
+    }
+    rows
+}
+
+/// Rebuilds snapshots from rows ordered by timestamp, pair, side and level
+pub(crate) fn snapshots_from_rows(rows: Vec<SnapshotRow>) -> Vec<OrderBookSnapshot> {
+    let mut snapshots: Vec<OrderBookSnapshot> = Vec::new();
+    for (pair, timestamp, side, _, price, quantity) in rows {
+        let same = snapshots
+            .last()
+            .is_some_and(|s| s.pair == pair && s.timestamp == timestamp);
+        if !same {
+            snapshots.push(OrderBookSnapshot {
+                pair,
+                timestamp,
+                bids: Vec::new(),
+                asks: Vec::new(),
+            });
+        }
+        let snapshot = snapshots.last_mut().unwrap();
+        let level = PriceLevel { price, quantity };
+        if side == "bid" {
+            snapshot.bids.push(level);
+        } else {
+            snapshot.asks.push(level);
+        }
+    }
+    snapshots
+}
+
+/// Where the aggregator writes klines
+pub trait KlineStore: Send + Sync {
+    fn save_klines<'a>(&'a self, klines: &'a [Kline]) -> StoreFuture<'a, ()>;
+
+    /// Saves klines, replacing stored ones with the same (pair, time_frame, utc_begin)
+    fn upsert_klines<'a>(&'a self, klines: &'a [Kline]) -> StoreFuture<'a, ()>;
+
+    /// Klines matching the filter, ordered by `utc_begin`
+    fn stream_klines<'a>(&'a self, filter: &'a KlineFilter) -> StoreStream<'a, Kline>;
+
+    /// The stored (pair, time_frame) series, sorted
+    fn load_series(&self) -> StoreFuture<'_, Vec<KlineKey>>;
+
+    /// Deletes the klines matching the filter, returns how many rows went
+    fn delete_klines<'a>(&'a self, filter: &'a KlineFilter) -> StoreFuture<'a, u64>;
+
+    /// The same as `stream_klines`, collected
+    fn load_klines<'a>(&'a self, filter: &'a KlineFilter) -> StoreFuture<'a, Vec<Kline>> {
+        Box::pin(self.stream_klines(filter).try_collect())
+    }
+}
+
+/// Where recent trades are written; a trade already stored under the same `tid` is kept as is
+pub trait TradeStore: Send + Sync {
+    fn save_trades<'a>(&'a self, trades: &'a [RecentTrade]) -> StoreFuture<'a, ()>;
+
+    /// Trades matching the filter, ordered by `timestamp`
+    fn stream_trades<'a>(&'a self, filter: &'a TradeFilter) -> StoreStream<'a, RecentTrade>;
+
+    /// Deletes the trades matching the filter, returns how many rows went
+    fn delete_trades<'a>(&'a self, filter: &'a TradeFilter) -> StoreFuture<'a, u64>;
+
+    /// The same as `stream_trades`, collected
+    fn load_trades<'a>(&'a self, filter: &'a TradeFilter) -> StoreFuture<'a, Vec<RecentTrade>> {
+        Box::pin(self.stream_trades(filter).try_collect())
+    }
+}
+
+/// Where the top levels of the order books are stored periodically
+pub trait OrderBookStore: Send + Sync {
+    fn save_order_book_snapshots<'a>(
+        &'a self,
+        snapshots: &'a [OrderBookSnapshot],
+    ) -> StoreFuture<'a, ()>;
+
+    /// Snapshots matching the filter, ordered by `timestamp`
+    fn load_order_book_snapshots<'a>(
+        &'a self,
+        filter: &'a SnapshotFilter,
+    ) -> StoreFuture<'a, Vec<OrderBookSnapshot>>;
+}
+
+/// Where tickers are written; a ticker already stored for the same (pair, timestamp) is kept as is
+pub trait TickerStore: Send + Sync {
+    fn save_tickers<'a>(&'a self, tickers: &'a [Ticker]) -> StoreFuture<'a, ()>;
+
+    /// Tickers matching the filter, ordered by `timestamp`
+    fn load_tickers<'a>(&'a self, filter: &'a TickerFilter) -> StoreFuture<'a, Vec<Ticker>>;
+}
+
+/// Replaces every column but the key, so a sync leaves the latest metadata
+pub(crate) const SYMBOLS_UPSERT: &str = " ON CONFLICT (exchange, pair) DO UPDATE SET \
+    exchange_symbol = excluded.exchange_symbol, base = excluded.base, quote = excluded.quote, \
+    status = excluded.status, trading = excluded.trading, price_precision = excluded.price_precision, \
+    quantity_precision = excluded.quantity_precision, min_quantity = excluded.min_quantity, \
+    min_amount = excluded.min_amount";
+
+/// Where the markets metadata is kept; saving replaces what is stored for the same (exchange, pair)
+pub trait SymbolStore: Send + Sync {
+    fn save_symbols<'a>(&'a self, symbols: &'a [SymbolInfo]) -> StoreFuture<'a, ()>;
+
+    /// The stored markets of one exchange, ordered by pair
+    fn load_symbols<'a>(&'a self, exchange: &'a str) -> StoreFuture<'a, Vec<SymbolInfo>>;
+}
+
+/// A recomputed value (the last candle changes until it closes) replaces the stored one
+pub(crate) const INDICATORS_UPSERT: &str =
+    " ON CONFLICT (pair, time_frame, utc_begin, indicator, params) DO UPDATE SET value = excluded.value";
+
+/// Where computed indicator series are kept, one row per (pair, time_frame, utc_begin, indicator, params)
+pub trait IndicatorStore: Send + Sync {
+    fn save_indicators<'a>(&'a self, points: &'a [IndicatorPoint]) -> StoreFuture<'a, ()>;
+
+    /// Values matching the filter, ordered by `utc_begin`
+    fn load_indicators<'a>(
+        &'a self,
+        filter: &'a IndicatorFilter,
+    ) -> StoreFuture<'a, Vec<IndicatorPoint>>;
+}
+
+/// How the space of deleted rows is given back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VacuumMode {
+    /// Left for new rows, the file doesn't shrink
+    None,
+    /// `VACUUM`: rewrites the whole database, which holds the write lock for as long
+    Full,
+    /// `incremental_vacuum`: frees the unused pages only, after a one-time `VACUUM` enabling it
+    Incremental,
+}
+
+impl FromStr for VacuumMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "none" => Ok(VacuumMode::None),
+            "full" => Ok(VacuumMode::Full),
+            "incremental" => Ok(VacuumMode::Incremental),
+            other => Err(format!(
+                "unknown vacuum mode '{}', expected none, full or incremental",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for VacuumMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VacuumMode::None => write!(f, "none"),
+            VacuumMode::Full => write!(f, "full"),
+            VacuumMode::Incremental => write!(f, "incremental"),
+        }
+    }
+}
+
+/// Housekeeping of the storage itself
+pub trait MaintenanceStore: Send + Sync {
+    /// Gives the space of deleted rows back, as far as the backend can
+    fn reclaim_space(&self, mode: VacuumMode) -> StoreFuture<'_, ()>;
+
+    /// Writes a consistent copy of the database to `target` (which must not exist) while it is
+    /// in use, returns the files written
+    fn backup<'a>(&'a self, target: &'a Path) -> StoreFuture<'a, Vec<PathBuf>>;
+}
+
+/// A complete storage backend
+pub trait Store:
+    KlineStore
+    + TradeStore
+    + OrderBookStore
+    + TickerStore
+    + SymbolStore
+    + IndicatorStore
+    + MaintenanceStore
+{
+}
+
+impl<T> Store for T where
+    T: KlineStore
+        + TradeStore
+        + OrderBookStore
+        + TickerStore
+        + SymbolStore
+        + IndicatorStore
+        + MaintenanceStore
+{
+}
+
+/// The file of a SQLite DB_URL
+pub fn sqlite_file(db_url: &str) -> &str {
+    db_url
+        .strip_prefix("sqlite://")
+        .or_else(|| db_url.strip_prefix("sqlite:"))
+        .unwrap_or(db_url)
+}
+
+/*
+    Opens the storage backend selected by the DB_URL scheme:
+        postgres://... or postgresql://...  - PostgreSQL
+        memory://                           - in-memory, nothing survives the process
+        sqlite://<file>, sqlite:<file>, <file> - SQLite file, or files with `partition_by`
+*/
+pub async fn open_store(
+    db_url: &str,
+    tuning: &SqliteTuning,
+) -> Result<Arc<dyn Store>, sqlx::Error> {
+    if db_url.starts_with("postgres://") || db_url.starts_with("postgresql://") {
+        info!("Using PostgreSQL storage");
+        return Ok(Arc::new(PostgresStore::connect(db_url).await?));
+    }
+    if db_url.starts_with("memory:") {
+        info!("Using in-memory storage");
+        return Ok(Arc::new(MemoryStore::new()));
+    }
+    let filename = sqlite_file(db_url);
+    if let Some(scheme) = tuning.partition_by {
+        info!("Using partitioned SQLite storage: {}", filename);
+        return Ok(Arc::new(
+            PartitionedStore::open(filename, scheme, tuning).await?,
+        ));
+    }
+    info!("Using SQLite storage: {}", filename);
+    Ok(Arc::new(SqliteStore::new(
+        establish_connection(filename, tuning).await?,
+    )))
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn trade(tid: &str, timestamp: i64) -> RecentTrade {
+        RecentTrade {
+            tid: tid.to_string(),
+            pair: "BTC_USDT".to_string(),
+            price: "30000.5".to_string(),
+            amount: "0.01".to_string(),
+            side: "buy".to_string(),
+            timestamp,
+        }
+    }
+
+    /// The same scenario for every backend
+    pub(crate) async fn check_store(store: &dyn Store) {
+        store
+            .save_klines(&[
+                Kline::sample("BTC_USDT", "MINUTE_1", 180),
+                Kline::sample("BTC_USDT", "MINUTE_1", 60),
+                Kline::sample("BTC_USDT", "HOUR_1", 0),
+                Kline::sample("ETH_USDT", "MINUTE_1", 120),
+            ])
+            .await
+            .unwrap();
+        let filter = KlineFilter {
+            pair: Some("BTC_USDT".to_string()),
+            time_frame: Some("MINUTE_1".to_string()),
+            from: Some(60),
+            to: None,
+        };
+        let klines = store.load_klines(&filter).await.unwrap();
+        let begins: Vec<i64> = klines.iter().map(|k| k.utc_begin).collect();
+        assert_eq!(begins, vec![60, 180]);
+        assert_eq!(klines[0].volume_bs.sell_quote, 4.0);
+        assert_eq!(
+            store
+                .load_klines(&KlineFilter::default())
+                .await
+                .unwrap()
+                .len(),
+            4
+        );
+
+        let mut replacement = Kline::sample("BTC_USDT", "MINUTE_1", 60);
+        replacement.c = 1.75;
+        store
+            .upsert_klines(&[replacement, Kline::sample("BTC_USDT", "MINUTE_1", 240)])
+            .await
+            .unwrap();
+        let klines = store.load_klines(&filter).await.unwrap();
+        let begins: Vec<i64> = klines.iter().map(|k| k.utc_begin).collect();
+        assert_eq!(begins, vec![60, 180, 240]);
+        assert_eq!(klines[0].c, 1.75);
+        assert_eq!(
+            store.load_series().await.unwrap(),
+            vec![
+                ("BTC_USDT".to_string(), "HOUR_1".to_string()),
+                ("BTC_USDT".to_string(), "MINUTE_1".to_string()),
+                ("ETH_USDT".to_string(), "MINUTE_1".to_string()),
+            ]
+        );
+        let eth = KlineFilter {
+            pair: Some("ETH_USDT".to_string()),
+            ..KlineFilter::default()
+        };
+        assert_eq!(store.delete_klines(&eth).await.unwrap(), 1);
+        assert_eq!(store.load_series().await.unwrap().len(), 2);
+
+        store
+            .save_trades(&[trade("2", 20), trade("1", 10)])
+            .await
+            .unwrap();
+        store.save_trades(&[trade("1", 99)]).await.unwrap(); // duplicate tid is ignored
+        let trades = store.load_trades(&TradeFilter::default()).await.unwrap();
+        let stamps: Vec<i64> = trades.iter().map(|t| t.timestamp).collect();
+        assert_eq!(stamps, vec![10, 20]);
+        let old = TradeFilter {
+            to: Some(15),
+            ..TradeFilter::default()
+        };
+        assert_eq!(store.delete_trades(&old).await.unwrap(), 1);
+        assert!(store.load_trades(&old).await.unwrap().is_empty());
+
+        let level = |price: f64| PriceLevel {
+            price,
+            quantity: 1.0,
+        };
+        let snapshot = |pair: &str, timestamp: i64| OrderBookSnapshot {
+            pair: pair.to_string(),
+            timestamp,
+            bids: vec![level(99.0), level(98.0)],
+            asks: vec![level(101.0)],
+        };
+        store
+            .save_order_book_snapshots(&[
+                snapshot("BTC_USDT", 2_000),
+                snapshot("ETH_USDT", 1_000),
+                snapshot("BTC_USDT", 1_000),
+            ])
+            .await
+            .unwrap();
+        let filter = SnapshotFilter {
+            pair: Some("BTC_USDT".to_string()),
+            ..SnapshotFilter::default()
+        };
+        assert_eq!(
+            store.load_order_book_snapshots(&filter).await.unwrap(),
+            vec![snapshot("BTC_USDT", 1_000), snapshot("BTC_USDT", 2_000)]
+        );
+
+        let ticker = |pair: &str, timestamp: i64, close: f64| Ticker {
+            pair: pair.to_string(),
+            timestamp,
+            open: 100.0,
+            high: 110.0,
+            low: 90.0,
+            close,
+            quantity: 5.0,
+            amount: 500.0,
+            trade_count: 42,
+            daily_change: (close - 100.0) / 100.0,
+        };
+        store
+            .save_tickers(&[
+                ticker("BTC_USDT", 2_000, 105.0),
+                ticker("ETH_USDT", 1_000, 99.0),
+            ])
+            .await
+            .unwrap();
+        store
+            .save_tickers(&[
+                ticker("BTC_USDT", 1_000, 101.0),
+                ticker("BTC_USDT", 2_000, 0.0),
+            ]) // the second is a duplicate
+            .await
+            .unwrap();
+        let filter = TickerFilter {
+            pair: Some("BTC_USDT".to_string()),
+            ..TickerFilter::default()
+        };
+        assert_eq!(
+            store.load_tickers(&filter).await.unwrap(),
+            vec![
+                ticker("BTC_USDT", 1_000, 101.0),
+                ticker("BTC_USDT", 2_000, 105.0)
+            ]
+        );
+
+        let symbol = |pair: &str, trading: bool| SymbolInfo {
+            exchange: "poloniex".to_string(),
+            pair: pair.to_string(),
+            exchange_symbol: pair.to_string(),
+            base: pair[..3].to_string(),
+            quote: "USDT".to_string(),
+            status: if trading { "NORMAL" } else { "OFFLINE" }.to_string(),
+            trading,
+            price_precision: 2,
+            quantity_precision: 6,
+            min_quantity: 0.000001,
+            min_amount: 1.0,
+        };
+        store
+            .save_symbols(&[symbol("ETH_USDT", true), symbol("BTC_USDT", true)])
+            .await
+            .unwrap();
+        store
+            .save_symbols(&[symbol("ETH_USDT", false)]) // a sync replaces the metadata
+            .await
+            .unwrap();
+        assert_eq!(
+            store.load_symbols("poloniex").await.unwrap(),
+            vec![symbol("BTC_USDT", true), symbol("ETH_USDT", false)]
+        );
+        assert!(store.load_symbols("binance").await.unwrap().is_empty());
+
+        let point = |utc_begin: i64, indicator: &str, value: f64| IndicatorPoint {
+            pair: "BTC_USDT".to_string(),
+            time_frame: "MINUTE_1".to_string(),
+            utc_begin,
+            indicator: indicator.to_string(),
+            params: "20,2".to_string(),
+            value,
+        };
+        store
+            .save_indicators(&[point(60, "bb.upper", 3.0), point(0, "bb.upper", 2.0)])
+            .await
+            .unwrap();
+        store
+            .save_indicators(&[point(60, "bb.upper", 3.5), point(60, "bb.lower", 1.0)]) // the first replaces
+            .await
+            .unwrap();
+        let filter = IndicatorFilter {
+            indicator: Some("bb.upper".to_string()),
+            ..IndicatorFilter::default()
+        };
+        assert_eq!(
+            store.load_indicators(&filter).await.unwrap(),
+            vec![point(0, "bb.upper", 2.0), point(60, "bb.upper", 3.5)]
+        );
+
+        store.reclaim_space(VacuumMode::Incremental).await.unwrap();
+        store.reclaim_space(VacuumMode::Full).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        let store = open_store("memory://", &SqliteTuning::default())
+            .await
+            .unwrap();
+        check_store(store.as_ref()).await;
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_store() {
+        let path = std::env::temp_dir().join(format!("store_{}.db", uuid::Uuid::new_v4()));
+        let url = format!("sqlite://{}", path.display());
+        let store = open_store(&url, &SqliteTuning::default()).await.unwrap();
+        check_store(store.as_ref()).await;
+        drop(store);
+        let _ = std::fs::remove_file(&path);
+    }
+}
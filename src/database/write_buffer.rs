@@ -0,0 +1,291 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{self, MissedTickBehavior};
+use tracing::{debug, debug_span, error, warn, Instrument};
+
+use super::store::KlineStore;
+use crate::error::Error;
+use crate::health::HealthTracker;
+use crate::metrics::metrics;
+use crate::parser::kline::Kline;
+
+/// Thresholds at which the buffer is written to the database
+#[derive(Debug, Clone)]
+pub struct WriteBufferConfig {
+    pub max_rows: usize,         // flush as soon as this many klines are waiting
+    pub max_delay: Duration,     // flush at least this often while there is anything waiting
+    pub max_retries: u32,        // failed writes of the same klines before they are dropped
+    pub max_pending_rows: usize, // klines kept while writes fail; the oldest beyond it are dropped
+}
+
+impl Default for WriteBufferConfig {
+    fn default() -> Self {
+        WriteBufferConfig {
+            max_rows: 5_000,
+            max_delay: Duration::from_millis(500),
+            max_retries: 10,
+            max_pending_rows: 200_000,
+        }
+    }
+}
+
+enum WriteCommand {
+    Push(Vec<Kline>),
+    Flush(oneshot::Sender<Result<(), sqlx::Error>>),
+}
+
+/*
+    Write-buffer actor. Handlers push their klines to it instead of writing them one by one,
+    the actor coalesces everything it receives and writes it with a single call
+    (one `save_klines` of the store, i.e. one transaction) when the size or time threshold is reached.
+    A failed write (a busy database, a lost connection) keeps the klines and is retried on the following
+    ticks; they are dropped only after `max_retries` failures in a row, and while writes fail at most
+    `max_pending_rows` klines are kept.
+    The handle is cheap to clone; the actor stops once every handle is dropped, writing what is left.
+*/
+#[derive(Clone)]
+pub struct KlineWriteBuffer {
+    sender: mpsc::Sender<WriteCommand>,
+}
+
+impl KlineWriteBuffer {
+    /// Starts the actor on the current tokio runtime
+    pub fn spawn(store: Arc<dyn KlineStore>, config: WriteBufferConfig) -> Self {
+        Self::start(store, config, None)
+    }
+
+    /// Like `spawn`, reporting every kline saved to the tracker
+    pub fn spawn_with_health(
+        store: Arc<dyn KlineStore>,
+        config: WriteBufferConfig,
+        health: HealthTracker,
+    ) -> Self {
+        Self::start(store, config, Some(health))
+    }
+
+    fn start(
+        store: Arc<dyn KlineStore>,
+        config: WriteBufferConfig,
+        health: Option<HealthTracker>,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel(1024);
+        let writer = Writer {
+            store,
+            config,
+            health,
+        };
+        tokio::spawn(run_actor(writer, receiver));
+        KlineWriteBuffer { sender }
+    }
+
+    /// Queues klines for writing
+    pub async fn push(&self, klines: Vec<Kline>) -> Result<(), Error> {
+        if klines.is_empty() {
+            return Ok(());
+        }
+        self.sender
+            .send(WriteCommand::Push(klines))
+            .await
+            .map_err(|_| Error::WriteBufferClosed)
+    }
+
+    /// Writes everything queued so far and waits for the result
+    pub async fn flush(&self) -> Result<(), Error> {
+        let (reply, result) = oneshot::channel();
+        self.sender
+            .send(WriteCommand::Flush(reply))
+            .await
+            .map_err(|_| Error::WriteBufferClosed)?;
+        Ok(result.await.map_err(|_| Error::WriteBufferClosed)??)
+    }
+}
+
+/// Where the actor writes, and who hears about it
+struct Writer {
+    store: Arc<dyn KlineStore>,
+    config: WriteBufferConfig,
+    health: Option<HealthTracker>,
+}
+
+/// The klines waiting to be written, and how many times in a row writing them has failed
+struct Pending {
+    klines: Vec<Kline>,
+    failures: u32,
+}
+
+async fn run_actor(writer: Writer, mut receiver: mpsc::Receiver<WriteCommand>) {
+    let mut pending = Pending {
+        klines: Vec::with_capacity(writer.config.max_rows),
+        failures: 0,
+    };
+    let mut ticker = time::interval(writer.config.max_delay);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            command = receiver.recv() => match command {
+                Some(WriteCommand::Push(klines)) => {
+                    pending.klines.extend(klines);
+                    // After a failure the next attempt waits for the ticker
+                    if pending.klines.len() >= writer.config.max_rows && pending.failures == 0 {
+                        let _ = write_pending(&writer, &mut pending).await;
+                    }
+                }
+                Some(WriteCommand::Flush(reply)) => {
+                    let _ = reply.send(write_pending(&writer, &mut pending).await);
+                }
+                None => {
+                    // All handles are gone - write the rest and stop
+                    let _ = write_pending(&writer, &mut pending).await;
+                    break;
+                }
+            },
+            _ = ticker.tick() => {
+                let _ = write_pending(&writer, &mut pending).await;
+            }
+        }
+    }
+}
+
+/// Writes the pending klines; a failed batch is kept for the next attempt until the retries run out
+async fn write_pending(writer: &Writer, pending: &mut Pending) -> Result<(), sqlx::Error> {
+    let config = &writer.config;
+    let klines = &mut pending.klines;
+    if klines.is_empty() {
+        return Ok(());
+    }
+    let timer = metrics().db_write_duration.start_timer();
+    let result = writer
+        .store
+        .save_klines(klines)
+        .instrument(debug_span!("db_write", rows = klines.len()))
+        .await;
+    timer.observe_duration();
+    match &result {
+        Ok(_) => {
+            metrics().record_saved_klines(klines);
+            if let Some(health) = &writer.health {
+                health.record_klines(klines);
+            }
+            debug!("Write buffer saved {} klines", klines.len());
+            klines.clear();
+            pending.failures = 0;
+        }
+        Err(e) => {
+            metrics().db_write_errors.inc();
+            pending.failures += 1;
+            if pending.failures > config.max_retries {
+                error!(
+                    "Failed to save {} klines {} times, dropping them: {}",
+                    klines.len(),
+                    pending.failures,
+                    e
+                );
+                klines.clear();
+                pending.failures = 0;
+            } else {
+                warn!(
+                    "Failed to save {} klines (attempt {}), retrying: {}",
+                    klines.len(),
+                    pending.failures,
+                    e
+                );
+                if klines.len() > config.max_pending_rows {
+                    let excess = klines.len() - config.max_pending_rows;
+                    error!("Write buffer full, dropping the {} oldest klines", excess);
+                    klines.drain(..excess);
+                }
+            }
+        }
+    }
+    result
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::store::{StoreFuture, StoreStream};
+    use crate::database::{KlineFilter, MemoryStore};
+    use crate::parser::KlineKey;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails the first `failures` writes, then writes into memory
+    struct FlakyStore {
+        inner: MemoryStore,
+        failures: AtomicU32,
+    }
+
+    impl KlineStore for FlakyStore {
+        fn save_klines<'a>(&'a self, klines: &'a [Kline]) -> StoreFuture<'a, ()> {
+            if self.failures.load(Ordering::SeqCst) > 0 {
+                self.failures.fetch_sub(1, Ordering::SeqCst);
+                return Box::pin(async { Err(sqlx::Error::PoolTimedOut) });
+            }
+            self.inner.save_klines(klines)
+        }
+
+        fn upsert_klines<'a>(&'a self, klines: &'a [Kline]) -> StoreFuture<'a, ()> {
+            self.inner.upsert_klines(klines)
+        }
+
+        fn stream_klines<'a>(&'a self, filter: &'a KlineFilter) -> StoreStream<'a, Kline> {
+            self.inner.stream_klines(filter)
+        }
+
+        fn load_series(&self) -> StoreFuture<'_, Vec<KlineKey>> {
+            self.inner.load_series()
+        }
+
+        fn delete_klines<'a>(&'a self, filter: &'a KlineFilter) -> StoreFuture<'a, u64> {
+            self.inner.delete_klines(filter)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_writes_are_retried() {
+        let store = Arc::new(FlakyStore {
+            inner: MemoryStore::new(),
+            failures: AtomicU32::new(2),
+        });
+        let config = WriteBufferConfig {
+            max_retries: 2,
+            max_delay: Duration::from_secs(3600), // only the flushes below write
+            ..WriteBufferConfig::default()
+        };
+        let health = HealthTracker::new(Default::default());
+        health.watch(&[("BTC_USDT".to_string(), "MINUTE_1".to_string())]);
+        let buffer = KlineWriteBuffer::spawn_with_health(store.clone(), config, health.clone());
+        let last_saved = || async { health.report().await.series[0].last_utc_begin };
+        let klines = vec![
+            Kline::sample("BTC_USDT", "MINUTE_1", 60_000),
+            Kline::sample("BTC_USDT", "MINUTE_1", 120_000),
+        ];
+        buffer.push(klines.clone()).await.unwrap();
+
+        // Two failures keep the klines, the third attempt writes them; only then are they fresh
+        assert!(buffer.flush().await.is_err());
+        assert!(buffer.flush().await.is_err());
+        assert_eq!(last_saved().await, None);
+        buffer.flush().await.unwrap();
+        assert_eq!(last_saved().await, Some(120_000));
+        let all = KlineFilter::default();
+        assert_eq!(store.load_klines(&all).await.unwrap().len(), 2);
+
+        // Past the retries they are dropped, and the buffer goes on
+        store.failures.store(3, Ordering::SeqCst);
+        buffer.push(klines.clone()).await.unwrap();
+        for _ in 0..3 {
+            assert!(buffer.flush().await.is_err());
+        }
+        buffer.flush().await.unwrap();
+        assert_eq!(store.load_klines(&all).await.unwrap().len(), 2);
+        buffer.push(klines).await.unwrap();
+        buffer.flush().await.unwrap();
+        assert_eq!(store.load_klines(&all).await.unwrap().len(), 4);
+    }
+}
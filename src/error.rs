@@ -0,0 +1,65 @@
+use thiserror::Error;
+
+use crate::{
+    config::ConfigError,
+    exchange::{ExchangeBuilderError, ExchangeFactoryError},
+    http_client::HttpClientError,
+};
+
+/// Errors the library reports to its users (and the binary turns into exit codes)
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Configuration error: {0}")]
+    Config(#[from] ConfigError),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("{0}")]
+    Http(#[from] HttpClientError),
+    #[error("Factory error: {0}")]
+    ExchangeFactory(#[from] ExchangeFactoryError),
+    #[error("Builder error: {0}")]
+    ExchangeBuilder(#[from] ExchangeBuilderError),
+    #[error("Write buffer is closed")]
+    WriteBufferClosed,
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Parquet error: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+    #[error("Arrow error: {0}")]
+    Arrow(#[from] arrow_schema::ArrowError),
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("ZIP error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Import error: {0}")]
+    Import(String),
+    #[error("Unhealthy: {0}")]
+    Unhealthy(String),
+    #[error("{0} discrepancies with the exchange")]
+    Discrepancies(usize),
+    #[error("None of the {0} URLs could be fetched")]
+    NothingFetched(usize),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Process exit code, following the BSD sysexits convention
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Config(_) | Error::ExchangeFactory(_) | Error::ExchangeBuilder(_) => 78, // EX_CONFIG
+            Error::Http(_) | Error::Unhealthy(_) | Error::NothingFetched(_) => 69, // EX_UNAVAILABLE
+            Error::Database(_) | Error::Io(_) => 74,                               // EX_IOERR
+            Error::Json(_)
+            | Error::Parquet(_)
+            | Error::Arrow(_)
+            | Error::Csv(_)
+            | Error::Zip(_)
+            | Error::Import(_)
+            | Error::Discrepancies(_) => 65, // EX_DATAERR
+            Error::WriteBufferClosed => 70,                                        // EX_SOFTWARE
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use std::error::Error;
 use std::fmt;
 
 #[derive(Debug)]
@@ -20,3 +21,32 @@ impl fmt::Display for ExchangeFactoryError {
         }
     }
 }
+
+impl Error for ExchangeFactoryError {}
+
+#[derive(Debug)]
+pub enum ExchangeBuilderError {
+    MissingName,
+    MissingRestUrl,
+    MissingRestClient,
+    MissingParser,
+    MissingCandleAggregator,
+    MissingStore,
+}
+
+impl fmt::Display for ExchangeBuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExchangeBuilderError::MissingName => write!(f, "Name is missing"),
+            ExchangeBuilderError::MissingRestUrl => write!(f, "REST URL is missing"),
+            ExchangeBuilderError::MissingRestClient => write!(f, "RestClient is missing"),
+            ExchangeBuilderError::MissingParser => write!(f, "Parser is missing"),
+            ExchangeBuilderError::MissingCandleAggregator => {
+                write!(f, "CandleAggregator is missing")
+            }
+            ExchangeBuilderError::MissingStore => write!(f, "Storage is missing"),
+        }
+    }
+}
+
+impl Error for ExchangeBuilderError {}
@@ -1,15 +1,16 @@
-mod error;
+pub mod error;
+pub use error::{ExchangeBuilderError, ExchangeFactoryError};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
 
-use sqlx::{Pool, Sqlite};
-use tracing::error;
-use tracing::info;
+use tokio::time::{self, Instant, MissedTickBehavior};
+use tracing::{debug_span, error, info, info_span, warn, Instrument};
 
 use crate::{
     aggregator::CandleAggregator,
     config::settings::Settings,
-    exchange::error::ExchangeFactoryError,
+    database::Store,
+    error::Error,
     http_client::http_client::{ReqwestClient, RestClient},
     parser::KlineParser,
 };
@@ -21,7 +22,7 @@ pub struct Exchange {
     pub rest_client: Box<dyn RestClient>,
     pub parser: KlineParser,
     pub aggregator: Option<Arc<CandleAggregator>>,
-    pub db_pool: Option<Arc<Pool<Sqlite>>>,
+    pub store: Option<Arc<dyn Store>>,
 }
 
 impl Exchange {
@@ -31,7 +32,7 @@ impl Exchange {
         rest_client: Box<dyn RestClient>,
         parser: KlineParser,
         aggregator: Option<Arc<CandleAggregator>>,
-        db_pool: Option<Arc<Pool<Sqlite>>>,
+        store: Option<Arc<dyn Store>>,
     ) -> Self {
         Self {
             name: name.to_string(),
@@ -39,18 +40,12 @@ impl Exchange {
             rest_client,
             parser,
             aggregator,
-            db_pool,
+            store,
         }
     }
 
-    //todo убрать
-    pub async fn connect(&self) -> Result<(), String> {
-        info!("Connecting to {} API at {}", self.name, self.rest_url);
-        Ok(())
-    }
-
-    /// Getting data from API
-    pub async fn run(&self, urls: &[(String, String, String)]) -> Result<(), String> {
+    /// Getting data from API; fails when none of the URLs could be fetched and parsed
+    pub async fn run(&self, urls: &[(String, String, String)]) -> Result<(), Error> {
         // 1. Collect (symbol, timeframe) before the loop
         let keys: Vec<(String, String)> = urls
             .iter()
@@ -59,43 +54,84 @@ impl Exchange {
 
         // 2. Call build_handlers() once before the loop to build a chain of handlers for filtering
         if let Some(aggregator) = self.aggregator.as_ref() {
-            // Clone db_pool to pass to build_handlers
-            if let Some(db_pool) = self.db_pool.clone() {
-                aggregator.clone().build_handlers(&keys, db_pool).await;
-            } else {
-                error!("db_pool is None");
-            }
+            aggregator.build_handlers(&keys).await;
         } else {
             error!("CandleAggregator is not set in ExchangeBuilder");
         }
 
+        self.collect_all(urls).await
+    }
+
+    /// Fetches the URLs again every `interval`, the handlers of `run` in place, until the task is dropped
+    pub async fn poll(&self, urls: &[(String, String, String)], interval: Duration) {
+        let mut timer = time::interval_at(Instant::now() + interval, interval);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            timer.tick().await;
+            if let Err(err) = self.collect_all(urls).await {
+                error!("Collecting from {} failed: {}", self.name, err);
+            }
+        }
+    }
+
+    async fn collect_all(&self, urls: &[(String, String, String)]) -> Result<(), Error> {
         // 3. In the loop we only receive and process data
-        for (key1, key2, url) in urls {
-            match self.rest_client.get(url).await {
-                Ok(data) => {
-                    // Parsing the data
-                    match self.parser.parse(&data, key1) {
-                        Ok(parsed_data) => {
-                            if let Some(aggregator) = self.aggregator.as_ref() {
-                                /* Here you can theoretically send the result of several requests from different Url */
-                                aggregator.http_response_process(parsed_data).await;
-                            } else {
-                                error!("CandleAggregator is not set in ExchangeBuilder");
-                            }
-                        }
-                        Err(parse_error) => {
-                            eprintln!("Failed to parse data from {}: {}", url, parse_error);
-                        }
-                    }
-                }
-                Err(fetch_error) => {
-                    eprintln!("Failed to fetch data from {}: {}", url, fetch_error);
-                }
+        info!("Collecting from {} API at {}", self.name, self.rest_url);
+        let mut failed = 0;
+        for (pair, timeframe, url) in urls {
+            let span =
+                info_span!("collect", exchange = %self.name, pair = %pair, timeframe = %timeframe);
+            if !self.collect(pair, url).instrument(span).await {
+                failed += 1;
             }
         }
 
+        // 4. Make sure everything received has reached the database
+        if let Some(aggregator) = self.aggregator.as_ref() {
+            aggregator.flush().await?;
+        }
+
+        if failed > 0 && failed == urls.len() {
+            return Err(Error::NothingFetched(failed));
+        }
+        if failed > 0 {
+            warn!("{} of {} URLs could not be fetched", failed, urls.len());
+        }
         Ok(())
     }
+
+    /// Fetches one URL and passes the parsed klines to the aggregator; false when it failed
+    async fn collect(&self, pair: &str, url: &str) -> bool {
+        let data = match self
+            .rest_client
+            .get(url)
+            .instrument(debug_span!("fetch", url = %url))
+            .await
+        {
+            Ok(data) => data,
+            Err(fetch_error) => {
+                warn!("Failed to fetch data from {}: {}", url, fetch_error);
+                return false;
+            }
+        };
+
+        // Parsing the data
+        let parsed_data = match debug_span!("parse").in_scope(|| self.parser.parse(&data, pair)) {
+            Ok(parsed_data) => parsed_data,
+            Err(parse_error) => {
+                warn!("Failed to parse data from {}: {}", url, parse_error);
+                return false;
+            }
+        };
+
+        if let Some(aggregator) = self.aggregator.as_ref() {
+            /* Here you can theoretically send the result of several requests from different Url */
+            aggregator.http_response_process(parsed_data).await;
+        } else {
+            error!("CandleAggregator is not set in ExchangeBuilder");
+        }
+        true
+    }
 }
 pub struct ExchangeFactory;
 
@@ -123,26 +159,20 @@ impl ExchangeFactory {
             .set_name(exchange_name)
             .set_rest_url(rest_url)
             .set_rest_client(Box::new(ReqwestClient::new()))) // Return Builder with Exchange configured
-
-        //todo добавить другие
     }
 }
 
-#[derive(Debug)]
-pub enum ExchangeBuilderError {
-    MissingName,
-    MissingRestUrl,
-    MissingRestClient,
-    MissingParser,
-    MissingCandleAggregator,
-    MissingDBPool,
+impl Default for ExchangeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 pub struct ExchangeBuilder {
     name: Option<String>,
     rest_url: Option<String>,
     rest_client: Option<Box<dyn RestClient>>,
-    db_pool: Option<Arc<Pool<Sqlite>>>,
+    store: Option<Arc<dyn Store>>,
     parser: Option<KlineParser>,
     aggregator: Option<Arc<CandleAggregator>>,
 }
@@ -150,12 +180,11 @@ pub struct ExchangeBuilder {
 impl ExchangeBuilder {
     // Create a new empty Builder
     pub fn new() -> Self {
-        let aggregator = CandleAggregator::get_instance();
         Self {
             name: None,
             rest_url: None,
             rest_client: None,
-            db_pool: None,
+            store: None,
             parser: None,
             aggregator: None,
         }
@@ -179,9 +208,9 @@ impl ExchangeBuilder {
         self
     }
 
-    // Set DB pool
-    pub fn set_target_db(mut self, db_pool: Pool<Sqlite>) -> Self {
-        self.db_pool = Some(Arc::new(db_pool));
+    // Set the storage backend
+    pub fn set_target_db(mut self, store: Arc<dyn Store>) -> Self {
+        self.store = Some(store);
         self
     }
 
@@ -204,14 +233,14 @@ impl ExchangeBuilder {
             .ok_or(ExchangeBuilderError::MissingRestClient)?;
         let parser = self.parser.ok_or(ExchangeBuilderError::MissingParser)?;
         let aggregator = self.aggregator.clone();
-        let pool = self.db_pool.ok_or(ExchangeBuilderError::MissingDBPool)?;
+        let store = self.store.ok_or(ExchangeBuilderError::MissingStore)?;
         Ok(Exchange::new(
             &name,
             &rest_url,
             rest_client,
             parser,
             aggregator,
-            Some(pool),
+            Some(store),
         ))
     }
 }
@@ -0,0 +1,192 @@
+pub mod records;
+pub mod writers;
+
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use futures_util::TryStreamExt;
+use tracing::info;
+
+use crate::database::{KlineFilter, KlineStore, TradeFilter, TradeStore};
+use crate::error::Error;
+use records::{ExportRecord, KlineRecord, TradeRecord};
+use writers::{CsvWriter, JsonLinesWriter, ParquetWriter, RecordWriter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    JsonLines,
+    Parquet,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" | "jsonlines" | "ndjson" => Ok(ExportFormat::JsonLines),
+            "parquet" => Ok(ExportFormat::Parquet),
+            other => Err(format!(
+                "unknown format '{}', expected csv, jsonl or parquet",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportFormat::Csv => write!(f, "csv"),
+            ExportFormat::JsonLines => write!(f, "jsonl"),
+            ExportFormat::Parquet => write!(f, "parquet"),
+        }
+    }
+}
+
+fn record_writer<'w, R, W>(
+    format: ExportFormat,
+    out: W,
+) -> Result<Box<dyn RecordWriter<R> + 'w>, Error>
+where
+    R: ExportRecord + 'w,
+    W: Write + Send + 'w,
+{
+    Ok(match format {
+        ExportFormat::Csv => Box::new(CsvWriter::new(out)?),
+        ExportFormat::JsonLines => Box::new(JsonLinesWriter::new(out)),
+        ExportFormat::Parquet => Box::new(ParquetWriter::new(out)?),
+    })
+}
+
+/*
+    Exports the klines matching the filter, row by row from the store's stream,
+    so the table is never loaded into memory. Returns the number of rows written.
+*/
+pub async fn export_klines<W: Write + Send>(
+    store: &dyn KlineStore,
+    filter: &KlineFilter,
+    format: ExportFormat,
+    out: W,
+) -> Result<u64, Error> {
+    let mut writer = record_writer::<KlineRecord, W>(format, out)?;
+    let mut rows = store.stream_klines(filter);
+    let mut count = 0u64;
+    while let Some(kline) = rows.try_next().await? {
+        writer.write(KlineRecord::from(&kline))?;
+        count += 1;
+    }
+    writer.finish()?;
+    info!("Exported {} klines as {}", count, format);
+    Ok(count)
+}
+
+/// The same for trades
+pub async fn export_trades<W: Write + Send>(
+    store: &dyn TradeStore,
+    filter: &TradeFilter,
+    format: ExportFormat,
+    out: W,
+) -> Result<u64, Error> {
+    let mut writer = record_writer::<TradeRecord, W>(format, out)?;
+    let mut rows = store.stream_trades(filter);
+    let mut count = 0u64;
+    while let Some(trade) = rows.try_next().await? {
+        writer.write(TradeRecord::from(&trade))?;
+        count += 1;
+    }
+    writer.finish()?;
+    info!("Exported {} trades as {}", count, format);
+    Ok(count)
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::MemoryStore;
+    use crate::parser::kline::Kline;
+    use arrow_array::{Array, Float64Array, StringArray};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    async fn store_with_klines() -> MemoryStore {
+        let store = MemoryStore::new();
+        let klines: Vec<Kline> = [("BTC_USDT", 60), ("ETH_USDT", 60), ("BTC_USDT", 120)]
+            .iter()
+            .map(|(pair, utc_begin)| Kline {
+                o: 1.5,
+                l: 1.0,
+                c: 1.75,
+                ..Kline::sample(pair, "MINUTE_1", *utc_begin)
+            })
+            .collect();
+        store.save_klines(&klines).await.unwrap();
+        store
+    }
+
+    fn btc_filter() -> KlineFilter {
+        KlineFilter {
+            pair: Some("BTC_USDT".to_string()),
+            ..KlineFilter::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_formats() {
+        let store = store_with_klines().await;
+
+        let mut csv = Vec::new();
+        let count = export_klines(&store, &btc_filter(), ExportFormat::Csv, &mut csv)
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], KlineRecord::COLUMNS.join(","));
+        assert_eq!(lines[1], "BTC_USDT,MINUTE_1,1.5,2,1,1.75,60,1,2,3,4");
+        assert_eq!(lines.len(), 3);
+
+        let mut jsonl = Vec::new();
+        export_klines(&store, &btc_filter(), ExportFormat::JsonLines, &mut jsonl)
+            .await
+            .unwrap();
+        let first: serde_json::Value =
+            serde_json::from_str(String::from_utf8(jsonl).unwrap().lines().next().unwrap())
+                .unwrap();
+        assert_eq!(first["close"], 1.75);
+        assert_eq!(first["utc_begin"], 60);
+
+        let path = std::env::temp_dir().join(format!("export_{}.parquet", uuid::Uuid::new_v4()));
+        let file = std::fs::File::create(&path).unwrap();
+        export_klines(&store, &btc_filter(), ExportFormat::Parquet, file)
+            .await
+            .unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<_> = reader.map(|b| b.unwrap()).collect();
+        let batch = &batches[0];
+        assert_eq!(batch.num_rows(), 2);
+        let pairs = batch
+            .column_by_name("pair")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(pairs.value(1), "BTC_USDT");
+        let close = batch
+            .column_by_name("close")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(close.len(), 2);
+        assert_eq!(close.value(0), 1.75);
+        let _ = std::fs::remove_file(&path);
+    }
+}
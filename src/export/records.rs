@@ -0,0 +1,194 @@
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use serde::Serialize;
+
+use crate::parser::{kline::Kline, recent_trade::RecentTrade};
+
+/// A flat row of an exported table, the same columns in every format
+pub trait ExportRecord: Serialize + Send + Sized {
+    const COLUMNS: &'static [&'static str];
+
+    /// Values in `COLUMNS` order, as CSV text
+    fn csv_fields(&self) -> Vec<String>;
+
+    fn schema() -> SchemaRef;
+
+    fn to_batch(rows: &[Self]) -> Result<RecordBatch, ArrowError>;
+}
+
+/// A row of `klines`, the fields are named like in `Kline`'s Display
+#[derive(Debug, Clone, Serialize)]
+pub struct KlineRecord {
+    pub pair: String,
+    pub time_frame: String,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub utc_begin: i64,
+    pub buy_base: f64,
+    pub sell_base: f64,
+    pub buy_quote: f64,
+    pub sell_quote: f64,
+}
+
+impl From<&Kline> for KlineRecord {
+    fn from(kline: &Kline) -> Self {
+        KlineRecord {
+            pair: kline.pair.clone(),
+            time_frame: kline.time_frame.clone(),
+            open: kline.o,
+            high: kline.h,
+            low: kline.l,
+            close: kline.c,
+            utc_begin: kline.utc_begin,
+            buy_base: kline.volume_bs.buy_base,
+            sell_base: kline.volume_bs.sell_base,
+            buy_quote: kline.volume_bs.buy_quote,
+            sell_quote: kline.volume_bs.sell_quote,
+        }
+    }
+}
+
+impl ExportRecord for KlineRecord {
+    const COLUMNS: &'static [&'static str] = &[
+        "pair",
+        "time_frame",
+        "open",
+        "high",
+        "low",
+        "close",
+        "utc_begin",
+        "buy_base",
+        "sell_base",
+        "buy_quote",
+        "sell_quote",
+    ];
+
+    fn csv_fields(&self) -> Vec<String> {
+        vec![
+            self.pair.clone(),
+            self.time_frame.clone(),
+            self.open.to_string(),
+            self.high.to_string(),
+            self.low.to_string(),
+            self.close.to_string(),
+            self.utc_begin.to_string(),
+            self.buy_base.to_string(),
+            self.sell_base.to_string(),
+            self.buy_quote.to_string(),
+            self.sell_quote.to_string(),
+        ]
+    }
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("pair", DataType::Utf8, false),
+            Field::new("time_frame", DataType::Utf8, false),
+            Field::new("open", DataType::Float64, false),
+            Field::new("high", DataType::Float64, false),
+            Field::new("low", DataType::Float64, false),
+            Field::new("close", DataType::Float64, false),
+            Field::new("utc_begin", DataType::Int64, false),
+            Field::new("buy_base", DataType::Float64, false),
+            Field::new("sell_base", DataType::Float64, false),
+            Field::new("buy_quote", DataType::Float64, false),
+            Field::new("sell_quote", DataType::Float64, false),
+        ]))
+    }
+
+    fn to_batch(rows: &[Self]) -> Result<RecordBatch, ArrowError> {
+        let strings = |f: fn(&Self) -> &str| -> ArrayRef {
+            Arc::new(rows.iter().map(f).map(Some).collect::<StringArray>())
+        };
+        let floats = |f: fn(&Self) -> f64| -> ArrayRef {
+            Arc::new(rows.iter().map(f).collect::<Float64Array>())
+        };
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                strings(|r| &r.pair),
+                strings(|r| &r.time_frame),
+                floats(|r| r.open),
+                floats(|r| r.high),
+                floats(|r| r.low),
+                floats(|r| r.close),
+                Arc::new(rows.iter().map(|r| r.utc_begin).collect::<Int64Array>()),
+                floats(|r| r.buy_base),
+                floats(|r| r.sell_base),
+                floats(|r| r.buy_quote),
+                floats(|r| r.sell_quote),
+            ],
+        )
+    }
+}
+
+/// A row of `recent_trades`; price and amount stay text, as stored
+#[derive(Debug, Clone, Serialize)]
+pub struct TradeRecord {
+    pub tid: String,
+    pub pair: String,
+    pub price: String,
+    pub amount: String,
+    pub side: String,
+    pub timestamp: i64,
+}
+
+impl From<&RecentTrade> for TradeRecord {
+    fn from(trade: &RecentTrade) -> Self {
+        TradeRecord {
+            tid: trade.tid.clone(),
+            pair: trade.pair.clone(),
+            price: trade.price.clone(),
+            amount: trade.amount.clone(),
+            side: trade.side.clone(),
+            timestamp: trade.timestamp,
+        }
+    }
+}
+
+impl ExportRecord for TradeRecord {
+    const COLUMNS: &'static [&'static str] =
+        &["tid", "pair", "price", "amount", "side", "timestamp"];
+
+    fn csv_fields(&self) -> Vec<String> {
+        vec![
+            self.tid.clone(),
+            self.pair.clone(),
+            self.price.clone(),
+            self.amount.clone(),
+            self.side.clone(),
+            self.timestamp.to_string(),
+        ]
+    }
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("tid", DataType::Utf8, false),
+            Field::new("pair", DataType::Utf8, false),
+            Field::new("price", DataType::Utf8, false),
+            Field::new("amount", DataType::Utf8, false),
+            Field::new("side", DataType::Utf8, false),
+            Field::new("timestamp", DataType::Int64, false),
+        ]))
+    }
+
+    fn to_batch(rows: &[Self]) -> Result<RecordBatch, ArrowError> {
+        let strings = |f: fn(&Self) -> &str| -> ArrayRef {
+            Arc::new(rows.iter().map(f).map(Some).collect::<StringArray>())
+        };
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                strings(|r| &r.tid),
+                strings(|r| &r.pair),
+                strings(|r| &r.price),
+                strings(|r| &r.amount),
+                strings(|r| &r.side),
+                Arc::new(rows.iter().map(|r| r.timestamp).collect::<Int64Array>()),
+            ],
+        )
+    }
+}
@@ -0,0 +1,117 @@
+use std::io::Write;
+use std::marker::PhantomData;
+
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+
+use super::records::ExportRecord;
+use crate::error::Error;
+
+/// Rows per Parquet record batch (and so the most rows held in memory)
+const PARQUET_BATCH_ROWS: usize = 8_192;
+
+/// Writes records of one table in one format
+pub trait RecordWriter<R: ExportRecord> {
+    fn write(&mut self, record: R) -> Result<(), Error>;
+
+    /// Writes whatever is buffered and the format's footer
+    fn finish(self: Box<Self>) -> Result<(), Error>;
+}
+
+pub struct CsvWriter<W: Write, R> {
+    out: csv::Writer<W>,
+    _record: PhantomData<R>,
+}
+
+impl<W: Write, R: ExportRecord> CsvWriter<W, R> {
+    pub fn new(out: W) -> Result<Self, Error> {
+        let mut out = csv::Writer::from_writer(out);
+        out.write_record(R::COLUMNS)?;
+        Ok(CsvWriter {
+            out,
+            _record: PhantomData,
+        })
+    }
+}
+
+impl<W: Write, R: ExportRecord> RecordWriter<R> for CsvWriter<W, R> {
+    fn write(&mut self, record: R) -> Result<(), Error> {
+        self.out.write_record(record.csv_fields())?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), Error> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+pub struct JsonLinesWriter<W: Write, R> {
+    out: W,
+    _record: PhantomData<R>,
+}
+
+impl<W: Write, R: ExportRecord> JsonLinesWriter<W, R> {
+    pub fn new(out: W) -> Self {
+        JsonLinesWriter {
+            out,
+            _record: PhantomData,
+        }
+    }
+}
+
+impl<W: Write, R: ExportRecord> RecordWriter<R> for JsonLinesWriter<W, R> {
+    fn write(&mut self, record: R) -> Result<(), Error> {
+        serde_json::to_writer(&mut self.out, &record)?;
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), Error> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Buffers rows into record batches, so memory is bounded by the batch size, not the table
+pub struct ParquetWriter<W: Write + Send, R> {
+    writer: ArrowWriter<W>,
+    rows: Vec<R>,
+}
+
+impl<W: Write + Send, R: ExportRecord> ParquetWriter<W, R> {
+    pub fn new(out: W) -> Result<Self, Error> {
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY) // readable by pandas/polars out of the box
+            .build();
+        Ok(ParquetWriter {
+            writer: ArrowWriter::try_new(out, R::schema(), Some(properties))?,
+            rows: Vec::with_capacity(PARQUET_BATCH_ROWS),
+        })
+    }
+
+    fn write_batch(&mut self) -> Result<(), Error> {
+        if !self.rows.is_empty() {
+            self.writer.write(&R::to_batch(&self.rows)?)?;
+            self.rows.clear();
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write + Send, R: ExportRecord> RecordWriter<R> for ParquetWriter<W, R> {
+    fn write(&mut self, record: R) -> Result<(), Error> {
+        self.rows.push(record);
+        if self.rows.len() >= PARQUET_BATCH_ROWS {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), Error> {
+        self.write_batch()?;
+        self.writer.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tracing::info;
+
+use crate::error::Error;
+use crate::metrics::unix_millis;
+use crate::parser::{kline::Kline, time_frame_millis, KlineKey};
+
+/// How far behind data may fall before the collector is reported unhealthy
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    pub kline_staleness: Duration, // after the candle following the last one should have started
+    pub trade_staleness: Duration, // since the last trade of a pair
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig {
+            kline_staleness: Duration::from_secs(300),
+            trade_staleness: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Watched {
+    series: Vec<KlineKey>,         // what the collector was configured to collect
+    ready: bool,                   // set once the aggregator has built its handlers
+    saved: HashMap<KlineKey, i64>, // `utc_begin` of the latest kline saved, by series
+    trades: HashMap<String, i64>,  // unix millis of the last trade received, by pair
+    trade_pairs: Vec<String>,      // pairs whose trades are subscribed to
+}
+
+/*
+    Data freshness of a running collector. A kline counts once it is saved (the write buffer
+    reports it), or taken by its sinks when the series isn't stored. Every configured series is
+    expected to get its next candle within `kline_staleness` of that candle's start; a series with no candle at all is stale once the
+    collector has run for `kline_staleness`. Likewise a pair whose trades are expected is stale
+    `trade_staleness` after its last trade, or after the start when it has had none; any other pair
+    is only checked once it has had a trade.
+*/
+#[derive(Clone)]
+pub struct HealthTracker {
+    config: HealthConfig,
+    started_at: i64,
+    watched: Arc<Mutex<Watched>>,
+}
+
+/// One configured series
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeriesHealth {
+    pub pair: String,
+    pub timeframe: String,
+    pub last_utc_begin: Option<i64>,
+    pub behind_secs: i64, // how long a newer candle is overdue, 0 when it isn't
+    pub stale: bool,
+}
+
+/// One pair's trades
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradeHealth {
+    pub pair: String,
+    pub last_received: Option<i64>,
+    pub stale: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub healthy: bool,
+    pub ready: bool,
+    pub series: Vec<SeriesHealth>,
+    pub trades: Vec<TradeHealth>,
+}
+
+impl HealthTracker {
+    pub fn new(config: HealthConfig) -> Self {
+        HealthTracker {
+            config,
+            started_at: unix_millis(),
+            watched: Arc::new(Mutex::new(Watched::default())),
+        }
+    }
+
+    /// Starts tracking the configured series; the collector is ready from now on
+    pub fn watch(&self, series: &[KlineKey]) {
+        let mut watched = self.watched.lock().unwrap();
+        watched.series = series.to_vec();
+        watched.ready = true;
+    }
+
+    /// Klines that have reached their destination, e.g. saved by the write buffer
+    pub fn record_klines(&self, klines: &[Kline]) {
+        let mut watched = self.watched.lock().unwrap();
+        for kline in klines {
+            let latest = watched
+                .saved
+                .entry((kline.pair.clone(), kline.time_frame.clone()))
+                .or_insert(kline.utc_begin);
+            *latest = (*latest).max(kline.utc_begin);
+        }
+    }
+
+    /// The pairs whose trades are subscribed to: from now on each one is expected to get trades
+    pub fn expect_trades(&self, pairs: &[String]) {
+        self.watched.lock().unwrap().trade_pairs = pairs.to_vec();
+    }
+
+    pub fn record_trade(&self, pair: &str) {
+        let now = unix_millis();
+        self.watched
+            .lock()
+            .unwrap()
+            .trades
+            .insert(pair.to_string(), now);
+    }
+
+    pub async fn report(&self) -> HealthReport {
+        let (series, ready, latest, trades, trade_pairs) = {
+            let watched = self.watched.lock().unwrap();
+            (
+                watched.series.clone(),
+                watched.ready,
+                watched.saved.clone(),
+                watched.trades.clone(),
+                watched.trade_pairs.clone(),
+            )
+        };
+        let now = unix_millis();
+        let kline_limit = self.config.kline_staleness.as_millis() as i64;
+        let trade_limit = self.config.trade_staleness.as_millis() as i64;
+
+        let series: Vec<SeriesHealth> = series
+            .into_iter()
+            .map(|(pair, timeframe)| {
+                let last_utc_begin = latest.get(&(pair.clone(), timeframe.clone())).copied();
+                // When the next candle was due: the end of the last one, or the start of the collector
+                let due = match last_utc_begin {
+                    Some(utc_begin) => utc_begin + time_frame_millis(&timeframe).unwrap_or(0),
+                    None => self.started_at,
+                };
+                let behind = (now - due).max(0);
+                SeriesHealth {
+                    pair,
+                    timeframe,
+                    last_utc_begin,
+                    behind_secs: behind / 1_000,
+                    stale: behind > kline_limit,
+                }
+            })
+            .collect();
+        let mut pairs: Vec<String> = trades.keys().cloned().collect();
+        pairs.extend(
+            trade_pairs
+                .into_iter()
+                .filter(|pair| !trades.contains_key(pair)),
+        );
+        pairs.sort();
+        let trades: Vec<TradeHealth> = pairs
+            .into_iter()
+            .map(|pair| {
+                let last_received = trades.get(&pair).copied();
+                // Trades are due since the last one, or since the start of the collector
+                let since = last_received.unwrap_or(self.started_at);
+                TradeHealth {
+                    pair,
+                    last_received,
+                    stale: now - since > trade_limit,
+                }
+            })
+            .collect();
+
+        HealthReport {
+            healthy: ready && series.iter().all(|s| !s.stale) && trades.iter().all(|t| !t.stale),
+            ready,
+            series,
+            trades,
+        }
+    }
+}
+
+async fn health(State(tracker): State<HealthTracker>) -> (StatusCode, Json<HealthReport>) {
+    let report = tracker.report().await;
+    let status = if report.healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
+async fn ready(State(tracker): State<HealthTracker>) -> StatusCode {
+    if tracker.report().await.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+/// `/health` (200 or 503 with the report) and `/ready` (200 once the collector is running)
+pub fn router(tracker: HealthTracker) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/ready", get(ready))
+        .with_state(tracker)
+}
+
+/// Serves the health checks on an already bound listener until the task is dropped
+pub async fn serve(listener: TcpListener, tracker: HealthTracker) -> Result<(), Error> {
+    info!("Health checks listening on {}", listener.local_addr()?);
+    axum::serve(listener, router(tracker)).await?;
+    Ok(())
+}
+
+/// Binds the address and serves the health checks
+pub async fn bind_and_serve(addr: SocketAddr, tracker: HealthTracker) -> Result<(), Error> {
+    serve(TcpListener::bind(addr).await?, tracker).await
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kline(time_frame: &str, utc_begin: i64) -> Kline {
+        Kline::sample("BTC_USDT", time_frame, utc_begin)
+    }
+
+    #[tokio::test]
+    async fn test_report_staleness() {
+        let tracker = HealthTracker::new(HealthConfig::default());
+        assert!(!tracker.report().await.ready);
+
+        let now = unix_millis();
+        let minute = ("BTC_USDT".to_string(), "MINUTE_1".to_string());
+        let hour = ("BTC_USDT".to_string(), "HOUR_1".to_string());
+        let day = ("BTC_USDT".to_string(), "DAY_1".to_string());
+        tracker.record_klines(&[
+            kline("MINUTE_1", now - 60_000),
+            kline("MINUTE_1", now - 30_000), // the current candle
+            kline("HOUR_1", now - 2 * 3_600_000), // an hour overdue
+        ]);
+        // No DAY_1 candle yet, but the collector has only just started
+        tracker.watch(&[minute, hour, day]);
+
+        let report = tracker.report().await;
+        assert!(report.ready);
+        let stale: Vec<(&str, bool)> = report
+            .series
+            .iter()
+            .map(|s| (s.timeframe.as_str(), s.stale))
+            .collect();
+        assert_eq!(
+            stale,
+            vec![("MINUTE_1", false), ("HOUR_1", true), ("DAY_1", false)]
+        );
+        assert_eq!(report.series[1].behind_secs / 60, 60);
+        assert!(!report.healthy);
+
+        tracker.record_trade("BTC_USDT");
+        assert!(!tracker.report().await.trades[0].stale);
+    }
+
+    #[tokio::test]
+    async fn test_expected_trades() {
+        let mut tracker = HealthTracker::new(HealthConfig::default());
+        tracker.watch(&[]);
+        tracker.expect_trades(&["BTC_USDT".to_string(), "ETH_USDT".to_string()]);
+        assert!(tracker.report().await.healthy);
+
+        // Two minutes later, with no trade of ETH_USDT since the start
+        tracker.started_at -= 120_000;
+        tracker.record_trade("BTC_USDT");
+        let report = tracker.report().await;
+        let stale: Vec<(&str, Option<i64>, bool)> = report
+            .trades
+            .iter()
+            .map(|t| (t.pair.as_str(), t.last_received.map(|_| 0), t.stale))
+            .collect();
+        assert_eq!(
+            stale,
+            vec![("BTC_USDT", Some(0), false), ("ETH_USDT", None, true)]
+        );
+        assert!(!report.healthy);
+    }
+}
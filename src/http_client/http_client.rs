@@ -1,12 +1,14 @@
 use super::HttpClientError;
+use crate::metrics::metrics;
 use reqwest::{self, Client};
 use std::{error::Error, future::Future, pin::Pin};
 
+/// The response body or the reason it could not be obtained
+pub type RestResponse<'a> =
+    Pin<Box<dyn Future<Output = Result<String, Box<dyn Error>>> + Send + 'a>>;
+
 pub trait RestClient: Send + Sync {
-    fn get<'a>(
-        &'a self,
-        url: &'a str,
-    ) -> Pin<Box<dyn Future<Output = Result<String, Box<dyn Error>>> + Send + 'a>>;
+    fn get<'a>(&'a self, url: &'a str) -> RestResponse<'a>;
 }
 
 pub struct ReqwestClient {
@@ -21,18 +23,26 @@ impl ReqwestClient {
     }
 }
 
+impl Default for ReqwestClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RestClient for ReqwestClient {
-    fn get<'a>(
-        &'a self,
-        url: &'a str,
-    ) -> Pin<Box<dyn Future<Output = Result<String, Box<dyn Error>>> + Send + 'a>> {
+    fn get<'a>(&'a self, url: &'a str) -> RestResponse<'a> {
         Box::pin(async move {
             let response = self.client.get(url).send().await.map_err(|err| {
+                metrics().rest_requests.with_label_values(&["error"]).inc();
                 Box::new(HttpClientError::new(&format!(
                     "Failed to send request: {}",
                     err
                 ))) as Box<dyn Error>
             })?;
+            metrics()
+                .rest_requests
+                .with_label_values(&[response.status().as_str()])
+                .inc();
             let text = response.text().await.map_err(|err| {
                 Box::new(HttpClientError::new(&format!(
                     "Failed to read response text: {}",
@@ -1,3 +1,4 @@
 pub mod error;
+#[allow(clippy::module_inception)]
 pub mod http_client;
 pub use error::HttpClientError;
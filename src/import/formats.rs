@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use super::{ImportOptions, ReadKlines};
+use crate::error::Error;
+use crate::parser::kline::{Kline, VBS};
+
+/// Quote assets recognised when a Binance symbol (BTCUSDT) is turned into a pair (BTC_USDT)
+const BINANCE_QUOTES: &[&str] = &[
+    "FDUSD", "USDT", "USDC", "TUSD", "BUSD", "DAI", "BTC", "ETH", "BNB", "EUR", "TRY",
+];
+
+/// Pair and interval taken from a bulk download file name, e.g. `BTCUSDT-1m-2025-01.csv`
+#[derive(Debug, Default)]
+pub(crate) struct SourceName {
+    pub symbol: Option<String>,
+    pub interval: Option<String>,
+}
+
+impl SourceName {
+    pub fn parse(name: &str) -> Self {
+        let file = name.rsplit(['/', '\\']).next().unwrap_or(name);
+        let stem = file.split('.').next().unwrap_or(file);
+        let mut parts = stem.split('-');
+        SourceName {
+            symbol: parts.next().filter(|s| !s.is_empty()).map(str::to_string),
+            interval: parts.next().map(str::to_string),
+        }
+    }
+}
+
+/// BTCUSDT -> BTC_USDT
+fn binance_pair(symbol: &str) -> Option<String> {
+    let symbol = symbol.to_uppercase();
+    BINANCE_QUOTES.iter().find_map(|quote| {
+        symbol
+            .strip_suffix(quote)
+            .filter(|base| !base.is_empty())
+            .map(|base| format!("{}_{}", base, quote))
+    })
+}
+
+/// Binance interval -> the time frame names used by Poloniex (and so by the stored klines)
+fn binance_time_frame(interval: &str) -> Option<&'static str> {
+    Some(match interval {
+        "1m" => "MINUTE_1",
+        "5m" => "MINUTE_5",
+        "10m" => "MINUTE_10",
+        "15m" => "MINUTE_15",
+        "30m" => "MINUTE_30",
+        "1h" => "HOUR_1",
+        "2h" => "HOUR_2",
+        "4h" => "HOUR_4",
+        "6h" => "HOUR_6",
+        "12h" => "HOUR_12",
+        "1d" => "DAY_1",
+        "3d" => "DAY_3",
+        "1w" => "WEEK_1",
+        "1M" => "MONTH_1",
+        _ => return None,
+    })
+}
+
+/*
+    data.binance.vision klines: open_time, open, high, low, close, volume, close_time, quote_volume,
+    trades, taker_buy_base, taker_buy_quote, ignore. Some files have a header row, spot files
+    since 2025 have microsecond timestamps. Volumes map onto VBS like the Poloniex fields do:
+    taker buy volumes -> buy_*, total volumes -> sell_*.
+*/
+pub(crate) fn read_binance<R: Read>(
+    reader: R,
+    source: &SourceName,
+    options: &ImportOptions,
+) -> Result<ReadKlines, Error> {
+    let pair = match &options.pair {
+        Some(pair) => pair.clone(),
+        None => source
+            .symbol
+            .as_deref()
+            .and_then(binance_pair)
+            .ok_or_else(|| {
+                Error::Import("can't tell the pair from the file name, use --pair".to_string())
+            })?,
+    };
+    let time_frame = match &options.time_frame {
+        Some(time_frame) => time_frame.clone(),
+        None => source
+            .interval
+            .as_deref()
+            .and_then(binance_time_frame)
+            .map(str::to_string)
+            .ok_or_else(|| {
+                Error::Import(
+                    "can't tell the interval from the file name, use --timeframe".to_string(),
+                )
+            })?,
+    };
+
+    let mut csv = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(reader);
+    let mut read = ReadKlines {
+        klines: Vec::new(),
+        unreadable: 0,
+    };
+    for (index, record) in csv.records().enumerate() {
+        let record = record?;
+        let number = |i: usize| record.get(i).and_then(|v| v.trim().parse::<f64>().ok());
+        let Some(open_time) = record.get(0).and_then(|v| v.trim().parse::<i64>().ok()) else {
+            if index > 0 {
+                read.unreadable += 1; // the first line may be a header
+            }
+            continue;
+        };
+        let fields = (
+            number(1),
+            number(2),
+            number(3),
+            number(4),
+            number(5),
+            number(7),
+            number(9),
+            number(10),
+        );
+        let (
+            Some(o),
+            Some(h),
+            Some(l),
+            Some(c),
+            Some(volume),
+            Some(quote_volume),
+            Some(buy_base),
+            Some(buy_quote),
+        ) = fields
+        else {
+            read.unreadable += 1;
+            continue;
+        };
+        read.klines.push(Kline {
+            pair: pair.clone(),
+            time_frame: time_frame.clone(),
+            o,
+            h,
+            l,
+            c,
+            utc_begin: if open_time >= 1_000_000_000_000_000 {
+                open_time / 1_000 // microseconds
+            } else {
+                open_time
+            },
+            volume_bs: VBS {
+                buy_base,
+                sell_base: volume,
+                buy_quote,
+                sell_quote: quote_volume,
+            },
+        });
+    }
+    Ok(read)
+}
+
+/*
+    Poloniex candles as CSV with a header row named like the REST response fields:
+    low, high, open, close, amount, quantity, buyTakerAmount, buyTakerQuantity, ..., interval, startTime.
+    `symbol` and `interval` columns are optional when --pair / --timeframe are given.
+*/
+pub(crate) fn read_poloniex<R: Read>(
+    reader: R,
+    source: &SourceName,
+    options: &ImportOptions,
+) -> Result<ReadKlines, Error> {
+    let mut csv = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+    let columns: HashMap<String, usize> = csv
+        .headers()?
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (name.trim().to_lowercase(), i))
+        .collect();
+    let column = |name: &str| -> Result<usize, Error> {
+        columns
+            .get(&name.to_lowercase())
+            .copied()
+            .ok_or_else(|| Error::Import(format!("column '{}' is missing", name)))
+    };
+    let low = column("low")?;
+    let high = column("high")?;
+    let open = column("open")?;
+    let close = column("close")?;
+    let amount = column("amount")?;
+    let quantity = column("quantity")?;
+    let buy_taker_amount = column("buyTakerAmount")?;
+    let buy_taker_quantity = column("buyTakerQuantity")?;
+    let start_time = column("startTime")?;
+    let symbol = column("symbol").ok();
+    let interval = column("interval").ok();
+    if options.pair.is_none() && symbol.is_none() && source.symbol.is_none() {
+        return Err(Error::Import("no symbol column, use --pair".to_string()));
+    }
+    if options.time_frame.is_none() && interval.is_none() {
+        return Err(Error::Import(
+            "no interval column, use --timeframe".to_string(),
+        ));
+    }
+
+    let mut read = ReadKlines {
+        klines: Vec::new(),
+        unreadable: 0,
+    };
+    for record in csv.records() {
+        let record = record?;
+        let text = |i: usize| record.get(i).map(str::trim).unwrap_or_default();
+        let number = |i: usize| text(i).parse::<f64>().ok();
+        let pair = options
+            .pair
+            .clone()
+            .or_else(|| symbol.map(|i| text(i).to_string()))
+            .or_else(|| source.symbol.clone())
+            .unwrap_or_default();
+        let time_frame = options
+            .time_frame
+            .clone()
+            .or_else(|| interval.map(|i| text(i).to_string()))
+            .unwrap_or_default();
+        let fields = (
+            number(open),
+            number(high),
+            number(low),
+            number(close),
+            number(amount),
+            number(quantity),
+            number(buy_taker_amount),
+            number(buy_taker_quantity),
+            text(start_time).parse::<i64>().ok(),
+        );
+        let (
+            Some(o),
+            Some(h),
+            Some(l),
+            Some(c),
+            Some(amount),
+            Some(quantity),
+            Some(buy_amount),
+            Some(buy_quantity),
+            Some(utc_begin),
+        ) = fields
+        else {
+            read.unreadable += 1;
+            continue;
+        };
+        read.klines.push(Kline {
+            pair,
+            time_frame,
+            o,
+            h,
+            l,
+            c,
+            utc_begin,
+            // The same mapping as VBS::from_data
+            volume_bs: VBS {
+                buy_base: buy_quantity,
+                sell_base: quantity,
+                buy_quote: buy_amount,
+                sell_quote: amount,
+            },
+        });
+    }
+    Ok(read)
+}
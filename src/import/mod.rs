@@ -0,0 +1,235 @@
+pub mod formats;
+
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+use tracing::{info, warn};
+
+use crate::database::KlineStore;
+use crate::error::Error;
+use crate::parser::kline::Kline;
+use formats::{read_binance, read_poloniex, SourceName};
+
+/// Klines per upsert transaction
+const IMPORT_BATCH: usize = 5_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// CSV with a header row named like the fields of the REST candles response
+    Poloniex,
+    /// data.binance.vision kline files: headerless CSV, optionally zipped
+    Binance,
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "poloniex" => Ok(ImportFormat::Poloniex),
+            "binance" => Ok(ImportFormat::Binance),
+            other => Err(format!(
+                "unknown format '{}', expected poloniex or binance",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ImportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportFormat::Poloniex => write!(f, "poloniex"),
+            ImportFormat::Binance => write!(f, "binance"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    pub format: ImportFormat,
+    pub pair: Option<String>, // overrides the pair found in the file or its name
+    pub time_frame: Option<String>, // overrides the time frame found in the file or its name
+}
+
+/// What happened to the rows of the imported files
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ImportReport {
+    pub rows: u64,
+    pub imported: u64,
+    pub rejected: u64,
+}
+
+/// The klines read from one CSV, before validation
+pub(crate) struct ReadKlines {
+    pub klines: Vec<Kline>,
+    pub unreadable: u64,
+}
+
+/*
+    Imports a CSV file, or every CSV inside a ZIP archive, into the store.
+    Rows are validated with the same rules as the REST parser (`Kline::validate`);
+    accepted klines replace stored ones of the same (pair, time_frame, utc_begin).
+*/
+pub async fn import_file(
+    store: &dyn KlineStore,
+    path: &Path,
+    options: &ImportOptions,
+) -> Result<ImportReport, Error> {
+    let mut report = ImportReport::default();
+    let is_zip = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("zip"));
+
+    if is_zip {
+        // Each entry is read in full before writing, so nothing borrowed from the archive lives across an await
+        let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+        for index in 0..archive.len() {
+            let (name, read) = {
+                let entry = archive.by_index(index)?;
+                let name = entry.name().to_string();
+                if !name.to_lowercase().ends_with(".csv") {
+                    continue;
+                }
+                let read = read_csv(entry, &name, options)?;
+                (name, read)
+            };
+            store_klines(store, &name, read, &mut report).await?;
+        }
+    } else {
+        let name = path.to_string_lossy().to_string();
+        let read = read_csv(File::open(path)?, &name, options)?;
+        store_klines(store, &name, read, &mut report).await?;
+    }
+
+    info!(
+        "Imported {}: {} rows, {} imported, {} rejected",
+        path.display(),
+        report.rows,
+        report.imported,
+        report.rejected
+    );
+    Ok(report)
+}
+
+fn read_csv<R: Read>(reader: R, name: &str, options: &ImportOptions) -> Result<ReadKlines, Error> {
+    let source = SourceName::parse(name);
+    match options.format {
+        ImportFormat::Poloniex => read_poloniex(reader, &source, options),
+        ImportFormat::Binance => read_binance(reader, &source, options),
+    }
+}
+
+async fn store_klines(
+    store: &dyn KlineStore,
+    name: &str,
+    read: ReadKlines,
+    report: &mut ImportReport,
+) -> Result<(), Error> {
+    report.rows += read.klines.len() as u64 + read.unreadable;
+    report.rejected += read.unreadable;
+
+    let mut valid = Vec::with_capacity(read.klines.len());
+    for kline in read.klines {
+        match kline.validate() {
+            Ok(()) => valid.push(kline),
+            Err(reason) => {
+                warn!("{}: rejected kline: {}", name, reason);
+                report.rejected += 1;
+            }
+        }
+    }
+    for batch in valid.chunks(IMPORT_BATCH) {
+        store.upsert_klines(batch).await?;
+        report.imported += batch.len() as u64;
+    }
+    Ok(())
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{KlineFilter, MemoryStore};
+    use std::io::Write;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("{}_{}", uuid::Uuid::new_v4(), name))
+    }
+
+    #[tokio::test]
+    async fn test_import_poloniex_csv() {
+        let path = temp_path("poloniex.csv");
+        std::fs::write(
+            &path,
+            "low,high,open,close,amount,quantity,buyTakerAmount,buyTakerQuantity,tradeCount,ts,weightedAverage,interval,startTime,closeTime\n\
+             29900,30100,30000,30050,9000,0.3,15000,0.5,10,1737709991000,30010,MINUTE_1,1737709920000,1737709979999\n\
+             30100,29900,30000,30050,9000,0.3,15000,0.5,10,1737709991000,30010,MINUTE_1,1737709980000,1737710039999\n",
+        )
+        .unwrap();
+        let store = MemoryStore::new();
+        let options = ImportOptions {
+            format: ImportFormat::Poloniex,
+            pair: Some("BTC_USDT".to_string()),
+            time_frame: None,
+        };
+
+        let report = import_file(&store, &path, &options).await.unwrap();
+        assert_eq!(
+            report,
+            ImportReport {
+                rows: 2,
+                imported: 1,
+                rejected: 1 // low above high
+            }
+        );
+        // A second import replaces instead of duplicating
+        import_file(&store, &path, &options).await.unwrap();
+        let klines = store.load_klines(&KlineFilter::default()).await.unwrap();
+        assert_eq!(klines.len(), 1);
+        assert_eq!(klines[0].time_frame, "MINUTE_1");
+        assert_eq!(klines[0].volume_bs.buy_base, 0.5);
+        assert_eq!(klines[0].volume_bs.sell_quote, 9000.0);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_import_binance_zip() {
+        let path = temp_path("BTCUSDT-1m-2025-01.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        zip.start_file(
+            "BTCUSDT-1m-2025-01.csv",
+            zip::write::SimpleFileOptions::default(),
+        )
+        .unwrap();
+        // Microsecond timestamps, as in the spot files since 2025
+        zip.write_all(
+            b"1735689600000000,93576.00,93610.93,93537.50,93610.93,8.21827,1735689659999999,768978.77,1812,3.95922,370474.22,0\n\
+              1735689660000000,93610.93,93652.00,93606.15,93634.00,5.22139,1735689719999999,488898.66,1428,2.81500,263593.15,0\n",
+        )
+        .unwrap();
+        zip.finish().unwrap();
+
+        let store = MemoryStore::new();
+        let options = ImportOptions {
+            format: ImportFormat::Binance,
+            pair: None,
+            time_frame: None,
+        };
+        let report = import_file(&store, &path, &options).await.unwrap();
+        assert_eq!(report.imported, 2);
+
+        let klines = store.load_klines(&KlineFilter::default()).await.unwrap();
+        assert_eq!(klines[0].pair, "BTC_USDT");
+        assert_eq!(klines[0].time_frame, "MINUTE_1");
+        assert_eq!(klines[0].utc_begin, 1735689600000);
+        assert_eq!(klines[0].volume_bs.sell_base, 8.21827);
+        assert_eq!(klines[0].volume_bs.buy_quote, 370474.22);
+        let _ = std::fs::remove_file(&path);
+    }
+}
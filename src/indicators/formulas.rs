@@ -0,0 +1,308 @@
+use std::collections::VecDeque;
+
+use super::Indicator;
+use crate::parser::kline::Kline;
+
+const DAY_MILLIS: i64 = 86_400_000;
+
+/// Simple moving average of the last `period` values
+#[derive(Debug, Clone)]
+pub struct Sma {
+    period: usize,
+    window: VecDeque<f64>,
+    sum: f64,
+}
+
+impl Sma {
+    pub fn new(period: usize) -> Self {
+        Sma {
+            period,
+            window: VecDeque::with_capacity(period + 1),
+            sum: 0.0,
+        }
+    }
+
+    pub fn push(&mut self, value: f64) -> Option<f64> {
+        self.window.push_back(value);
+        self.sum += value;
+        if self.window.len() > self.period {
+            self.sum -= self.window.pop_front().unwrap_or_default();
+        }
+        (self.window.len() == self.period).then(|| self.sum / self.period as f64)
+    }
+}
+
+impl Indicator for Sma {
+    fn next(&mut self, kline: &Kline) -> Vec<(&'static str, f64)> {
+        self.push(kline.c).map(|v| ("", v)).into_iter().collect()
+    }
+
+    fn box_clone(&self) -> Box<dyn Indicator> {
+        Box::new(self.clone())
+    }
+}
+
+/// Exponential moving average, seeded with the SMA of the first `period` values
+#[derive(Debug, Clone)]
+pub struct Ema {
+    period: usize,
+    seen: usize,
+    seed: f64,
+    value: Option<f64>,
+}
+
+impl Ema {
+    pub fn new(period: usize) -> Self {
+        Ema {
+            period,
+            seen: 0,
+            seed: 0.0,
+            value: None,
+        }
+    }
+
+    pub fn push(&mut self, x: f64) -> Option<f64> {
+        self.value = match self.value {
+            Some(prev) => {
+                let alpha = 2.0 / (self.period as f64 + 1.0);
+                Some(alpha * x + (1.0 - alpha) * prev)
+            }
+            None => {
+                self.seen += 1;
+                self.seed += x;
+                (self.seen == self.period).then(|| self.seed / self.period as f64)
+            }
+        };
+        self.value
+    }
+}
+
+impl Indicator for Ema {
+    fn next(&mut self, kline: &Kline) -> Vec<(&'static str, f64)> {
+        self.push(kline.c).map(|v| ("", v)).into_iter().collect()
+    }
+
+    fn box_clone(&self) -> Box<dyn Indicator> {
+        Box::new(self.clone())
+    }
+}
+
+/// Wilder's smoothing: the mean of the first `period` values, then `(prev * (period - 1) + x) / period`
+#[derive(Debug, Clone)]
+struct Wilder {
+    period: usize,
+    seen: usize,
+    value: f64,
+}
+
+impl Wilder {
+    fn new(period: usize) -> Self {
+        Wilder {
+            period,
+            seen: 0,
+            value: 0.0,
+        }
+    }
+
+    fn push(&mut self, x: f64) -> Option<f64> {
+        let n = self.period as f64;
+        if self.seen < self.period {
+            self.seen += 1;
+            self.value += x / n;
+        } else {
+            self.value = (self.value * (n - 1.0) + x) / n;
+        }
+        (self.seen == self.period).then_some(self.value)
+    }
+}
+
+/// Relative strength index over `period` closes, with Wilder's smoothing
+#[derive(Debug, Clone)]
+pub struct Rsi {
+    prev_close: Option<f64>,
+    gains: Wilder,
+    losses: Wilder,
+}
+
+impl Rsi {
+    pub fn new(period: usize) -> Self {
+        Rsi {
+            prev_close: None,
+            gains: Wilder::new(period),
+            losses: Wilder::new(period),
+        }
+    }
+}
+
+impl Indicator for Rsi {
+    fn next(&mut self, kline: &Kline) -> Vec<(&'static str, f64)> {
+        let Some(prev) = self.prev_close.replace(kline.c) else {
+            return Vec::new();
+        };
+        let change = kline.c - prev;
+        let gain = self.gains.push(change.max(0.0));
+        let loss = self.losses.push((-change).max(0.0));
+        match (gain, loss) {
+            (Some(_), Some(0.0)) => vec![("", 100.0)],
+            (Some(gain), Some(loss)) => vec![("", 100.0 - 100.0 / (1.0 + gain / loss))],
+            _ => Vec::new(),
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Indicator> {
+        Box::new(self.clone())
+    }
+}
+
+/// MACD line (fast EMA - slow EMA), its signal EMA and the histogram between them
+#[derive(Debug, Clone)]
+pub struct Macd {
+    fast: Ema,
+    slow: Ema,
+    signal: Ema,
+}
+
+impl Macd {
+    pub fn new(fast: usize, slow: usize, signal: usize) -> Self {
+        Macd {
+            fast: Ema::new(fast),
+            slow: Ema::new(slow),
+            signal: Ema::new(signal),
+        }
+    }
+}
+
+impl Indicator for Macd {
+    fn next(&mut self, kline: &Kline) -> Vec<(&'static str, f64)> {
+        let (Some(fast), Some(slow)) = (self.fast.push(kline.c), self.slow.push(kline.c)) else {
+            return Vec::new();
+        };
+        let macd = fast - slow;
+        match self.signal.push(macd) {
+            Some(signal) => vec![("", macd), ("signal", signal), ("hist", macd - signal)],
+            None => vec![("", macd)],
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Indicator> {
+        Box::new(self.clone())
+    }
+}
+
+/// Bollinger Bands: SMA of `period` closes and `width` population standard deviations around it
+#[derive(Debug, Clone)]
+pub struct Bollinger {
+    period: usize,
+    width: f64,
+    window: VecDeque<f64>,
+}
+
+impl Bollinger {
+    pub fn new(period: usize, width: f64) -> Self {
+        Bollinger {
+            period,
+            width,
+            window: VecDeque::with_capacity(period + 1),
+        }
+    }
+}
+
+impl Indicator for Bollinger {
+    fn next(&mut self, kline: &Kline) -> Vec<(&'static str, f64)> {
+        self.window.push_back(kline.c);
+        if self.window.len() > self.period {
+            self.window.pop_front();
+        }
+        if self.window.len() < self.period {
+            return Vec::new();
+        }
+        let n = self.period as f64;
+        let mean = self.window.iter().sum::<f64>() / n;
+        let variance = self.window.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+        let band = self.width * variance.sqrt();
+        vec![
+            ("middle", mean),
+            ("upper", mean + band),
+            ("lower", mean - band),
+        ]
+    }
+
+    fn box_clone(&self) -> Box<dyn Indicator> {
+        Box::new(self.clone())
+    }
+}
+
+/// Average true range over `period` klines, with Wilder's smoothing
+#[derive(Debug, Clone)]
+pub struct Atr {
+    prev_close: Option<f64>,
+    average: Wilder,
+}
+
+impl Atr {
+    pub fn new(period: usize) -> Self {
+        Atr {
+            prev_close: None,
+            average: Wilder::new(period),
+        }
+    }
+}
+
+impl Indicator for Atr {
+    fn next(&mut self, kline: &Kline) -> Vec<(&'static str, f64)> {
+        let range = kline.h - kline.l;
+        let true_range = match self.prev_close.replace(kline.c) {
+            Some(prev) => range
+                .max((kline.h - prev).abs())
+                .max((kline.l - prev).abs()),
+            None => range,
+        };
+        self.average
+            .push(true_range)
+            .map(|v| ("", v))
+            .into_iter()
+            .collect()
+    }
+
+    fn box_clone(&self) -> Box<dyn Indicator> {
+        Box::new(self.clone())
+    }
+}
+
+/// Volume-weighted average of the typical price (h + l + c) / 3, restarting every UTC day
+#[derive(Debug, Clone, Default)]
+pub struct Vwap {
+    day: Option<i64>,
+    weighted: f64,
+    volume: f64,
+}
+
+impl Vwap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Indicator for Vwap {
+    fn next(&mut self, kline: &Kline) -> Vec<(&'static str, f64)> {
+        let day = kline.utc_begin.div_euclid(DAY_MILLIS);
+        if self.day != Some(day) {
+            *self = Vwap {
+                day: Some(day),
+                ..Vwap::default()
+            };
+        }
+        let volume = kline.volume_bs.sell_base; // the total base volume
+        self.weighted += (kline.h + kline.l + kline.c) / 3.0 * volume;
+        self.volume += volume;
+        if self.volume > 0.0 {
+            vec![("", self.weighted / self.volume)]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Indicator> {
+        Box::new(self.clone())
+    }
+}
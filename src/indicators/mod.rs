@@ -0,0 +1,348 @@
+pub mod formulas;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::database::IndicatorStore;
+use crate::parser::{kline::Kline, KlineKey};
+use formulas::{Atr, Bollinger, Ema, Macd, Rsi, Sma, Vwap};
+
+/// A calculation fed one kline of a series at a time, in order
+pub trait Indicator: Send + Sync {
+    /// The outputs once there are enough klines: (output, value), the main output being ""
+    fn next(&mut self, kline: &Kline) -> Vec<(&'static str, f64)>;
+
+    fn box_clone(&self) -> Box<dyn Indicator>;
+}
+
+/*
+    An indicator and its parameters, written as in INDICATORS:
+        sma:20  ema:50  rsi:14  macd:12,26,9  bb:20,2  atr:14  vwap
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndicatorSpec {
+    Sma(usize),
+    Ema(usize),
+    Rsi(usize),
+    Macd {
+        fast: usize,
+        slow: usize,
+        signal: usize,
+    },
+    Bollinger {
+        period: usize,
+        width: f64,
+    },
+    Atr(usize),
+    Vwap, // restarts every UTC day
+}
+
+impl IndicatorSpec {
+    /// The value of the `indicator` column for the main output
+    pub fn name(&self) -> &'static str {
+        match self {
+            IndicatorSpec::Sma(_) => "sma",
+            IndicatorSpec::Ema(_) => "ema",
+            IndicatorSpec::Rsi(_) => "rsi",
+            IndicatorSpec::Macd { .. } => "macd",
+            IndicatorSpec::Bollinger { .. } => "bb",
+            IndicatorSpec::Atr(_) => "atr",
+            IndicatorSpec::Vwap => "vwap",
+        }
+    }
+
+    /// The value of the `params` column, e.g. `12,26,9`
+    pub fn params(&self) -> String {
+        match self {
+            IndicatorSpec::Sma(n)
+            | IndicatorSpec::Ema(n)
+            | IndicatorSpec::Rsi(n)
+            | IndicatorSpec::Atr(n) => n.to_string(),
+            IndicatorSpec::Macd { fast, slow, signal } => format!("{},{},{}", fast, slow, signal),
+            IndicatorSpec::Bollinger { period, width } => format!("{},{}", period, width),
+            IndicatorSpec::Vwap => String::new(),
+        }
+    }
+
+    /// A calculation in its initial state
+    pub fn build(&self) -> Box<dyn Indicator> {
+        match *self {
+            IndicatorSpec::Sma(n) => Box::new(Sma::new(n)),
+            IndicatorSpec::Ema(n) => Box::new(Ema::new(n)),
+            IndicatorSpec::Rsi(n) => Box::new(Rsi::new(n)),
+            IndicatorSpec::Macd { fast, slow, signal } => Box::new(Macd::new(fast, slow, signal)),
+            IndicatorSpec::Bollinger { period, width } => Box::new(Bollinger::new(period, width)),
+            IndicatorSpec::Atr(n) => Box::new(Atr::new(n)),
+            IndicatorSpec::Vwap => Box::new(Vwap::new()),
+        }
+    }
+}
+
+impl FromStr for IndicatorSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, params) = s.split_once(':').unwrap_or((s, ""));
+        let params: Vec<&str> = params
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .collect();
+        let period = |i: usize| -> Result<usize, String> {
+            params
+                .get(i)
+                .and_then(|p| p.parse().ok())
+                .filter(|n| *n > 0)
+                .ok_or_else(|| format!("'{}' needs a positive period as parameter {}", s, i + 1))
+        };
+        let spec = match name.to_lowercase().as_str() {
+            "sma" => IndicatorSpec::Sma(period(0)?),
+            "ema" => IndicatorSpec::Ema(period(0)?),
+            "rsi" => IndicatorSpec::Rsi(period(0)?),
+            "atr" => IndicatorSpec::Atr(period(0)?),
+            "macd" => IndicatorSpec::Macd {
+                fast: period(0)?,
+                slow: period(1)?,
+                signal: period(2)?,
+            },
+            "bb" => IndicatorSpec::Bollinger {
+                period: period(0)?,
+                width: params
+                    .get(1)
+                    .and_then(|p| p.parse().ok())
+                    .filter(|w: &f64| w.is_finite() && *w > 0.0)
+                    .ok_or_else(|| format!("'{}' needs a positive band width", s))?,
+            },
+            "vwap" => IndicatorSpec::Vwap,
+            other => return Err(format!("unknown indicator '{}'", other)),
+        };
+        Ok(spec)
+    }
+}
+
+impl fmt::Display for IndicatorSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndicatorSpec::Vwap => write!(f, "{}", self.name()),
+            _ => write!(f, "{}:{}", self.name(), self.params()),
+        }
+    }
+}
+
+/// Parses a `;`-separated list such as `sma:20;macd:12,26,9`
+pub fn parse_specs(list: &str) -> Result<Vec<IndicatorSpec>, String> {
+    list.split(';')
+        .filter(|s| !s.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// One computed value, a row of the `indicators` table
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IndicatorPoint {
+    pub pair: String,
+    pub time_frame: String,
+    pub utc_begin: i64,
+    pub indicator: String, // spec name, with the output for the secondary ones: `macd.signal`, `bb.upper`
+    pub params: String,
+    pub value: f64,
+}
+
+/*
+    The indicators of one series. The exchange keeps changing the last candle until it closes,
+    so the state before the newest kline is kept aside: a kline with the same start replaces
+    the newest one instead of being counted again, and klines older than it are ignored.
+*/
+struct SeriesState {
+    newest: Option<i64>,
+    before_newest: Vec<Box<dyn Indicator>>,
+    current: Vec<Box<dyn Indicator>>,
+}
+
+impl SeriesState {
+    fn new(specs: &[IndicatorSpec]) -> Self {
+        SeriesState {
+            newest: None,
+            before_newest: specs.iter().map(IndicatorSpec::build).collect(),
+            current: specs.iter().map(IndicatorSpec::build).collect(),
+        }
+    }
+
+    fn update(&mut self, specs: &[IndicatorSpec], kline: &Kline) -> Vec<IndicatorPoint> {
+        match self.newest {
+            Some(newest) if kline.utc_begin < newest => return Vec::new(),
+            Some(newest) if kline.utc_begin == newest => {
+                self.current = self.before_newest.iter().map(|i| i.box_clone()).collect();
+            }
+            _ => {
+                self.before_newest = self.current.iter().map(|i| i.box_clone()).collect();
+            }
+        }
+        self.newest = Some(kline.utc_begin);
+
+        let mut points = Vec::new();
+        for (spec, indicator) in specs.iter().zip(self.current.iter_mut()) {
+            for (output, value) in indicator.next(kline) {
+                points.push(IndicatorPoint {
+                    pair: kline.pair.clone(),
+                    time_frame: kline.time_frame.clone(),
+                    utc_begin: kline.utc_begin,
+                    indicator: if output.is_empty() {
+                        spec.name().to_string()
+                    } else {
+                        format!("{}.{}", spec.name(), output)
+                    },
+                    params: spec.params(),
+                    value,
+                });
+            }
+        }
+        points
+    }
+}
+
+/// Computes the indicators over klines of any series, e.g. a query result, from scratch
+pub fn compute(specs: &[IndicatorSpec], klines: &[Kline]) -> Vec<IndicatorPoint> {
+    let mut sorted: Vec<&Kline> = klines.iter().collect();
+    sorted.sort_by_key(|k| k.utc_begin);
+    let mut series: HashMap<KlineKey, SeriesState> = HashMap::new();
+    let mut points = Vec::new();
+    for kline in sorted {
+        let state = series
+            .entry((kline.pair.clone(), kline.time_frame.clone()))
+            .or_insert_with(|| SeriesState::new(specs));
+        points.extend(state.update(specs, kline));
+    }
+    points
+}
+
+/*
+    Keeps the indicators of every series up to date as klines arrive (e.g. from the aggregator).
+    With a store, every computed point is saved, replacing the one of the same key.
+*/
+#[derive(Clone)]
+pub struct IndicatorEngine {
+    specs: Arc<Vec<IndicatorSpec>>,
+    series: Arc<Mutex<HashMap<KlineKey, SeriesState>>>,
+    store: Option<Arc<dyn IndicatorStore>>,
+}
+
+impl IndicatorEngine {
+    pub fn new(specs: Vec<IndicatorSpec>) -> Self {
+        IndicatorEngine {
+            specs: Arc::new(specs),
+            series: Arc::new(Mutex::new(HashMap::new())),
+            store: None,
+        }
+    }
+
+    pub fn with_store(mut self, store: Arc<dyn IndicatorStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    pub fn specs(&self) -> &[IndicatorSpec] {
+        &self.specs
+    }
+
+    /// Feeds klines in order of their start, returns the points they produced
+    pub fn update(&self, klines: &[Kline]) -> Vec<IndicatorPoint> {
+        let mut sorted: Vec<&Kline> = klines.iter().collect();
+        sorted.sort_by_key(|k| k.utc_begin);
+        let mut series = self.series.lock().unwrap();
+        let mut points = Vec::new();
+        for kline in sorted {
+            let state = series
+                .entry((kline.pair.clone(), kline.time_frame.clone()))
+                .or_insert_with(|| SeriesState::new(&self.specs));
+            points.extend(state.update(&self.specs, kline));
+        }
+        points
+    }
+
+    /// `update`, then saves the points when the engine has a store
+    pub async fn process(&self, klines: &[Kline]) -> Result<Vec<IndicatorPoint>, sqlx::Error> {
+        let points = self.update(klines);
+        if let Some(store) = &self.store {
+            store.save_indicators(&points).await?;
+        }
+        Ok(points)
+    }
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::kline::VBS;
+
+    fn kline(utc_begin: i64, h: f64, l: f64, c: f64) -> Kline {
+        Kline {
+            pair: "BTC_USDT".to_string(),
+            time_frame: "MINUTE_1".to_string(),
+            o: c,
+            h,
+            l,
+            c,
+            utc_begin,
+            volume_bs: VBS {
+                buy_base: 1.0,
+                sell_base: 2.0,
+                buy_quote: 0.0,
+                sell_quote: 0.0,
+            },
+        }
+    }
+
+    fn values(points: &[IndicatorPoint], indicator: &str) -> Vec<f64> {
+        points
+            .iter()
+            .filter(|p| p.indicator == indicator)
+            .map(|p| (p.value * 1e4).round() / 1e4)
+            .collect()
+    }
+
+    #[test]
+    fn test_indicators() {
+        let closes = [1.0, 2.0, 3.0, 4.0, 5.0, 4.0];
+        let klines: Vec<Kline> = closes
+            .iter()
+            .enumerate()
+            .map(|(i, c)| kline(i as i64 * 60_000, c + 1.0, c - 1.0, *c))
+            .collect();
+        let specs = parse_specs("sma:3;ema:3;rsi:3;bb:3,2;atr:3;macd:2,3,2;vwap").unwrap();
+        assert_eq!(specs[3].to_string(), "bb:3,2");
+        let points = compute(&specs, &klines);
+
+        assert_eq!(values(&points, "sma"), vec![2.0, 3.0, 4.0, 4.3333]);
+        assert_eq!(values(&points, "ema"), vec![2.0, 3.0, 4.0, 4.0]);
+        assert_eq!(values(&points, "rsi"), vec![100.0, 100.0, 66.6667]);
+        assert_eq!(values(&points, "bb.upper")[0], 3.633);
+        assert_eq!(values(&points, "atr"), vec![2.0, 2.0, 2.0, 2.0]);
+        assert_eq!(values(&points, "macd.signal").len(), 3);
+        assert_eq!(values(&points, "vwap")[2], 2.0);
+        assert!(points
+            .iter()
+            .all(|p| !p.params.is_empty() || p.indicator == "vwap"));
+
+        // The open candle changing several times ends up where a single final value would
+        let engine = IndicatorEngine::new(specs.clone());
+        engine.update(&klines[..5]);
+        engine.update(&[kline(300_000, 9.0, 1.0, 7.0)]);
+        let replayed = engine.update(&[klines[5].clone()]);
+        let expected: Vec<IndicatorPoint> = points
+            .iter()
+            .filter(|p| p.utc_begin == 300_000)
+            .cloned()
+            .collect();
+        assert_eq!(replayed, expected);
+        assert!(engine.update(&klines[..1]).is_empty()); // older than the newest
+    }
+}
@@ -1,10 +1,30 @@
 pub mod aggregator;
+pub mod api;
+pub mod backtest;
+pub mod backup;
+pub mod capture;
 pub mod config;
 pub mod database;
+pub mod error;
+pub mod exchange;
+pub mod export;
+pub mod health;
 pub mod http_client;
+pub mod import;
+pub mod indicators;
+pub mod live;
+pub mod metrics;
+pub mod order_book;
 pub mod parser;
+pub mod reconcile;
+pub mod replay;
+pub mod retention;
+pub mod sinks;
+pub mod symbols;
+pub mod ticker;
 pub mod websocket_client;
 // export core modules for use as a library
 pub use aggregator::CandleAggregator;
 pub use config::settings::Settings;
+pub use error::Error;
 pub use websocket_client::WebSocketClient;
@@ -0,0 +1,57 @@
+pub mod server;
+
+use std::sync::Arc;
+
+use tokio::sync::broadcast;
+
+use crate::parser::{kline::Kline, recent_trade::RecentTrade};
+
+/// Events a slow subscriber may fall behind by before it starts losing them
+pub const LIVE_FEED_CAPACITY: usize = 4_096;
+
+/// Something that has just arrived from the exchange
+#[derive(Debug, Clone)]
+pub enum LiveEvent {
+    Candle(Kline),
+    Trade(RecentTrade),
+}
+
+/*
+    In-process fan-out of live candles and trades: the aggregator publishes once,
+    every subscriber (e.g. a WebSocket client connection) gets its own copy.
+    Publishing never waits; a subscriber that can't keep up loses the oldest events.
+*/
+#[derive(Clone)]
+pub struct LiveFeed {
+    sender: broadcast::Sender<Arc<LiveEvent>>,
+}
+
+impl Default for LiveFeed {
+    fn default() -> Self {
+        Self::new(LIVE_FEED_CAPACITY)
+    }
+}
+
+impl LiveFeed {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        LiveFeed { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<LiveEvent>> {
+        self.sender.subscribe()
+    }
+
+    pub fn publish_klines(&self, klines: &[Kline]) {
+        for kline in klines {
+            // An error only means nobody is subscribed
+            let _ = self.sender.send(Arc::new(LiveEvent::Candle(kline.clone())));
+        }
+    }
+
+    pub fn publish_trades(&self, trades: &[RecentTrade]) {
+        for trade in trades {
+            let _ = self.sender.send(Arc::new(LiveEvent::Trade(trade.clone())));
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info, warn};
+
+use super::{LiveEvent, LiveFeed};
+use crate::error::Error;
+use crate::export::records::{KlineRecord, TradeRecord};
+
+/// How long the server waits after failing to accept a client before it accepts again
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// What a client can subscribe to
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "channel", rename_all = "lowercase")]
+pub enum Subscription {
+    Candles { pair: String, timeframe: String },
+    Trades { pair: String },
+}
+
+impl Subscription {
+    fn matches(&self, event: &LiveEvent) -> bool {
+        match (self, event) {
+            (Subscription::Candles { pair, timeframe }, LiveEvent::Candle(kline)) => {
+                *pair == kline.pair && *timeframe == kline.time_frame
+            }
+            (Subscription::Trades { pair }, LiveEvent::Trade(trade)) => *pair == trade.pair,
+            _ => false,
+        }
+    }
+}
+
+/*
+    Client requests, e.g.
+        {"op": "subscribe", "channel": "candles", "pair": "BTC_USDT", "timeframe": "MINUTE_1"}
+        {"op": "unsubscribe", "channel": "trades", "pair": "BTC_USDT"}
+*/
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Request {
+    Subscribe(Subscription),
+    Unsubscribe(Subscription),
+}
+
+/// The JSON pushed to subscribers; rows are shaped like the export and the query API
+fn event_message(event: &LiveEvent) -> Value {
+    match event {
+        LiveEvent::Candle(kline) => json!({
+            "channel": "candles",
+            "data": KlineRecord::from(kline),
+        }),
+        LiveEvent::Trade(trade) => json!({
+            "channel": "trades",
+            "data": TradeRecord::from(trade),
+        }),
+    }
+}
+
+/// Accepts WebSocket clients on an already bound listener until the task is dropped
+pub async fn serve(listener: TcpListener, feed: LiveFeed) -> Result<(), Error> {
+    info!("Live feed listening on {}", listener.local_addr()?);
+    loop {
+        // Failures such as running out of file descriptors pass: the server waits and goes on
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                warn!("Live feed failed to accept a client: {}", err);
+                time::sleep(ACCEPT_RETRY_DELAY).await;
+                continue;
+            }
+        };
+        let feed = feed.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_client(stream, feed).await {
+                debug!("Live feed client {} disconnected: {}", peer, err);
+            }
+        });
+    }
+}
+
+/// Binds the address and serves the live feed
+pub async fn bind_and_serve(addr: SocketAddr, feed: LiveFeed) -> Result<(), Error> {
+    serve(TcpListener::bind(addr).await?, feed).await
+}
+
+async fn handle_client(
+    stream: TcpStream,
+    feed: LiveFeed,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    let socket = tokio_tungstenite::accept_async(stream).await?;
+    let (mut sink, mut incoming) = socket.split();
+    // Subscribed before the first request is read, so nothing published after it is missed
+    let mut events = feed.subscribe();
+    let mut subscriptions: HashSet<Subscription> = HashSet::new();
+
+    loop {
+        tokio::select! {
+            message = incoming.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => return Ok(()),
+                    Some(Ok(_)) => continue, // pings are answered by tungstenite
+                    Some(Err(err)) => return Err(err),
+                };
+                let reply = match serde_json::from_str::<Request>(text.as_str()) {
+                    Ok(Request::Subscribe(subscription)) => {
+                        let reply = json!({ "event": "subscribed", "subscription": subscription });
+                        subscriptions.insert(subscription);
+                        reply
+                    }
+                    Ok(Request::Unsubscribe(subscription)) => {
+                        let reply = json!({ "event": "unsubscribed", "subscription": subscription });
+                        subscriptions.remove(&subscription);
+                        reply
+                    }
+                    Err(err) => json!({ "event": "error", "message": err.to_string() }),
+                };
+                sink.send(Message::text(reply.to_string())).await?;
+            }
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if subscriptions.iter().any(|s| s.matches(&event)) {
+                        sink.send(Message::text(event_message(&event).to_string())).await?;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Live feed client is too slow, {} events dropped", skipped);
+                    let notice = json!({ "event": "lagged", "skipped": skipped });
+                    sink.send(Message::text(notice.to_string())).await?;
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+}
@@ -1,81 +1,330 @@
-mod aggregator;
-mod config;
-mod database;
-mod exchange;
-mod http_client;
-mod parser;
-mod websocket_client;
-use aggregator::CandleAggregator;
-use config::settings::Settings;
-use parser::KlineParser;
-use tracing::{debug, error, info, warn};
-use tracing_subscriber::FmtSubscriber;
-
-use database::establish_connection;
-use exchange::{Exchange, ExchangeBuilderError, ExchangeFactory};
+mod cli;
+
+use clap::Parser;
+use cli::{
+    BacktestArgs, BackupArgs, Cli, Command, ExportArgs, ExportTable, ImportArgs, IndicatorsArgs,
+    PartitionsAction, PartitionsArgs, ReconcileArgs, ReparseArgs, ReplayArgs, ReportFormat,
+    StatusArgs,
+};
+use rust_kline_ws::aggregator::{CandleAggregator, TradeCandles};
+use rust_kline_ws::api;
+use rust_kline_ws::backtest::{run_backtest, BrokerConfig};
+use rust_kline_ws::backup::{self, run_backups, BackupConfig};
+use rust_kline_ws::capture::{
+    open_capture, reparse_candles, CaptureConfig, CapturingClient, RawCapture,
+};
+use rust_kline_ws::config::settings::{
+    BackupSettings, HealthSettings, LogFormat, LogSettings, RetentionSettings, Settings,
+    StorageSettings,
+};
+use rust_kline_ws::config::ConfigError;
+use rust_kline_ws::health::{self, HealthConfig, HealthReport, HealthTracker};
+use rust_kline_ws::http_client::http_client::{ReqwestClient, RestClient};
+use rust_kline_ws::http_client::HttpClientError;
+use rust_kline_ws::live::{self, LiveFeed};
+use rust_kline_ws::metrics;
+use rust_kline_ws::order_book::{self, OrderBookConfig, OrderBooks};
+use rust_kline_ws::parser::{self, KlineParser};
+use rust_kline_ws::reconcile::{reconcile, Discrepancy, ReconcileOptions};
+use rust_kline_ws::replay::{final_states, rebuilt_klines, Replay, ReplayPace};
+use rust_kline_ws::retention::{apply_retention, run_retention, RetentionPolicy};
+use rust_kline_ws::sinks::{
+    FeedSink, IndicatorSink, JsonlSink, KlineSink, RedisSink, StdoutSink, StoreSink,
+};
+use rust_kline_ws::symbols::resolve_symbols;
+use rust_kline_ws::ticker::{self, LatestTickers};
+use rust_kline_ws::Error;
+use std::collections::HashMap;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info};
+use tracing_subscriber::EnvFilter;
+
+use rust_kline_ws::database::{
+    open_store, sqlite_file, KlineFilter, KlineStore, KlineWriteBuffer, PartitionedStore,
+    SqliteTuning, Store, TradeFilter, WriteBufferConfig,
+};
+use rust_kline_ws::exchange::{Exchange, ExchangeFactory};
+use rust_kline_ws::export::{export_klines, export_trades};
+use rust_kline_ws::import::{import_file, ImportOptions, ImportReport};
+use rust_kline_ws::indicators::{self, parse_specs, IndicatorEngine};
+use rust_kline_ws::websocket_client::WebSocketClient;
 
 #[tokio::main]
-async fn main() {
-    // Configuring logging
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(tracing::Level::INFO) // Logging level INFO or DEBUG
-        .finish();
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
 
-    tracing::subscriber::set_global_default(subscriber).expect("Failed to set subscriber");
+    if let Err(err) = init_logging() {
+        eprintln!("{}", err); // there is no logger to report it
+        return ExitCode::from(err.exit_code());
+    }
 
-    debug!("Debug logging level is enabled!");
-    info!("Info logging level is enabled!");
-    warn!("Warn logging level is enabled!");
-    error!("Error logging level is enabled!");
+    let result = match cli.command.unwrap_or(Command::Run) {
+        Command::Run => run().await,
+        Command::Export(args) => export(args).await,
+        Command::Import(args) => import(args).await,
+        Command::Status(args) => status(args).await,
+        Command::Indicators(args) => compute_indicators(args).await,
+        Command::Backtest(args) => backtest(args).await,
+        Command::Replay(args) => replay(args).await,
+        Command::Reconcile(args) => reconcile_klines(args).await,
+        Command::Reparse(args) => reparse(args).await,
+        Command::Retention => retention().await,
+        Command::Partitions(args) => partitions(args).await,
+        Command::Backup(args) => backup_database(args).await,
+    };
 
-    let settings = Settings::from_env();
+    match result {
+        Ok(()) => {
+            info!("Finish");
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            error!("{}", err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
 
-    info!("Database URL: {}", settings.db_url);
+/// Configures logging to stderr (stdout may carry exported data)
+fn init_logging() -> Result<(), Error> {
+    let settings = LogSettings::from_env()?;
+    let filter = EnvFilter::try_new(&settings.filter).map_err(|e| ConfigError::Invalid {
+        name: "LOG_LEVEL",
+        value: settings.filter.clone(),
+        reason: e.to_string(),
+    })?;
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr);
+    match settings.format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+    Ok(())
+}
+
+async fn run() -> Result<(), Error> {
+    let mut settings = Settings::from_env()?;
+
+    info!("Database URL: {}", settings.storage.db_url);
     info!("EXCHANGE: {}", settings.exchange);
 
     info!("Starting application...");
 
-    // Create and customize the exchange
-    let exchange = match setup_exchange(&settings).await {
-        Ok(exchange) => {
-            //todo не нужно
-            if let Err(err) = exchange.connect().await {
-                error!("Failed to connect to exchange: {}", err);
-                return;
+    // Opening the storage backend selected by the DB_URL scheme
+    let store = open_storage(&settings.storage).await?;
+
+    // Archiving the raw REST responses and WebSocket frames when CAPTURE_DIR is set
+    let capture = match &settings.capture.dir {
+        Some(dir) => Some(RawCapture::spawn(
+            CaptureConfig::new(dir)
+                .with_max_file_bytes(settings.capture.max_file_mb * 1024 * 1024)
+                .with_zstd_level(settings.capture.zstd_level),
+        )?),
+        None => None,
+    };
+
+    // Up before the symbols are resolved over the network, so a slow exchange still shows up on them
+    if let Some(addr) = settings.metrics_addr {
+        tokio::spawn(async move {
+            if let Err(err) = metrics::bind_and_serve(addr).await {
+                error!("Metrics endpoint stopped: {}", err);
             }
-            Some(exchange)
-        }
-        Err(err) => {
-            error!("Failed to setup exchange: {}", err);
-            None
-        }
+        });
+    }
+
+    let health = settings.health.addr.map(|addr| {
+        let tracker = HealthTracker::new(HealthConfig {
+            kline_staleness: Duration::from_secs(settings.health.kline_staleness_secs),
+            trade_staleness: Duration::from_secs(settings.health.trade_staleness_secs),
+        });
+        let server_tracker = tracker.clone();
+        tokio::spawn(async move {
+            if let Err(err) = health::bind_and_serve(addr, server_tracker).await {
+                error!("Health checks stopped: {}", err);
+            }
+        });
+        tracker
+    });
+
+    // Checking SYMBOLS against the exchange's markets and expanding its patterns
+    let rest_url = match settings.exchange.to_lowercase().as_str() {
+        "binance" => &settings.binance_rest_url,
+        _ => &settings.poloniex_rest_url_base,
     };
+    settings.symbols = resolve_symbols(
+        rest_client(capture.as_ref()).as_ref(),
+        store.as_ref(),
+        &settings.exchange,
+        rest_url,
+        &settings.symbols,
+        settings.symbols_sync,
+    )
+    .await?;
 
-    if let Some(exchange) = exchange {
-        info!("The Exchange process is running");
+    if let Some(addr) = settings.api_addr {
+        let store = store.clone();
+        tokio::spawn(async move {
+            if let Err(err) = api::bind_and_serve(addr, store).await {
+                error!("Query API stopped: {}", err);
+            }
+        });
+    }
+
+    if !settings.retention.rules.is_empty() {
+        let policy = RetentionPolicy {
+            rules: settings.retention.rules.clone(),
+            vacuum: settings.retention.vacuum,
+        };
+        let interval = Duration::from_secs(settings.retention.interval_secs.max(1));
+        tokio::spawn(run_retention(store.clone(), policy, interval));
+    }
 
-        let urls = generate_urls(
-            &settings.poloniex_rest_url_base,
-            &settings.poloniex_rest_url_endpoint,
-            &settings.symbols,
-            &settings.timeframes,
-        );
+    if let Some(dir) = &settings.backup.dir {
+        let config = BackupConfig::new(dir)
+            .with_keep(settings.backup.keep)
+            .with_compress(settings.backup.compress);
+        let interval = Duration::from_secs(settings.backup.interval_secs.max(1));
+        let db_name = backup_file_name(&settings.storage.db_url);
+        tokio::spawn(run_backups(store.clone(), db_name, config, interval));
+    }
 
-        {
-            for url in &urls {
-                debug!("{}", url.2);
+    let feed = settings.live_addr.map(|addr| {
+        let feed = LiveFeed::default();
+        let server_feed = feed.clone();
+        tokio::spawn(async move {
+            if let Err(err) = live::server::bind_and_serve(addr, server_feed).await {
+                error!("Live feed stopped: {}", err);
             }
+        });
+        feed
+    });
+
+    // Create and customize the exchange
+    if let Some(health) = health.as_ref().filter(|_| settings.trades_ws) {
+        health.expect_trades(&settings.symbols);
+    }
+    let exchange = setup_exchange(&settings, store.clone(), feed, health, capture.as_ref()).await?;
+
+    info!("The Exchange process is running");
+
+    let urls = generate_urls(
+        &settings.poloniex_rest_url_base,
+        &settings.poloniex_rest_url_endpoint,
+        &settings.symbols,
+        &settings.timeframes,
+    );
+
+    {
+        for url in &urls {
+            debug!("{}", url.2);
         }
+    }
+
+    exchange.run(&urls).await?;
 
-        if let Err(err) = exchange.run(&urls).await {
-            error!("Failed to run exchange: {}", err);
+    // Started once the handlers are built, which the candles built from trades go through
+    let trades = exchange.aggregator.clone().filter(|_| settings.trades_ws);
+    let streaming = spawn_streams(&settings, &store, trades, capture.as_ref());
+    if streaming {
+        // Order books, tickers and trades are kept up to date, and the klines fetched again, until the collector is stopped
+        info!("Recording order books, tickers and trades, press Ctrl-C to stop");
+        let interval = Duration::from_secs(settings.poll_interval_secs.max(1));
+        tokio::select! {
+            _ = exchange.poll(&urls, interval) => {}
+            result = tokio::signal::ctrl_c() => result?,
         }
-    } else {
-        // Handling a case where an exchange has not been created
-        error!("Exchange not available");
     }
-    // You can pass the db_pool to other parts of the application
-    info!("Finish");
+    if let Some(capture) = &capture {
+        capture.flush().await?;
+    }
+    Ok(())
+}
+
+/// A REST client, capturing its responses when there is a capture
+fn rest_client(capture: Option<&RawCapture>) -> Box<dyn RestClient> {
+    let client = Box::new(ReqwestClient::new());
+    match capture {
+        Some(capture) => Box::new(CapturingClient::new(client, capture.clone())),
+        None => client,
+    }
+}
+
+/// Starts the order book, ticker and trade tasks that are enabled; false when there are none
+fn spawn_streams(
+    settings: &Settings,
+    store: &Arc<dyn Store>,
+    trades: Option<Arc<CandleAggregator>>,
+    capture: Option<&RawCapture>,
+) -> bool {
+    let books = settings.order_book.enabled.then(|| {
+        let books = OrderBooks::new();
+        let config = OrderBookConfig {
+            depth: settings.order_book.depth,
+            snapshot_interval: Duration::from_millis(settings.order_book.snapshot_interval_ms),
+        };
+        tokio::spawn(order_book::record_snapshots(
+            books.clone(),
+            store.clone(),
+            config,
+        ));
+        books
+    });
+
+    let tickers = (settings.tickers.rest || settings.tickers.ws).then(|| {
+        let tickers = LatestTickers::new();
+        let interval = Duration::from_millis(settings.tickers.interval_ms);
+        if settings.tickers.rest {
+            let urls = settings
+                .symbols
+                .iter()
+                .map(|symbol| {
+                    let url = settings
+                        .tickers
+                        .rest_endpoint
+                        .replace("{base_url}", &settings.poloniex_rest_url_base)
+                        .replace("{symbol}", symbol);
+                    (symbol.clone(), url)
+                })
+                .collect();
+            tokio::spawn(ticker::poll_tickers(
+                rest_client(capture),
+                urls,
+                tickers.clone(),
+                interval,
+            ));
+        }
+        tokio::spawn(ticker::record_tickers(
+            tickers.clone(),
+            store.clone(),
+            interval,
+        ));
+        tickers
+    });
+
+    let streaming = books.is_some() || tickers.is_some() || trades.is_some();
+    if books.is_some() || settings.tickers.ws || trades.is_some() {
+        let mut client = WebSocketClient::new(&settings.poloniex_ws_url, &settings.symbols);
+        if let Some(books) = &books {
+            client = client.with_order_books(books.clone());
+        }
+        if let Some(tickers) = tickers.as_ref().filter(|_| settings.tickers.ws) {
+            client = client.with_tickers(tickers.clone());
+        }
+        if let Some(aggregator) = trades {
+            client = client.with_trades(aggregator);
+        }
+        if let Some(capture) = capture {
+            client = client.with_capture(capture.clone());
+        }
+        tokio::spawn(async move { client.run().await });
+    }
+
+    streaming
 }
 
 fn generate_urls(
@@ -98,39 +347,519 @@ fn generate_urls(
     urls
 }
 
+/// Opens the storage backend selected by the DB_URL scheme
+async fn open_storage(settings: &StorageSettings) -> Result<Arc<dyn Store>, Error> {
+    let tuning = SqliteTuning::from_settings(settings)?;
+    Ok(open_store(&settings.db_url, &tuning).await?)
+}
+
+/// Exports a table from the store to a file or stdout
+async fn export(args: ExportArgs) -> Result<(), Error> {
+    let settings = StorageSettings::from_env()?;
+    let store = open_storage(&settings).await?;
+
+    let out: Box<dyn Write + Send> = if args.output.as_os_str() == "-" {
+        Box::new(BufWriter::new(io::stdout()))
+    } else {
+        Box::new(BufWriter::new(std::fs::File::create(&args.output)?))
+    };
+
+    match args.table {
+        ExportTable::Klines => {
+            let filter = KlineFilter {
+                pair: args.pair,
+                time_frame: args.timeframe,
+                from: args.from,
+                to: args.to,
+            };
+            export_klines(store.as_ref(), &filter, args.format, out).await?;
+        }
+        ExportTable::Trades => {
+            let filter = TradeFilter {
+                pair: args.pair,
+                from: args.from,
+                to: args.to,
+            };
+            export_trades(store.as_ref(), &filter, args.format, out).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Imports kline archives into the store
+async fn import(args: ImportArgs) -> Result<(), Error> {
+    let settings = StorageSettings::from_env()?;
+    let store = open_storage(&settings).await?;
+    let options = ImportOptions {
+        format: args.format,
+        pair: args.pair,
+        time_frame: args.timeframe,
+    };
+
+    let mut total = ImportReport::default();
+    for file in &args.files {
+        let report = import_file(store.as_ref(), file, &options).await?;
+        total.rows += report.rows;
+        total.imported += report.imported;
+        total.rejected += report.rejected;
+    }
+    info!(
+        "Import finished: {} rows, {} imported, {} rejected",
+        total.rows, total.imported, total.rejected
+    );
+    Ok(())
+}
+
+/// Computes indicators over stored klines and stores them, e.g. for a new indicator or after an import
+async fn compute_indicators(args: IndicatorsArgs) -> Result<(), Error> {
+    let settings = StorageSettings::from_env()?;
+    let list = match args.indicators {
+        Some(list) => list,
+        None => std::env::var("INDICATORS").map_err(|_| ConfigError::Missing("INDICATORS"))?,
+    };
+    let specs = parse_specs(&list).map_err(|reason| ConfigError::Invalid {
+        name: "INDICATORS",
+        value: list.clone(),
+        reason,
+    })?;
+    let store = open_storage(&settings).await?;
+
+    let filter = KlineFilter {
+        pair: args.pair,
+        time_frame: args.timeframe,
+        from: args.from,
+        to: args.to,
+    };
+    let klines = store.load_klines(&filter).await?;
+    let points = indicators::compute(&specs, &klines);
+    store.save_indicators(&points).await?;
+    info!(
+        "Stored {} indicator values computed over {} klines",
+        points.len(),
+        klines.len()
+    );
+    Ok(())
+}
+
+/// Runs a built-in strategy over stored klines and writes its report
+async fn backtest(args: BacktestArgs) -> Result<(), Error> {
+    let settings = StorageSettings::from_env()?;
+    let store = open_storage(&settings).await?;
+    let filter = KlineFilter {
+        pair: args.pair,
+        time_frame: args.timeframe,
+        from: args.from,
+        to: args.to,
+    };
+    let config = BrokerConfig {
+        initial_cash: args.cash,
+        fee_rate: args.fee,
+        slippage: args.slippage,
+    };
+    let mut strategy = args.strategy.build();
+    let report = run_backtest(store.as_ref(), &filter, strategy.as_mut(), config).await?;
+    info!(
+        "Backtest of {} over {} klines: return {:.2}%, max drawdown {:.2}%, {} trades, win rate {:.1}%",
+        args.strategy,
+        report.klines,
+        report.total_return * 100.0,
+        report.max_drawdown * 100.0,
+        report.trades.len(),
+        report.win_rate * 100.0
+    );
+
+    let mut out: Box<dyn Write + Send> = if args.output.as_os_str() == "-" {
+        Box::new(BufWriter::new(io::stdout()))
+    } else {
+        Box::new(BufWriter::new(std::fs::File::create(&args.output)?))
+    };
+    match args.format {
+        ReportFormat::Json => report.write_json(&mut out)?,
+        ReportFormat::Csv => report.write_equity_csv(&mut out)?,
+    }
+    out.flush()?;
+    if let Some(path) = &args.trades {
+        report.write_trades_csv(BufWriter::new(std::fs::File::create(path)?))?;
+    }
+    Ok(())
+}
+
+/// Rebuilds candles from stored or captured trades and exports their final states
+async fn replay(args: ReplayArgs) -> Result<(), Error> {
+    let candles = TradeCandles::new(&args.timeframes).map_err(|reason| ConfigError::Invalid {
+        name: "timeframes",
+        value: args.timeframes.join(","),
+        reason,
+    })?;
+    let (replay, rebuilt) = Replay::in_memory(candles, args.pace).await;
+
+    let filter = TradeFilter {
+        pair: args.pair,
+        from: args.from,
+        to: args.to,
+    };
+    info!("Replaying trades at {} pace", args.pace);
+    match &args.capture {
+        Some(path) => replay.capture(open_capture(path)?, &filter).await?,
+        None => {
+            let store = open_storage(&StorageSettings::from_env()?).await?;
+            replay.stored_trades(store.as_ref(), &filter).await?
+        }
+    };
+
+    let finals = rebuilt_klines(&rebuilt).await?;
+    rebuilt.upsert_klines(&finals).await?;
+    let out: Box<dyn Write + Send> = if args.output.as_os_str() == "-" {
+        Box::new(BufWriter::new(io::stdout()))
+    } else {
+        Box::new(BufWriter::new(std::fs::File::create(&args.output)?))
+    };
+    export_klines(rebuilt.as_ref(), &KlineFilter::default(), args.format, out).await?;
+    Ok(())
+}
+
+/// Compares stored or rebuilt candles of one series with the exchange's and prints the report
+async fn reconcile_klines(args: ReconcileArgs) -> Result<(), Error> {
+    let settings = StorageSettings::from_env()?;
+    let rest_url = match args.rest_url {
+        Some(url) => url,
+        None => std::env::var("POLONIEX_REST_URL_BASE")
+            .map_err(|_| ConfigError::Missing("POLONIEX_REST_URL_BASE"))?,
+    };
+    let invalid_time_frame = |reason: String| ConfigError::Invalid {
+        name: "timeframe",
+        value: args.timeframe.clone(),
+        reason,
+    };
+    let step = parser::time_frame_millis(&args.timeframe)
+        .ok_or_else(|| invalid_time_frame("unknown time frame".to_string()))?;
+    let store = open_storage(&settings).await?;
+    let options = ReconcileOptions {
+        pair: args.pair.clone(),
+        time_frame: args.timeframe.clone(),
+        from: args.from,
+        to: args.to.unwrap_or_else(metrics::unix_millis),
+        tolerance: args.tolerance,
+        replace: args.replace,
+    };
+
+    let local = if args.rebuild {
+        let candles =
+            TradeCandles::new(std::slice::from_ref(&args.timeframe)).map_err(invalid_time_frame)?;
+        let (replay, rebuilt) = Replay::in_memory(candles, ReplayPace::Max).await;
+        let filter = TradeFilter {
+            pair: Some(options.pair.clone()),
+            from: Some(options.from),
+            to: Some(options.to + step - 1), // up to the end of the last candle
+        };
+        replay.stored_trades(store.as_ref(), &filter).await?;
+        rebuilt_klines(&rebuilt).await?
+    } else {
+        let filter = KlineFilter {
+            pair: Some(options.pair.clone()),
+            time_frame: Some(options.time_frame.clone()),
+            from: Some(options.from),
+            to: Some(options.to),
+        };
+        final_states(store.load_klines(&filter).await?)
+    };
+
+    let report = reconcile(
+        &ReqwestClient::new(),
+        &rest_url,
+        store.as_ref(),
+        local,
+        &options,
+    )
+    .await?;
+    let mut out = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut out, &report)?;
+    writeln!(out)?;
+
+    let remaining = if args.replace {
+        report
+            .discrepancies
+            .iter()
+            .filter(|d| matches!(d, Discrepancy::Unexpected { .. }))
+            .count()
+    } else {
+        report.discrepancies.len()
+    };
+    if remaining > 0 {
+        return Err(Error::Discrepancies(remaining));
+    }
+    Ok(())
+}
+
+/// Prints the health report of a running collector
+async fn status(args: StatusArgs) -> Result<(), Error> {
+    let url = match args.url {
+        Some(url) => url,
+        None => match HealthSettings::from_env()?.addr {
+            Some(addr) => format!("http://{}", addr),
+            None => return Err(ConfigError::Missing("HEALTH_ADDR").into()),
+        },
+    };
+    let unreachable =
+        |err: reqwest::Error| HttpClientError::new(&format!("Failed to query {}: {}", url, err));
+    // 503 still carries the report
+    let report: HealthReport = reqwest::get(format!("{}/health", url.trim_end_matches('/')))
+        .await
+        .map_err(unreachable)?
+        .json()
+        .await
+        .map_err(unreachable)?;
+
+    let mut out = io::stdout().lock();
+    for series in &report.series {
+        writeln!(
+            out,
+            "{:<6} {} {} last candle {} behind {}s",
+            if series.stale { "STALE" } else { "ok" },
+            series.pair,
+            series.timeframe,
+            series
+                .last_utc_begin
+                .map_or("none".to_string(), |t| t.to_string()),
+            series.behind_secs
+        )?;
+    }
+    for trades in &report.trades {
+        writeln!(
+            out,
+            "{:<6} {} trades last received {}",
+            if trades.stale { "STALE" } else { "ok" },
+            trades.pair,
+            trades
+                .last_received
+                .map_or("none".to_string(), |t| t.to_string())
+        )?;
+    }
+
+    if !report.ready {
+        return Err(Error::Unhealthy(
+            "the collector is not running yet".to_string(),
+        ));
+    }
+    if !report.healthy {
+        let stale = report.series.iter().filter(|s| s.stale).count()
+            + report.trades.iter().filter(|t| t.stale).count();
+        return Err(Error::Unhealthy(format!("{} stale", stale)));
+    }
+    Ok(())
+}
+
+/// Parses captured REST candle responses again and upserts the klines into the configured store
+async fn reparse(args: ReparseArgs) -> Result<(), Error> {
+    let store = open_storage(&StorageSettings::from_env()?).await?;
+    for path in &args.files {
+        info!("Re-parsing {}", path.display());
+        reparse_candles(open_capture(path)?, store.as_ref()).await?;
+    }
+    Ok(())
+}
+
+/// Applies the retention rules once and prints what was deleted as JSON
+async fn retention() -> Result<(), Error> {
+    let settings = RetentionSettings::from_env()?;
+    let store = open_storage(&StorageSettings::from_env()?).await?;
+    let policy = RetentionPolicy {
+        rules: settings.rules,
+        vacuum: settings.vacuum,
+    };
+    let report = apply_retention(store.as_ref(), &policy, metrics::unix_millis()).await?;
+    let mut out = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut out, &report)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Lists, merges or archives the partitions of the SQLite database of DB_URL
+async fn partitions(args: PartitionsArgs) -> Result<(), Error> {
+    let settings = StorageSettings::from_env()?;
+    let tuning = SqliteTuning::from_settings(&settings)?;
+    let scheme = tuning
+        .partition_by
+        .ok_or(ConfigError::Missing("SQLITE_PARTITION_BY"))?;
+    let store = PartitionedStore::open(sqlite_file(&settings.db_url), scheme, &tuning).await?;
+    match args.action {
+        PartitionsAction::List => {
+            let mut out = io::stdout().lock();
+            serde_json::to_writer_pretty(&mut out, &store.list().await?)?;
+            writeln!(out)?;
+        }
+        PartitionsAction::Merge { keys } => {
+            let moved = store.merge(&keys).await?;
+            info!("Moved {} rows into the main file", moved);
+        }
+        PartitionsAction::Archive { keys, dir } => {
+            store.archive(&keys, &dir).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Name of the database copy in a backup: the file name of DB_URL
+fn backup_file_name(db_url: &str) -> String {
+    Path::new(sqlite_file(db_url))
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "db.sqlite".to_string())
+}
+
+/// Backs up the database of DB_URL once and prints the backup as JSON
+async fn backup_database(args: BackupArgs) -> Result<(), Error> {
+    let settings = BackupSettings::from_env()?;
+    let storage = StorageSettings::from_env()?;
+    let dir = args
+        .dir
+        .or(settings.dir)
+        .ok_or(ConfigError::Missing("BACKUP_DIR"))?;
+    let config = BackupConfig::new(dir)
+        .with_keep(args.keep.unwrap_or(settings.keep))
+        .with_compress(settings.compress && !args.no_compress);
+    let store = open_storage(&storage).await?;
+    let report =
+        backup::backup(store.as_ref(), &backup_file_name(&storage.db_url), &config).await?;
+    let mut out = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut out, &report)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// The sinks KLINE_SINKS names, one of each, when what they need is configured
+fn kline_sinks(
+    settings: &Settings,
+    writer: &KlineWriteBuffer,
+    feed: Option<&LiveFeed>,
+    engine: Option<&IndicatorEngine>,
+) -> Result<HashMap<String, Arc<dyn KlineSink>>, Error> {
+    let mut sinks: HashMap<String, Arc<dyn KlineSink>> = HashMap::new();
+    for name in settings.kline_sinks.iter().flat_map(|rule| &rule.sinks) {
+        if sinks.contains_key(name) {
+            continue;
+        }
+        let missing = |needs: &str| ConfigError::Invalid {
+            name: "KLINE_SINKS",
+            value: name.to_string(),
+            reason: format!("the {} sink needs {}", name, needs),
+        };
+        let sink: Arc<dyn KlineSink> =
+            match name.as_str() {
+                "live" => Arc::new(FeedSink(feed.ok_or_else(|| missing("LIVE_ADDR"))?.clone())),
+                "indicators" => Arc::new(IndicatorSink(
+                    engine.ok_or_else(|| missing("INDICATORS"))?.clone(),
+                )),
+                "stdout" => Arc::new(StdoutSink),
+                "jsonl" => Arc::new(JsonlSink::new(
+                    settings
+                        .sinks
+                        .jsonl_dir
+                        .as_ref()
+                        .ok_or_else(|| missing("SINK_JSONL_DIR"))?,
+                    settings.sinks.jsonl_max_file_mb * 1024 * 1024,
+                )?),
+                "redis" => {
+                    let url = settings
+                        .sinks
+                        .redis_url
+                        .as_ref()
+                        .ok_or_else(|| missing("SINK_REDIS_URL"))?;
+                    Arc::new(RedisSink::new(url, &settings.sinks.redis_channel).map_err(
+                        |reason| ConfigError::Invalid {
+                            name: "SINK_REDIS_URL",
+                            value: url.clone(),
+                            reason,
+                        },
+                    )?)
+                }
+                "store" => Arc::new(StoreSink(writer.clone())),
+                // Parsing KLINE_SINKS checked the names against SINK_NAMES: one is missing here
+                _ => {
+                    return Err(ConfigError::Invalid {
+                        name: "KLINE_SINKS",
+                        value: name.to_string(),
+                        reason: format!("unknown sink '{}'", name),
+                    }
+                    .into())
+                }
+            };
+        sinks.insert(name.clone(), sink);
+    }
+    Ok(sinks)
+}
+
 /// Creates and configures an Exchange instance
-async fn setup_exchange(settings: &Settings) -> Result<Exchange, String> {
+async fn setup_exchange(
+    settings: &Settings,
+    store: Arc<dyn Store>,
+    feed: Option<LiveFeed>,
+    health: Option<HealthTracker>,
+    capture: Option<&RawCapture>,
+) -> Result<Exchange, Error> {
     /*** Factory returns Builder ***/
-    let mut builder =
-        ExchangeFactory::create(settings).map_err(|err| format!("Factory error: {}", err))?;
+    let mut builder = ExchangeFactory::create(settings)?;
     debug!("The ExchangeFactory is complete ");
 
-    // Establishing a connection to the database
-    let db_pool = establish_connection(&settings.db_url).await;
-    builder = builder.set_target_db(db_pool);
-    debug!("Builder setting db pool is complete");
+    if capture.is_some() {
+        builder = builder.set_rest_client(rest_client(capture));
+    }
+
+    builder = builder.set_target_db(store.clone());
+    debug!("Builder setting storage is complete");
 
     // Create a parser
     let parser = KlineParser::new();
     builder = builder.set_parser(parser);
 
-    // Create an aggregator
-    let aggregator = CandleAggregator::get_instance().clone();
-    builder = builder.set_aggregator(aggregator);
+    // Create an aggregator that writes into this exchange's database through the write buffer
+    let config = WriteBufferConfig {
+        max_rows: settings.storage.write_buffer_max_rows,
+        max_delay: Duration::from_millis(settings.storage.write_buffer_max_delay_ms),
+        ..WriteBufferConfig::default()
+    };
+    let writer = match &health {
+        Some(health) => KlineWriteBuffer::spawn_with_health(store.clone(), config, health.clone()),
+        None => KlineWriteBuffer::spawn(store.clone(), config),
+    };
+    let mut aggregator = CandleAggregator::new(writer.clone());
+    if settings.trades_ws {
+        // The live trades are stored and build the candles of the time frames they can
+        let time_frames: Vec<String> = settings
+            .timeframes
+            .iter()
+            .filter(|time_frame| TradeCandles::can_build(time_frame))
+            .cloned()
+            .collect();
+        let candles = TradeCandles::new(&time_frames).map_err(|reason| ConfigError::Invalid {
+            name: "TIMEFRAMES",
+            value: settings.timeframes.join(","),
+            reason,
+        })?;
+        aggregator = aggregator
+            .with_trade_store(store.clone())
+            .with_trade_candles(candles);
+    }
+    let engine = (!settings.indicators.is_empty())
+        .then(|| IndicatorEngine::new(settings.indicators.clone()).with_store(store));
+    let sinks = kline_sinks(settings, &writer, feed.as_ref(), engine.as_ref())?;
+    for rule in &settings.kline_sinks {
+        let rule_sinks = rule.sinks.iter().map(|name| sinks[name].clone()).collect();
+        aggregator = aggregator.with_series_sinks(rule.series.clone(), rule_sinks);
+    }
+    if let Some(engine) = engine {
+        aggregator = aggregator.with_indicators(engine);
+    }
+    if let Some(feed) = feed {
+        aggregator = aggregator.with_live_feed(feed);
+    }
+    if let Some(health) = health {
+        aggregator = aggregator.with_health(health);
+    }
+    builder = builder.set_aggregator(Arc::new(aggregator));
 
     // Assembling the Exchange object
-    match builder.build() {
-        Ok(exchange) => {
-            debug!("The Exchange is complete ");
-            Ok(exchange)
-        }
-        Err(ExchangeBuilderError::MissingName) => Err("Name is missing".to_string()),
-        Err(ExchangeBuilderError::MissingRestUrl) => Err("REST URL is missing".to_string()),
-        Err(ExchangeBuilderError::MissingRestClient) => Err("RestClient is missing".to_string()),
-        Err(ExchangeBuilderError::MissingParser) => Err("Parser is missing".to_string()),
-        Err(ExchangeBuilderError::MissingCandleAggregator) => {
-            Err("CandleAggregator is missing".to_string())
-        }
-        Err(ExchangeBuilderError::MissingDBPool) => Err("DBPool is missing".to_string()),
-    }
+    let exchange = builder.build()?;
+    debug!("The Exchange is complete ");
+    Ok(exchange)
 }
@@ -0,0 +1,228 @@
+use std::net::SocketAddr;
+use std::sync::LazyLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::http::header::CONTENT_TYPE;
+use axum::routing::get;
+use axum::Router;
+use prometheus::{
+    Encoder, GaugeVec, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts,
+    Registry, TextEncoder,
+};
+use tokio::net::TcpListener;
+use tracing::info;
+
+use crate::error::Error;
+use crate::parser::{kline::Kline, time_frame_millis};
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// The process-wide metrics, registered on first use
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+/*
+    Collector metrics in the Prometheus text format. Alerting on a symbol that stopped updating:
+        time() - klines_last_saved_timestamp_seconds > 300
+*/
+pub struct Metrics {
+    registry: Registry,
+    pub rest_requests: IntCounterVec, // by HTTP status, `error` when no response
+    pub parse_rejects: IntCounterVec, // candles dropped by the parser, by pair
+    pub klines_saved: IntCounterVec,  // by pair, time frame
+    pub klines_last_saved: GaugeVec,  // unix time of the last save, by pair, time frame
+    pub klines_unexpected: IntCounterVec, // reaching the aggregator's fallback handler, by pair, time frame
+    pub kline_save_lag: HistogramVec,     // candle close -> saved, by time frame
+    pub db_write_duration: Histogram,     // one write-buffer batch
+    pub db_write_errors: IntCounter,
+    pub trades_received: IntCounterVec, // by pair, `rate()` gives trades/sec
+    pub trades_last_received: GaugeVec, // unix time of the last trade, by pair
+    pub websocket_reconnects: IntCounter,
+    pub order_book_resyncs: IntCounterVec, // books dropped after a sequence gap, by pair
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        let metrics = Metrics {
+            rest_requests: IntCounterVec::new(
+                Opts::new("rest_requests_total", "REST requests to the exchange"),
+                &["status"],
+            )
+            .unwrap(),
+            parse_rejects: IntCounterVec::new(
+                Opts::new("parse_rejects_total", "Candles rejected by the parser"),
+                &["pair"],
+            )
+            .unwrap(),
+            klines_saved: IntCounterVec::new(
+                Opts::new("klines_saved_total", "Klines written to the store"),
+                &["pair", "timeframe"],
+            )
+            .unwrap(),
+            klines_unexpected: IntCounterVec::new(
+                Opts::new(
+                    "klines_unexpected_total",
+                    "Klines of series the aggregator has no handler for",
+                ),
+                &["pair", "timeframe"],
+            )
+            .unwrap(),
+            klines_last_saved: GaugeVec::new(
+                Opts::new(
+                    "klines_last_saved_timestamp_seconds",
+                    "When klines of the series were last written",
+                ),
+                &["pair", "timeframe"],
+            )
+            .unwrap(),
+            kline_save_lag: HistogramVec::new(
+                HistogramOpts::new(
+                    "kline_save_lag_seconds",
+                    "Time from a candle's close to its write",
+                )
+                .buckets(vec![1.0, 5.0, 15.0, 30.0, 60.0, 300.0, 900.0, 3600.0]),
+                &["timeframe"],
+            )
+            .unwrap(),
+            db_write_duration: Histogram::with_opts(HistogramOpts::new(
+                "db_write_duration_seconds",
+                "Duration of one write-buffer batch",
+            ))
+            .unwrap(),
+            db_write_errors: IntCounter::new(
+                "db_write_errors_total",
+                "Failed write-buffer batches",
+            )
+            .unwrap(),
+            trades_received: IntCounterVec::new(
+                Opts::new("trades_received_total", "Recent trades received"),
+                &["pair"],
+            )
+            .unwrap(),
+            trades_last_received: GaugeVec::new(
+                Opts::new(
+                    "trades_last_received_timestamp_seconds",
+                    "When a trade of the pair was last received",
+                ),
+                &["pair"],
+            )
+            .unwrap(),
+            websocket_reconnects: IntCounter::new(
+                "websocket_reconnects_total",
+                "Reconnections of the exchange WebSocket",
+            )
+            .unwrap(),
+            order_book_resyncs: IntCounterVec::new(
+                Opts::new(
+                    "order_book_resyncs_total",
+                    "Order books resubscribed after a sequence gap",
+                ),
+                &["pair"],
+            )
+            .unwrap(),
+            registry,
+        };
+        let collectors: [Box<dyn prometheus::core::Collector>; 12] = [
+            Box::new(metrics.rest_requests.clone()),
+            Box::new(metrics.parse_rejects.clone()),
+            Box::new(metrics.klines_saved.clone()),
+            Box::new(metrics.klines_last_saved.clone()),
+            Box::new(metrics.klines_unexpected.clone()),
+            Box::new(metrics.kline_save_lag.clone()),
+            Box::new(metrics.db_write_duration.clone()),
+            Box::new(metrics.db_write_errors.clone()),
+            Box::new(metrics.trades_received.clone()),
+            Box::new(metrics.trades_last_received.clone()),
+            Box::new(metrics.websocket_reconnects.clone()),
+            Box::new(metrics.order_book_resyncs.clone()),
+        ];
+        for collector in collectors {
+            metrics.registry.register(collector).unwrap(); // names are unique, this can't fail
+        }
+        metrics
+    }
+
+    /// Counts a batch of klines that has just been written
+    pub fn record_saved_klines(&self, klines: &[Kline]) {
+        let now = unix_millis();
+        for kline in klines {
+            let labels = [kline.pair.as_str(), kline.time_frame.as_str()];
+            self.klines_saved.with_label_values(&labels).inc();
+            self.klines_last_saved
+                .with_label_values(&labels)
+                .set(now as f64 / 1_000.0);
+            // The current candle is saved before it closes, only closed ones have a lag
+            if let Some(length) = time_frame_millis(&kline.time_frame) {
+                let close = kline.utc_begin + length;
+                if close <= now {
+                    self.kline_save_lag
+                        .with_label_values(&[kline.time_frame.as_str()])
+                        .observe((now - close) as f64 / 1_000.0);
+                }
+            }
+        }
+    }
+
+    /// All metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap(); // writing to a Vec can't fail
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+/// Current time in unix milliseconds
+pub fn unix_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// The `/metrics` endpoint
+pub fn router() -> Router {
+    Router::new().route(
+        "/metrics",
+        get(|| async {
+            (
+                [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+                metrics().render(),
+            )
+        }),
+    )
+}
+
+/// Serves `/metrics` on an already bound listener until the task is dropped
+pub async fn serve(listener: TcpListener) -> Result<(), Error> {
+    info!("Metrics listening on {}", listener.local_addr()?);
+    axum::serve(listener, router()).await?;
+    Ok(())
+}
+
+/// Binds the address and serves `/metrics`
+pub async fn bind_and_serve(addr: SocketAddr) -> Result<(), Error> {
+    serve(TcpListener::bind(addr).await?).await
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_saved_klines() {
+        let kline = Kline::sample("METRICS_TEST", "MINUTE_1", unix_millis() - 120_000);
+        metrics().record_saved_klines(&[kline.clone(), kline]);
+
+        let text = metrics().render();
+        assert!(text.contains(r#"klines_saved_total{pair="METRICS_TEST",timeframe="MINUTE_1"} 2"#));
+        assert!(text.contains("klines_last_saved_timestamp_seconds{pair=\"METRICS_TEST\""));
+        assert!(text.contains(r#"kline_save_lag_seconds_count{timeframe="MINUTE_1"}"#));
+    }
+}
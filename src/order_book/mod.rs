@@ -0,0 +1,333 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::{self, MissedTickBehavior};
+use tracing::{debug, error};
+
+use crate::database::OrderBookStore;
+use crate::parser::order_book::{OrderBookSnapshot, PriceLevel};
+use crate::websocket_client::message::BookData;
+
+/// How the local order books are persisted
+#[derive(Debug, Clone)]
+pub struct OrderBookConfig {
+    pub depth: usize,                // levels per side in a stored snapshot
+    pub snapshot_interval: Duration, // how often the books are stored
+}
+
+impl Default for OrderBookConfig {
+    fn default() -> Self {
+        OrderBookConfig {
+            depth: 20,
+            snapshot_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Why a message couldn't be applied; the book has to be resynchronized from a new snapshot
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookError {
+    NotSynced,                            // an update arrived before the snapshot
+    Gap { expected: i64, received: i64 }, // a message was missed
+    InvalidLevel(String),
+}
+
+impl fmt::Display for BookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BookError::NotSynced => write!(f, "update before the snapshot"),
+            BookError::Gap { expected, received } => write!(
+                f,
+                "sequence gap: expected lastId {}, received {}",
+                expected, received
+            ),
+            BookError::InvalidLevel(level) => write!(f, "invalid level {}", level),
+        }
+    }
+}
+
+impl std::error::Error for BookError {}
+
+/// A price as a map key; prices from the exchange are finite, so the total order is the numeric one
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Price(f64);
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+fn parse_level((price, quantity): &(String, String)) -> Result<(Price, f64), BookError> {
+    let invalid = || BookError::InvalidLevel(format!("[{}, {}]", price, quantity));
+    let price: f64 = price.parse().map_err(|_| invalid())?;
+    let quantity: f64 = quantity.parse().map_err(|_| invalid())?;
+    if !price.is_finite() || !quantity.is_finite() || quantity < 0.0 {
+        return Err(invalid());
+    }
+    Ok((Price(price), quantity))
+}
+
+/*
+    The L2 book of one symbol, kept from a snapshot and the updates that follow it.
+    Every message carries its own `id` and the `lastId` of the previous one, so a lost message
+    shows up as a gap; the book is then cleared until a new snapshot arrives.
+*/
+#[derive(Debug, Default)]
+pub struct OrderBook {
+    bids: BTreeMap<Price, f64>,
+    asks: BTreeMap<Price, f64>,
+    last_id: Option<i64>, // None until the snapshot
+    timestamp: i64,
+}
+
+impl OrderBook {
+    pub fn apply_snapshot(&mut self, data: &BookData) -> Result<(), BookError> {
+        self.reset();
+        let bids = data
+            .bids
+            .iter()
+            .map(parse_level)
+            .collect::<Result<_, _>>()?;
+        let asks = data
+            .asks
+            .iter()
+            .map(parse_level)
+            .collect::<Result<_, _>>()?;
+        self.bids = bids;
+        self.asks = asks;
+        self.bids.retain(|_, quantity| *quantity > 0.0);
+        self.asks.retain(|_, quantity| *quantity > 0.0);
+        self.last_id = Some(data.id);
+        self.timestamp = data.ts;
+        Ok(())
+    }
+
+    /// Applies a delta: a level with zero quantity is removed, any other replaces the stored one
+    pub fn apply_update(&mut self, data: &BookData) -> Result<(), BookError> {
+        let expected = self.last_id.ok_or(BookError::NotSynced)?;
+        if data.last_id != expected {
+            self.reset();
+            return Err(BookError::Gap {
+                expected,
+                received: data.last_id,
+            });
+        }
+        // Parse everything first, so a bad message leaves the book untouched
+        let bids: Vec<(Price, f64)> = data
+            .bids
+            .iter()
+            .map(parse_level)
+            .collect::<Result<_, _>>()?;
+        let asks: Vec<(Price, f64)> = data
+            .asks
+            .iter()
+            .map(parse_level)
+            .collect::<Result<_, _>>()?;
+        for (side, levels) in [(&mut self.bids, bids), (&mut self.asks, asks)] {
+            for (price, quantity) in levels {
+                if quantity == 0.0 {
+                    side.remove(&price);
+                } else {
+                    side.insert(price, quantity);
+                }
+            }
+        }
+        self.last_id = Some(data.id);
+        self.timestamp = data.ts;
+        Ok(())
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.last_id.is_some()
+    }
+
+    pub fn reset(&mut self) {
+        *self = OrderBook::default();
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    /// The best `depth` levels of each side
+    pub fn snapshot(&self, pair: &str, depth: usize) -> OrderBookSnapshot {
+        let level = |(price, quantity): (&Price, &f64)| PriceLevel {
+            price: price.0,
+            quantity: *quantity,
+        };
+        OrderBookSnapshot {
+            pair: pair.to_string(),
+            timestamp: self.timestamp,
+            bids: self.bids.iter().rev().take(depth).map(level).collect(),
+            asks: self.asks.iter().take(depth).map(level).collect(),
+        }
+    }
+}
+
+/// The books of all symbols, shared by the WebSocket client that updates them and their readers
+#[derive(Clone, Default)]
+pub struct OrderBooks {
+    books: Arc<Mutex<HashMap<String, OrderBook>>>,
+}
+
+impl OrderBooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn apply_snapshot(&self, data: &BookData) -> Result<(), BookError> {
+        let mut books = self.books.lock().unwrap();
+        books
+            .entry(data.symbol.clone())
+            .or_default()
+            .apply_snapshot(data)
+    }
+
+    pub fn apply_update(&self, data: &BookData) -> Result<(), BookError> {
+        let mut books = self.books.lock().unwrap();
+        books
+            .entry(data.symbol.clone())
+            .or_default()
+            .apply_update(data)
+    }
+
+    /// Drops the symbol's book until its next snapshot
+    pub fn reset(&self, symbol: &str) {
+        if let Some(book) = self.books.lock().unwrap().get_mut(symbol) {
+            book.reset();
+        }
+    }
+
+    /// The top of one symbol's book, if it is in sync
+    pub fn snapshot(&self, symbol: &str, depth: usize) -> Option<OrderBookSnapshot> {
+        let books = self.books.lock().unwrap();
+        books
+            .get(symbol)
+            .filter(|book| book.is_synced())
+            .map(|book| book.snapshot(symbol, depth))
+    }
+
+    /// The tops of all books in sync
+    pub fn snapshots(&self, depth: usize) -> Vec<OrderBookSnapshot> {
+        let books = self.books.lock().unwrap();
+        books
+            .iter()
+            .filter(|(_, book)| book.is_synced())
+            .map(|(symbol, book)| book.snapshot(symbol, depth))
+            .collect()
+    }
+}
+
+/*
+    Stores the top of every book that has changed since it was last stored, every `snapshot_interval`.
+    Runs until the task is dropped; a failed write is logged and retried with the next tick.
+*/
+pub async fn record_snapshots(
+    books: OrderBooks,
+    store: Arc<dyn OrderBookStore>,
+    config: OrderBookConfig,
+) {
+    let mut ticker = time::interval(config.snapshot_interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut stored: HashMap<String, i64> = HashMap::new(); // timestamp of the last stored snapshot, by pair
+
+    loop {
+        ticker.tick().await;
+        let snapshots: Vec<OrderBookSnapshot> = books
+            .snapshots(config.depth)
+            .into_iter()
+            .filter(|s| stored.get(&s.pair) != Some(&s.timestamp))
+            .collect();
+        if snapshots.is_empty() {
+            continue;
+        }
+        match store.save_order_book_snapshots(&snapshots).await {
+            Ok(_) => {
+                debug!("Stored {} order book snapshots", snapshots.len());
+                for snapshot in snapshots {
+                    stored.insert(snapshot.pair, snapshot.timestamp);
+                }
+            }
+            Err(e) => error!("Failed to store order book snapshots: {}", e),
+        }
+    }
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book_data(id: i64, last_id: i64, bids: &[(&str, &str)], asks: &[(&str, &str)]) -> BookData {
+        let levels = |levels: &[(&str, &str)]| {
+            levels
+                .iter()
+                .map(|(p, q)| (p.to_string(), q.to_string()))
+                .collect()
+        };
+        BookData {
+            symbol: "BTC_USDT".to_string(),
+            asks: levels(asks),
+            bids: levels(bids),
+            last_id,
+            id,
+            ts: id * 1_000,
+        }
+    }
+
+    #[test]
+    fn test_snapshot_updates_and_gap() {
+        let mut book = OrderBook::default();
+        assert_eq!(
+            book.apply_update(&book_data(1, 0, &[], &[])),
+            Err(BookError::NotSynced)
+        );
+
+        book.apply_snapshot(&book_data(
+            10,
+            9,
+            &[("100", "1"), ("99", "2"), ("98", "3")],
+            &[("101", "1"), ("102", "2")],
+        ))
+        .unwrap();
+        // Remove a bid, change an ask, add a better one
+        book.apply_update(&book_data(
+            11,
+            10,
+            &[("100", "0")],
+            &[("102", "5"), ("100.5", "0.1")],
+        ))
+        .unwrap();
+
+        let top = book.snapshot("BTC_USDT", 2);
+        let prices = |levels: &[PriceLevel]| levels.iter().map(|l| l.price).collect::<Vec<_>>();
+        assert_eq!(prices(&top.bids), vec![99.0, 98.0]);
+        assert_eq!(prices(&top.asks), vec![100.5, 101.0]);
+        assert_eq!(top.timestamp, 11_000);
+        assert_eq!(book.snapshot("BTC_USDT", 5).asks[2].quantity, 5.0);
+
+        // Message 12 was lost
+        assert_eq!(
+            book.apply_update(&book_data(13, 12, &[], &[])),
+            Err(BookError::Gap {
+                expected: 11,
+                received: 12
+            })
+        );
+        assert!(!book.is_synced());
+    }
+}
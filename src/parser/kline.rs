@@ -30,6 +30,61 @@ impl fmt::Display for Kline {
     }
 }
 
+impl Kline {
+    /*
+        The rules a kline has to pass to be stored, whatever its source (REST response, imported file):
+        known series and start time, positive finite prices with the low and high enclosing
+        the open and close, non-negative finite volumes.
+    */
+    pub fn validate(&self) -> Result<(), String> {
+        if self.pair.is_empty() || self.time_frame.is_empty() {
+            return Err("pair or time frame is empty".to_string());
+        }
+        if self.utc_begin <= 0 {
+            return Err(format!("invalid start time {}", self.utc_begin));
+        }
+        if [self.o, self.h, self.l, self.c]
+            .iter()
+            .any(|p| !p.is_finite() || *p <= 0.0)
+        {
+            return Err(format!("invalid prices in {}", self));
+        }
+        if self.l > self.o.min(self.c) || self.h < self.o.max(self.c) {
+            return Err(format!("low/high do not enclose open/close in {}", self));
+        }
+        let v = &self.volume_bs;
+        if [v.buy_base, v.sell_base, v.buy_quote, v.sell_quote]
+            .iter()
+            .any(|x| !x.is_finite() || *x < 0.0)
+        {
+            return Err(format!("invalid volumes in {}", self));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Kline {
+    /// A valid kline for tests: open 1, high 2, low 0.5, close 1.5, volumes 1, 2, 3 and 4
+    pub(crate) fn sample(pair: &str, time_frame: &str, utc_begin: i64) -> Kline {
+        Kline {
+            pair: pair.to_string(),
+            time_frame: time_frame.to_string(),
+            o: 1.0,
+            h: 2.0,
+            l: 0.5,
+            c: 1.5,
+            utc_begin,
+            volume_bs: VBS {
+                buy_base: 1.0,
+                sell_base: 2.0,
+                buy_quote: 3.0,
+                sell_quote: 4.0,
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct VBS {
     pub buy_base: f64,   // Объём покупок в базовой валюте - buyTakerQuantity
@@ -1,25 +1,74 @@
 pub mod kline;
+pub mod order_book;
 pub mod recent_trade;
+pub mod symbol;
+pub mod ticker;
 use std::collections::HashMap;
 
 use kline::{Kline, VBS};
 use serde_json::Value;
 
+use crate::metrics::metrics;
+
+/// (pair, timeframe) - the key by which kline series are grouped
+pub type KlineKey = (String, String);
+/// Klines grouped by (pair, timeframe)
+pub type GroupedKlines = HashMap<KlineKey, Vec<Kline>>;
+
+/// Length of a Poloniex time frame in milliseconds (a month is taken as 30 days)
+pub fn time_frame_millis(time_frame: &str) -> Option<i64> {
+    const MINUTE: i64 = 60_000;
+    Some(match time_frame {
+        "MINUTE_1" => MINUTE,
+        "MINUTE_5" => 5 * MINUTE,
+        "MINUTE_10" => 10 * MINUTE,
+        "MINUTE_15" => 15 * MINUTE,
+        "MINUTE_30" => 30 * MINUTE,
+        "HOUR_1" => 60 * MINUTE,
+        "HOUR_2" => 120 * MINUTE,
+        "HOUR_4" => 240 * MINUTE,
+        "HOUR_6" => 360 * MINUTE,
+        "HOUR_12" => 720 * MINUTE,
+        "DAY_1" => 1_440 * MINUTE,
+        "DAY_3" => 3 * 1_440 * MINUTE,
+        "WEEK_1" => 7 * 1_440 * MINUTE,
+        "MONTH_1" => 30 * 1_440 * MINUTE,
+        _ => return None,
+    })
+}
+
+/// The UTC (year, month, day) of a timestamp in ms, after Howard Hinnant's `civil_from_days`
+pub fn utc_date(timestamp: i64) -> (i64, u32, u32) {
+    let days = timestamp.div_euclid(86_400_000) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
 pub struct KlineParser;
 
+impl Default for KlineParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl KlineParser {
     pub fn new() -> Self {
         KlineParser
     }
 
-    pub fn parse(
-        &self,
-        response: &str,
-        pair: &str,
-    ) -> Result<HashMap<(String, String), Vec<Kline>>, String> {
+    pub fn parse(&self, response: &str, pair: &str) -> Result<GroupedKlines, String> {
         match serde_json::from_str::<Vec<Vec<Value>>>(response) {
             Ok(parsed) => {
-                let grouped_klines = parsed
+                let received = parsed.len();
+                let klines: Vec<Kline> = parsed
                     .into_iter()
                     .flat_map(|item| {
                         if item.len() != 14 {
@@ -29,7 +78,7 @@ impl KlineParser {
                         // Creating VBS from data
                         let vbs = VBS::from_data(&item)?;
 
-                        Some(Kline {
+                        let kline = Kline {
                             pair: pair.to_string(),
                             time_frame: item[11]
                                 .as_str()
@@ -53,16 +102,28 @@ impl KlineParser {
                                 .unwrap_or_default(),
                             utc_begin: item[12].as_i64().unwrap_or(0),
                             volume_bs: vbs,
-                        })
+                        };
+                        // Skip klines that don't pass validation
+                        kline.validate().ok().map(|_| kline)
                     })
-                    .fold(
-                        HashMap::new(),
-                        |mut acc: HashMap<(String, String), Vec<Kline>>, kline| {
+                    .collect();
+
+                let rejected = received - klines.len();
+                if rejected > 0 {
+                    metrics()
+                        .parse_rejects
+                        .with_label_values(&[pair])
+                        .inc_by(rejected as u64);
+                }
+
+                let grouped_klines =
+                    klines
+                        .into_iter()
+                        .fold(HashMap::new(), |mut acc: GroupedKlines, kline| {
                             let key = (kline.pair.clone(), kline.time_frame.clone());
-                            acc.entry(key).or_insert_with(Vec::new).push(kline);
+                            acc.entry(key).or_default().push(kline);
                             acc
-                        },
-                    );
+                        });
 
                 Ok(grouped_klines)
             }
@@ -0,0 +1,15 @@
+/// One price level of an order book side
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceLevel {
+    pub price: f64,
+    pub quantity: f64,
+}
+
+/// The best levels of an order book at one moment
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderBookSnapshot {
+    pub pair: String,
+    pub timestamp: i64, // exchange time of the last applied message, unix millis
+    pub bids: Vec<PriceLevel>, // best (highest) first
+    pub asks: Vec<PriceLevel>, // best (lowest) first
+}
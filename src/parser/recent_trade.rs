@@ -1,8 +1,41 @@
+use serde::Deserialize;
+
+#[derive(Debug, Clone)]
 pub struct RecentTrade {
     pub tid: String,    // ID транзакции
     pub pair: String,   // Название валютной пары
     pub price: String,  // Цена транзакции
     pub amount: String, // Объём в базовой валюте
-    pub side: String,   // Покупка или продажа
-    pub timestamp: i64, // Время UTC UnixNano
+    pub side: String,   // Сторона тейкера: buy или sell
+    pub timestamp: i64, // Время UTC в миллисекундах
+}
+
+/*
+    A trade as sent by the `trades` WebSocket channel. `quantity` is the base volume and
+    `amount` the quote volume; the taker side decides whether it counts as a buy.
+*/
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TradeData {
+    pub symbol: String,
+    pub id: String,
+    pub price: String,
+    pub quantity: String,
+    pub amount: String,
+    pub taker_side: String,
+    pub create_time: i64,
+    pub ts: i64,
+}
+
+impl From<TradeData> for RecentTrade {
+    fn from(data: TradeData) -> Self {
+        RecentTrade {
+            tid: data.id,
+            pair: data.symbol,
+            price: data.price,
+            amount: data.quantity,
+            side: data.taker_side,
+            timestamp: data.create_time,
+        }
+    }
 }
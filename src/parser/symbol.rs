@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+/// What the exchange says about one market
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolInfo {
+    pub exchange: String,        // lowercase exchange name, e.g. `poloniex`
+    pub pair: String,            // BASE_QUOTE, as pairs are named everywhere else
+    pub exchange_symbol: String, // the exchange's own name, e.g. BTCUSDT on Binance
+    pub base: String,
+    pub quote: String,
+    pub status: String,          // as reported, e.g. NORMAL or TRADING
+    pub trading: bool,           // whether the market is open for trading now
+    pub price_precision: i64,    // decimal places of prices
+    pub quantity_precision: i64, // decimal places of quantities
+    pub min_quantity: f64,       // smallest order, base currency
+    pub min_amount: f64,         // smallest order, quote currency
+}
+
+fn number(value: &str) -> Result<f64, String> {
+    value
+        .parse::<f64>()
+        .map_err(|_| format!("invalid number '{}'", value))
+}
+
+/// Decimal places of a step such as `0.00010000` (4)
+fn decimals(step: &str) -> i64 {
+    match step.split_once('.') {
+        Some((_, fraction)) => fraction.trim_end_matches('0').len() as i64,
+        None => 0,
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PoloniexMarket {
+    symbol: String,
+    base_currency_name: String,
+    quote_currency_name: String,
+    state: String,
+    symbol_trade_limit: PoloniexTradeLimit,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PoloniexTradeLimit {
+    price_scale: i64,
+    quantity_scale: i64,
+    min_quantity: String,
+    min_amount: String,
+}
+
+/// Parses the Poloniex `/markets` response
+pub fn parse_poloniex_markets(response: &str) -> Result<Vec<SymbolInfo>, String> {
+    let markets: Vec<PoloniexMarket> = serde_json::from_str(response)
+        .map_err(|e| format!("Failed to parse markets response: {}", e))?;
+    markets
+        .into_iter()
+        .map(|market| {
+            Ok(SymbolInfo {
+                exchange: "poloniex".to_string(),
+                pair: market.symbol.clone(),
+                trading: market.state == "NORMAL",
+                price_precision: market.symbol_trade_limit.price_scale,
+                quantity_precision: market.symbol_trade_limit.quantity_scale,
+                min_quantity: number(&market.symbol_trade_limit.min_quantity)?,
+                min_amount: number(&market.symbol_trade_limit.min_amount)?,
+                exchange_symbol: market.symbol,
+                base: market.base_currency_name,
+                quote: market.quote_currency_name,
+                status: market.state,
+            })
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct BinanceExchangeInfo {
+    symbols: Vec<BinanceSymbol>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinanceSymbol {
+    symbol: String,
+    status: String,
+    base_asset: String,
+    quote_asset: String,
+    filters: Vec<HashMap<String, serde_json::Value>>,
+}
+
+impl BinanceSymbol {
+    fn filter(&self, filter_type: &str, field: &str) -> Option<&str> {
+        self.filters
+            .iter()
+            .find(|f| f.get("filterType").and_then(|t| t.as_str()) == Some(filter_type))
+            .and_then(|f| f.get(field))
+            .and_then(|v| v.as_str())
+    }
+}
+
+/*
+    Parses the Binance `exchangeInfo` response. Precisions come from the PRICE_FILTER tick size
+    and the LOT_SIZE step size, the minimum amount from the NOTIONAL (or older MIN_NOTIONAL) filter.
+*/
+pub fn parse_binance_exchange_info(response: &str) -> Result<Vec<SymbolInfo>, String> {
+    let info: BinanceExchangeInfo = serde_json::from_str(response)
+        .map_err(|e| format!("Failed to parse exchangeInfo response: {}", e))?;
+    info.symbols
+        .into_iter()
+        .map(|symbol| {
+            let tick_size = symbol.filter("PRICE_FILTER", "tickSize").unwrap_or("1");
+            let step_size = symbol.filter("LOT_SIZE", "stepSize").unwrap_or("1");
+            let min_quantity = symbol.filter("LOT_SIZE", "minQty").unwrap_or("0");
+            let min_amount = symbol
+                .filter("NOTIONAL", "minNotional")
+                .or_else(|| symbol.filter("MIN_NOTIONAL", "minNotional"))
+                .unwrap_or("0");
+            Ok(SymbolInfo {
+                exchange: "binance".to_string(),
+                pair: format!("{}_{}", symbol.base_asset, symbol.quote_asset),
+                trading: symbol.status == "TRADING",
+                price_precision: decimals(tick_size),
+                quantity_precision: decimals(step_size),
+                min_quantity: number(min_quantity)?,
+                min_amount: number(min_amount)?,
+                exchange_symbol: symbol.symbol,
+                base: symbol.base_asset,
+                quote: symbol.quote_asset,
+                status: symbol.status,
+            })
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinanceTicker {
+    symbol: String,
+    quote_volume: String,
+}
+
+/// 24h quote volumes by exchange symbol from the Binance `ticker/24hr` response
+pub fn parse_binance_volumes(response: &str) -> Result<HashMap<String, f64>, String> {
+    let tickers: Vec<BinanceTicker> = serde_json::from_str(response)
+        .map_err(|e| format!("Failed to parse 24hr ticker response: {}", e))?;
+    tickers
+        .into_iter()
+        .map(|t| Ok((t.symbol, number(&t.quote_volume)?)))
+        .collect()
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_markets() {
+        let poloniex = r#"[{"symbol":"BTC_USDT","baseCurrencyName":"BTC","quoteCurrencyName":"USDT",
+            "displayName":"BTC/USDT","state":"NORMAL","visibleStartTime":1659018819512,
+            "tradableStartTime":1659018819512,"symbolTradeLimit":{"symbol":"BTC_USDT","priceScale":2,
+            "quantityScale":6,"amountScale":2,"minQuantity":"0.000001","minAmount":"1",
+            "highestBid":"0","lowestAsk":"0"},"crossMargin":{"supportCrossMargin":true,"maxLeverage":3}}]"#;
+        let symbols = parse_poloniex_markets(poloniex).unwrap();
+        assert_eq!(symbols[0].pair, "BTC_USDT");
+        assert!(symbols[0].trading);
+        assert_eq!(symbols[0].quantity_precision, 6);
+        assert_eq!(symbols[0].min_amount, 1.0);
+
+        let binance = r#"{"timezone":"UTC","serverTime":1737709931000,"symbols":[{"symbol":"ETHBTC",
+            "status":"BREAK","baseAsset":"ETH","baseAssetPrecision":8,"quoteAsset":"BTC",
+            "quotePrecision":8,"filters":[
+                {"filterType":"PRICE_FILTER","minPrice":"0.00001000","maxPrice":"922327.00000000","tickSize":"0.00001000"},
+                {"filterType":"LOT_SIZE","minQty":"0.00010000","maxQty":"100000.00000000","stepSize":"0.00010000"},
+                {"filterType":"NOTIONAL","minNotional":"0.00010000","applyMinToMarket":true}]}]}"#;
+        let symbols = parse_binance_exchange_info(binance).unwrap();
+        assert_eq!(symbols[0].pair, "ETH_BTC");
+        assert_eq!(symbols[0].exchange_symbol, "ETHBTC");
+        assert!(!symbols[0].trading);
+        assert_eq!(symbols[0].price_precision, 5);
+        assert_eq!(symbols[0].quantity_precision, 4);
+        assert_eq!(symbols[0].min_quantity, 0.0001);
+    }
+}
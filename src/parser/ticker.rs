@@ -0,0 +1,109 @@
+use serde::Deserialize;
+
+/// 24h statistics of a pair at one moment
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ticker {
+    pub pair: String,
+    pub timestamp: i64,    // exchange time, unix millis
+    pub open: f64,         // price 24h ago
+    pub high: f64,         // 24h high
+    pub low: f64,          // 24h low
+    pub close: f64,        // last price
+    pub quantity: f64,     // 24h volume in the base currency
+    pub amount: f64,       // 24h volume in the quote currency
+    pub trade_count: i64,  // 24h trades
+    pub daily_change: f64, // (close - open) / open
+}
+
+/*
+    A ticker as sent by `markets/{symbol}/ticker24h` and the `ticker` WebSocket channel;
+    prices and volumes come as strings. Fields only one of them has (bid, ask, markPrice, ...) are ignored.
+*/
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TickerData {
+    pub symbol: String,
+    pub open: String,
+    pub high: String,
+    pub low: String,
+    pub close: String,
+    pub quantity: String,
+    pub amount: String,
+    pub trade_count: i64,
+    pub daily_change: String,
+    pub ts: i64,
+}
+
+impl TryFrom<TickerData> for Ticker {
+    type Error = String;
+
+    fn try_from(data: TickerData) -> Result<Self, Self::Error> {
+        let number = |name: &str, value: &str| {
+            value
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .ok_or_else(|| {
+                    format!("invalid {} '{}' in the {} ticker", name, value, data.symbol)
+                })
+        };
+        Ok(Ticker {
+            timestamp: data.ts,
+            open: number("open", &data.open)?,
+            high: number("high", &data.high)?,
+            low: number("low", &data.low)?,
+            close: number("close", &data.close)?,
+            quantity: number("quantity", &data.quantity)?,
+            amount: number("amount", &data.amount)?,
+            trade_count: data.trade_count,
+            daily_change: number("dailyChange", &data.daily_change)?,
+            pair: data.symbol,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TickerResponse {
+    One(TickerData),
+    Many(Vec<TickerData>), // `markets/ticker24h`, every symbol at once
+}
+
+/// Parses a REST ticker response, for one symbol or for all of them
+pub fn parse_tickers(response: &str) -> Result<Vec<Ticker>, String> {
+    let data = match serde_json::from_str(response) {
+        Ok(TickerResponse::One(data)) => vec![data],
+        Ok(TickerResponse::Many(data)) => data,
+        Err(e) => return Err(format!("Failed to parse ticker response: {}", e)),
+    };
+    data.into_iter().map(Ticker::try_from).collect()
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tickers() {
+        let response = r#"{"symbol":"BTC_USDT","open":"30000","low":"29500","high":"30600",
+            "close":"30300","quantity":"120.5","amount":"3630000","tradeCount":4210,
+            "startTime":1737623520000,"closeTime":1737709931000,"displayName":"BTC/USDT",
+            "dailyChange":"0.01","bid":"30299","bidQuantity":"0.2","ask":"30301",
+            "askQuantity":"0.1","ts":1737709931000,"markPrice":"30300.2"}"#;
+        let tickers = parse_tickers(response).unwrap();
+        assert_eq!(tickers.len(), 1);
+        assert_eq!(tickers[0].pair, "BTC_USDT");
+        assert_eq!(tickers[0].close, 30300.0);
+        assert_eq!(tickers[0].trade_count, 4210);
+        assert_eq!(tickers[0].daily_change, 0.01);
+
+        let many = format!(
+            "[{}]",
+            response.replace("\"close\":\"30300\"", "\"close\":\"NaN\"")
+        );
+        assert!(parse_tickers(&many).is_err());
+    }
+}
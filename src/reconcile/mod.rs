@@ -0,0 +1,349 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::database::KlineStore;
+use crate::error::Error;
+use crate::http_client::http_client::RestClient;
+use crate::http_client::HttpClientError;
+use crate::metrics::unix_millis;
+use crate::parser::{kline::Kline, time_frame_millis, KlineParser};
+
+/// Most candles the Poloniex candles endpoint returns per request
+const PAGE_LIMIT: usize = 500;
+
+/// The series and range to reconcile; `tolerance` is relative, e.g. 0.001 for 0.1%
+#[derive(Debug, Clone)]
+pub struct ReconcileOptions {
+    pub pair: String,
+    pub time_frame: String,
+    pub from: i64,
+    pub to: i64,
+    pub tolerance: f64,
+    pub replace: bool, // upsert the exchange version of every missing or mismatching candle
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Discrepancy {
+    /// The exchange has a candle the local series lacks
+    Missing { utc_begin: i64 },
+    /// A local candle the exchange doesn't have
+    Unexpected { utc_begin: i64 },
+    Mismatch {
+        utc_begin: i64,
+        field: &'static str,
+        local: f64,
+        exchange: f64,
+    },
+}
+
+impl Discrepancy {
+    pub fn utc_begin(&self) -> i64 {
+        match self {
+            Discrepancy::Missing { utc_begin }
+            | Discrepancy::Unexpected { utc_begin }
+            | Discrepancy::Mismatch { utc_begin, .. } => *utc_begin,
+        }
+    }
+}
+
+/// Outcome of a reconciliation; counts are of closed candles
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconcileReport {
+    pub pair: String,
+    pub time_frame: String,
+    pub from: i64,
+    pub to: i64,
+    pub local: usize,
+    pub exchange: usize,
+    pub matched: usize,
+    pub discrepancies: Vec<Discrepancy>,
+    pub replaced: usize,
+}
+
+fn fields(kline: &Kline) -> [(&'static str, f64); 8] {
+    let v = &kline.volume_bs;
+    [
+        ("open", kline.o),
+        ("high", kline.h),
+        ("low", kline.l),
+        ("close", kline.c),
+        ("buy_base", v.buy_base),
+        ("sell_base", v.sell_base),
+        ("buy_quote", v.buy_quote),
+        ("sell_quote", v.sell_quote),
+    ]
+}
+
+fn differs(local: f64, exchange: f64, tolerance: f64) -> bool {
+    (local - exchange).abs() > tolerance * local.abs().max(exchange.abs()) + f64::EPSILON
+}
+
+/// Compares two series candle by candle; candles are matched by `utc_begin`
+pub fn compare(local: &[Kline], exchange: &[Kline], tolerance: f64) -> (usize, Vec<Discrepancy>) {
+    let local: BTreeMap<i64, &Kline> = local.iter().map(|k| (k.utc_begin, k)).collect();
+    let exchange: BTreeMap<i64, &Kline> = exchange.iter().map(|k| (k.utc_begin, k)).collect();
+    let mut matched = 0;
+    let mut discrepancies = Vec::new();
+
+    for (utc_begin, remote) in &exchange {
+        let Some(kline) = local.get(utc_begin) else {
+            discrepancies.push(Discrepancy::Missing {
+                utc_begin: *utc_begin,
+            });
+            continue;
+        };
+        let before = discrepancies.len();
+        for ((field, value), (_, remote_value)) in fields(kline).into_iter().zip(fields(remote)) {
+            if differs(value, remote_value, tolerance) {
+                discrepancies.push(Discrepancy::Mismatch {
+                    utc_begin: *utc_begin,
+                    field,
+                    local: value,
+                    exchange: remote_value,
+                });
+            }
+        }
+        if discrepancies.len() == before {
+            matched += 1;
+        }
+    }
+    for utc_begin in local.keys().filter(|t| !exchange.contains_key(t)) {
+        discrepancies.push(Discrepancy::Unexpected {
+            utc_begin: *utc_begin,
+        });
+    }
+    discrepancies.sort_by_key(Discrepancy::utc_begin);
+    (matched, discrepancies)
+}
+
+/// Fetches the candles of a range from the Poloniex candles endpoint, page by page
+pub async fn fetch_exchange_klines(
+    client: &dyn RestClient,
+    base_url: &str,
+    pair: &str,
+    time_frame: &str,
+    from: i64,
+    to: i64,
+) -> Result<Vec<Kline>, Error> {
+    let step = time_frame_millis(time_frame)
+        .ok_or_else(|| HttpClientError::new(&format!("unknown time frame {}", time_frame)))?;
+    let parser = KlineParser::new();
+    let mut klines: Vec<Kline> = Vec::new();
+    let mut start = from;
+    while start <= to {
+        let url = format!(
+            "{}/markets/{}/candles?interval={}&startTime={}&endTime={}&limit={}",
+            base_url.trim_end_matches('/'),
+            pair,
+            time_frame,
+            start,
+            to,
+            PAGE_LIMIT
+        );
+        let response = client.get(&url).await.map_err(|e| {
+            HttpClientError::new(&format!("Failed to fetch data from {}: {}", url, e))
+        })?;
+        let mut page: Vec<Kline> = parser
+            .parse(&response, pair)
+            .map_err(|e| HttpClientError::new(&format!("Failed to parse {}: {}", url, e)))?
+            .into_values()
+            .flatten()
+            .filter(|k| k.time_frame == time_frame && (start..=to).contains(&k.utc_begin))
+            .collect();
+        page.sort_by_key(|k| k.utc_begin);
+        let Some(last) = page.last().map(|k| k.utc_begin) else {
+            break;
+        };
+        let full = page.len() >= PAGE_LIMIT;
+        klines.extend(page);
+        if !full {
+            break;
+        }
+        start = last + step;
+    }
+    Ok(klines)
+}
+
+/*
+    Compares local candles of one series (stored, or rebuilt from trades) with the exchange's,
+    over the closed candles of the range: the still open one would differ anyway. With `replace`
+    the exchange version of every missing or mismatching candle is upserted into the store;
+    unexpected local candles are only reported.
+*/
+pub async fn reconcile(
+    client: &dyn RestClient,
+    base_url: &str,
+    store: &dyn KlineStore,
+    local: Vec<Kline>,
+    options: &ReconcileOptions,
+) -> Result<ReconcileReport, Error> {
+    let step = time_frame_millis(&options.time_frame).unwrap_or_default();
+    let closed_before = unix_millis();
+    let in_range = |k: &Kline| {
+        k.pair == options.pair
+            && k.time_frame == options.time_frame
+            && (options.from..=options.to).contains(&k.utc_begin)
+            && k.utc_begin + step <= closed_before
+    };
+    let local: Vec<Kline> = local.into_iter().filter(in_range).collect();
+    let exchange: Vec<Kline> = fetch_exchange_klines(
+        client,
+        base_url,
+        &options.pair,
+        &options.time_frame,
+        options.from,
+        options.to,
+    )
+    .await?
+    .into_iter()
+    .filter(in_range)
+    .collect();
+
+    let (matched, discrepancies) = compare(&local, &exchange, options.tolerance);
+    let mut replaced = 0;
+    if options.replace {
+        let replacements: Vec<Kline> = exchange
+            .iter()
+            .filter(|k| {
+                discrepancies.iter().any(|d| {
+                    d.utc_begin() == k.utc_begin && !matches!(d, Discrepancy::Unexpected { .. })
+                })
+            })
+            .cloned()
+            .collect();
+        store.upsert_klines(&replacements).await?;
+        replaced = replacements.len();
+    }
+
+    if discrepancies.is_empty() {
+        info!(
+            "{} {}: {} candles match the exchange",
+            options.pair, options.time_frame, matched
+        );
+    } else {
+        warn!(
+            "{} {}: {} of {} exchange candles match, {} discrepancies, {} replaced",
+            options.pair,
+            options.time_frame,
+            matched,
+            exchange.len(),
+            discrepancies.len(),
+            replaced
+        );
+    }
+    Ok(ReconcileReport {
+        pair: options.pair.clone(),
+        time_frame: options.time_frame.clone(),
+        from: options.from,
+        to: options.to,
+        local: local.len(),
+        exchange: exchange.len(),
+        matched,
+        discrepancies,
+        replaced,
+    })
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{KlineFilter, MemoryStore};
+    use crate::http_client::http_client::RestResponse;
+
+    /// Answers every request with the same body
+    struct ScriptedRest(String);
+
+    impl RestClient for ScriptedRest {
+        fn get<'a>(&'a self, _url: &'a str) -> RestResponse<'a> {
+            Box::pin(async move { Ok(self.0.clone()) })
+        }
+    }
+
+    /// A Poloniex candle: low, high, open, close, amount, quantity, buyTakerAmount, buyTakerQuantity, ...
+    fn candle(utc_begin: i64, close: &str) -> serde_json::Value {
+        serde_json::json!([
+            "99",
+            "110",
+            "100",
+            close,
+            "1000",
+            "10",
+            "400",
+            "4",
+            7,
+            utc_begin + 59_999,
+            "100",
+            "MINUTE_1",
+            utc_begin,
+            utc_begin + 59_999
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_replaces_discrepancies() {
+        let response = serde_json::json!([
+            candle(60_000, "105"),
+            candle(120_000, "105"),
+            candle(180_000, "105"),
+        ]);
+        let client = ScriptedRest(response.to_string());
+        let parsed = KlineParser::new()
+            .parse(&response.to_string(), "BTC_USDT")
+            .unwrap();
+        let mut exchange: Vec<Kline> = parsed.into_values().flatten().collect();
+        exchange.sort_by_key(|k| k.utc_begin);
+
+        // Local: the first candle matches, the second has another close, the third is missing
+        // and one more isn't at the exchange
+        let mut local = vec![exchange[0].clone(), exchange[1].clone()];
+        local[1].c = 106.0;
+        let mut extra = exchange[0].clone();
+        extra.utc_begin = 240_000;
+        local.push(extra);
+
+        let store = MemoryStore::new();
+        store.save_klines(&local).await.unwrap();
+        let options = ReconcileOptions {
+            pair: "BTC_USDT".to_string(),
+            time_frame: "MINUTE_1".to_string(),
+            from: 0,
+            to: 300_000,
+            tolerance: 0.001,
+            replace: true,
+        };
+        let report = reconcile(&client, "http://rest", &store, local, &options)
+            .await
+            .unwrap();
+
+        assert_eq!((report.local, report.exchange, report.matched), (3, 3, 1));
+        assert_eq!(
+            report.discrepancies,
+            vec![
+                Discrepancy::Mismatch {
+                    utc_begin: 120_000,
+                    field: "close",
+                    local: 106.0,
+                    exchange: 105.0
+                },
+                Discrepancy::Missing { utc_begin: 180_000 },
+                Discrepancy::Unexpected { utc_begin: 240_000 },
+            ]
+        );
+        assert_eq!(report.replaced, 2);
+        let stored = store.load_klines(&KlineFilter::default()).await.unwrap();
+        assert_eq!(stored.len(), 4);
+        assert!(stored.iter().all(|k| k.c == 105.0));
+
+        // A difference within the tolerance isn't reported
+        let mut near = exchange.clone();
+        near[1].c = 105.5;
+        assert_eq!(compare(&near, &exchange, 0.01), (3, vec![]));
+        assert_eq!(compare(&near, &exchange, 0.001).1.len(), 1);
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::TryStreamExt;
+use tokio::time::{self, Instant};
+use tracing::{info, warn};
+
+use crate::aggregator::{CandleAggregator, TradeCandles};
+use crate::capture::{CaptureRecord, CaptureSource};
+use crate::database::{
+    KlineFilter, KlineStore, KlineWriteBuffer, MemoryStore, TradeFilter, TradeStore,
+    WriteBufferConfig,
+};
+use crate::error::Error;
+use crate::parser::kline::Kline;
+use crate::parser::recent_trade::RecentTrade;
+use crate::websocket_client::message::WebSocketMessage;
+
+/// How fast recorded trades are played back, by their timestamps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayPace {
+    /// `1.0` is real time, `10.0` ten times faster
+    Speed(f64),
+    /// No waiting at all
+    Max,
+}
+
+impl FromStr for ReplayPace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "max" => Ok(ReplayPace::Max),
+            "realtime" => Ok(ReplayPace::Speed(1.0)),
+            other => other
+                .strip_suffix('x')
+                .unwrap_or(other)
+                .parse::<f64>()
+                .ok()
+                .filter(|speed| speed.is_finite() && *speed > 0.0)
+                .map(ReplayPace::Speed)
+                .ok_or_else(|| {
+                    format!(
+                        "unknown pace '{}', expected realtime, max or a speed such as 10x",
+                        s
+                    )
+                }),
+        }
+    }
+}
+
+impl fmt::Display for ReplayPace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayPace::Speed(speed) => write!(f, "{}x", speed),
+            ReplayPace::Max => write!(f, "max"),
+        }
+    }
+}
+
+/// Waits until a recorded timestamp is due, measured from the first one
+struct Pacer {
+    pace: ReplayPace,
+    start: Option<(Instant, i64)>,
+}
+
+impl Pacer {
+    async fn wait(&mut self, timestamp: i64) {
+        let ReplayPace::Speed(speed) = self.pace else {
+            return;
+        };
+        match self.start {
+            None => self.start = Some((Instant::now(), timestamp)),
+            Some((started, first)) => {
+                let offset = (timestamp - first).max(0) as f64 / 1_000.0 / speed;
+                time::sleep_until(started + Duration::from_secs_f64(offset)).await;
+            }
+        }
+    }
+}
+
+/// What a replay went through
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayReport {
+    pub trades: u64,
+    pub batches: u64,    // calls of the aggregator
+    pub unreadable: u64, // capture lines that were not a message
+}
+
+/*
+    Plays recorded trades back into an aggregator through `trades_process`, the path live trades
+    take with `TRADES_WS`, so candles can be rebuilt deterministically. The aggregator needs `with_trade_candles` and its
+    handlers built; what it writes is flushed before a replay returns. Trades of one timestamp
+    (stored rows) or of one message (captures) go in together.
+*/
+pub struct Replay {
+    aggregator: Arc<CandleAggregator>,
+    pace: ReplayPace,
+}
+
+impl Replay {
+    pub fn new(aggregator: Arc<CandleAggregator>, pace: ReplayPace) -> Self {
+        Replay { aggregator, pace }
+    }
+
+    /// A replay into a new in-memory store, for candles rebuilt next to (not over) the stored ones
+    pub async fn in_memory(candles: TradeCandles, pace: ReplayPace) -> (Self, Arc<MemoryStore>) {
+        let store = Arc::new(MemoryStore::new());
+        let aggregator = CandleAggregator::new(KlineWriteBuffer::spawn(
+            store.clone(),
+            WriteBufferConfig::default(),
+        ))
+        .with_trade_candles(candles);
+        aggregator.build_handlers(&[]).await;
+        (Replay::new(Arc::new(aggregator), pace), store)
+    }
+
+    fn pacer(&self) -> Pacer {
+        Pacer {
+            pace: self.pace,
+            start: None,
+        }
+    }
+
+    async fn play(&self, pacer: &mut Pacer, trades: &[RecentTrade], report: &mut ReplayReport) {
+        let Some(timestamp) = trades.iter().map(|t| t.timestamp).min() else {
+            return;
+        };
+        pacer.wait(timestamp).await;
+        self.aggregator.trades_process(trades).await;
+        report.trades += trades.len() as u64;
+        report.batches += 1;
+    }
+
+    /// Replays the stored `recent_trades` rows matching the filter, in timestamp order
+    pub async fn stored_trades(
+        &self,
+        store: &dyn TradeStore,
+        filter: &TradeFilter,
+    ) -> Result<ReplayReport, Error> {
+        let mut report = ReplayReport::default();
+        let mut pacer = self.pacer();
+        let mut batch: Vec<RecentTrade> = Vec::new();
+        let mut trades = store.stream_trades(filter);
+        while let Some(trade) = trades.try_next().await? {
+            if batch.last().is_some_and(|t| t.timestamp != trade.timestamp) {
+                self.play(&mut pacer, &batch, &mut report).await;
+                batch.clear();
+            }
+            batch.push(trade);
+        }
+        self.play(&mut pacer, &batch, &mut report).await;
+        self.finish(report).await
+    }
+
+    /*
+        Replays a JSON Lines capture of raw WebSocket messages, one message per line as received,
+        or of `CaptureRecord`s as archived by `RawCapture` (their REST records are skipped).
+        Messages of the `trades` channel are played (only the trades matching the filter), every
+        other message is skipped.
+    */
+    pub async fn capture<R: BufRead>(
+        &self,
+        reader: R,
+        filter: &TradeFilter,
+    ) -> Result<ReplayReport, Error> {
+        let mut report = ReplayReport::default();
+        let mut pacer = self.pacer();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let message = match CaptureRecord::parse(&line) {
+                Some(record) if record.source == CaptureSource::Ws => record.data,
+                Some(_) => continue,
+                None => line,
+            };
+            let data = match WebSocketMessage::parse(&message) {
+                Ok(WebSocketMessage::Trades(data)) => data,
+                Ok(_) => continue,
+                Err(err) => {
+                    warn!("Capture line {}: {}", index + 1, err);
+                    report.unreadable += 1;
+                    continue;
+                }
+            };
+            let trades: Vec<RecentTrade> = data
+                .into_iter()
+                .map(RecentTrade::from)
+                .filter(|trade| filter.matches(trade))
+                .collect();
+            self.play(&mut pacer, &trades, &mut report).await;
+        }
+        self.finish(report).await
+    }
+
+    async fn finish(&self, report: ReplayReport) -> Result<ReplayReport, Error> {
+        self.aggregator.flush().await?;
+        info!(
+            "Replayed {} trades in {} batches ({} unreadable lines)",
+            report.trades, report.batches, report.unreadable
+        );
+        Ok(report)
+    }
+}
+
+/*
+    The aggregator saves every state of an open candle as it changes (`save_klines` appends),
+    so a replay leaves several rows per candle. Keeps the last of each, in the order given,
+    e.g. that of `load_klines`.
+*/
+pub fn final_states(klines: Vec<Kline>) -> Vec<Kline> {
+    let mut index: HashMap<(String, String, i64), usize> = HashMap::new();
+    let mut finals: Vec<Kline> = Vec::new();
+    for kline in klines {
+        let key = (
+            kline.pair.clone(),
+            kline.time_frame.clone(),
+            kline.utc_begin,
+        );
+        match index.entry(key) {
+            Entry::Occupied(entry) => finals[*entry.get()] = kline,
+            Entry::Vacant(entry) => {
+                entry.insert(finals.len());
+                finals.push(kline);
+            }
+        }
+    }
+    finals
+}
+
+/// The final states of the candles an `in_memory` replay has built
+pub async fn rebuilt_klines(store: &MemoryStore) -> Result<Vec<Kline>, sqlx::Error> {
+    Ok(final_states(
+        store.load_klines(&KlineFilter::default()).await?,
+    ))
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_replay_capture() {
+        assert_eq!("10x".parse::<ReplayPace>(), Ok(ReplayPace::Speed(10.0)));
+        assert_eq!("realtime".parse::<ReplayPace>(), Ok(ReplayPace::Speed(1.0)));
+        assert!("0x".parse::<ReplayPace>().is_err());
+
+        let candles = TradeCandles::new(&["MINUTE_1".to_string()]).unwrap();
+        let (replay, store) = Replay::in_memory(candles, ReplayPace::Max).await;
+
+        let trade = |id: &str, symbol: &str, price: &str, side: &str, time: i64| {
+            format!(
+                r#"{{"channel":"trades","data":[{{"symbol":"{}","amount":"0","quantity":"1","takerSide":"{}","createTime":{},"price":"{}","id":"{}","ts":{}}}]}}"#,
+                symbol, side, time, price, id, time
+            )
+        };
+        let capture = [
+            r#"{"event":"subscribe","channel":"trades","symbols":["BTC_USDT"]}"#.to_string(),
+            trade("1", "BTC_USDT", "100", "buy", 60_000),
+            trade("2", "ETH_USDT", "5", "buy", 61_000), // filtered out
+            "not json".to_string(),
+            trade("3", "BTC_USDT", "90", "sell", 119_999),
+            serde_json::to_string(&CaptureRecord {
+                ts: 120_100,
+                source: CaptureSource::Ws,
+                url: "wss://ws".to_string(),
+                data: trade("4", "BTC_USDT", "95", "buy", 120_000),
+            })
+            .unwrap(),
+        ]
+        .join("\n");
+
+        let filter = TradeFilter {
+            pair: Some("BTC_USDT".to_string()),
+            from: None,
+            to: None,
+        };
+        let report = replay.capture(capture.as_bytes(), &filter).await.unwrap();
+        assert_eq!(
+            report,
+            ReplayReport {
+                trades: 3,
+                batches: 3,
+                unreadable: 1
+            }
+        );
+
+        // Each state of the first candle was saved
+        let klines = store.load_klines(&KlineFilter::default()).await.unwrap();
+        assert_eq!(klines.len(), 3);
+        let klines = rebuilt_klines(&store).await.unwrap();
+        assert_eq!(klines.len(), 2);
+        assert_eq!((klines[0].o, klines[0].l, klines[0].c), (100.0, 90.0, 90.0));
+        assert_eq!(klines[0].volume_bs.buy_base, 1.0);
+        assert_eq!(klines[0].volume_bs.sell_base, 2.0);
+        assert_eq!((klines[1].utc_begin, klines[1].o), (120_000, 95.0));
+    }
+}
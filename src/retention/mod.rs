@@ -0,0 +1,437 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::TryStreamExt;
+use serde::Serialize;
+use tokio::time::{self, MissedTickBehavior};
+use tracing::{error, info};
+
+use crate::database::{KlineFilter, KlineStore, Store, TradeFilter, VacuumMode};
+use crate::error::Error;
+use crate::metrics::unix_millis;
+use crate::parser::{
+    kline::{Kline, VBS},
+    time_frame_millis,
+};
+
+/// Upserts of downsampled klines are done this many at a time
+const DOWNSAMPLE_BATCH: usize = 500;
+
+/// What a rule applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RetentionTarget {
+    /// The `recent_trades` table
+    Trades,
+    /// Klines of every time frame without a rule of its own
+    Klines,
+    /// Klines of one time frame, e.g. `MINUTE_1`
+    TimeFrame(String),
+}
+
+/*
+    How long rows are kept, `target:age[:downsample]`:
+        trades:7d               - recent trades older than 7 days are deleted
+        MINUTE_1:90d:HOUR_1     - 1m candles older than 90 days are rolled into 1h ones, then deleted
+        HOUR_1:forever          - 1h candles are never deleted
+        klines:365d             - candles of the other time frames are kept a year
+    Ages are in `m`, `h`, `d` or `w`.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionRule {
+    pub target: RetentionTarget,
+    pub keep: Option<i64>, // ms, None keeps forever
+    pub downsample: Option<String>,
+}
+
+fn parse_age(age: &str) -> Result<Option<i64>, String> {
+    if age == "forever" {
+        return Ok(None);
+    }
+    let unit = match age.chars().last() {
+        Some('m') => 60_000,
+        Some('h') => 3_600_000,
+        Some('d') => 86_400_000,
+        Some('w') => 7 * 86_400_000,
+        _ => {
+            return Err(format!(
+                "unknown age '{}', expected e.g. 90d or forever",
+                age
+            ))
+        }
+    };
+    age[..age.len() - 1]
+        .parse::<i64>()
+        .ok()
+        .filter(|n| *n > 0)
+        .map(|n| Some(n * unit))
+        .ok_or_else(|| format!("unknown age '{}', expected e.g. 90d or forever", age))
+}
+
+impl FromStr for RetentionRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.trim().split(':').map(str::trim).collect();
+        let (target, age, downsample) = match parts[..] {
+            [target, age] => (target, age, None),
+            [target, age, downsample] => (target, age, Some(downsample.to_string())),
+            _ => {
+                return Err(format!(
+                    "invalid rule '{}', expected target:age[:downsample]",
+                    s
+                ))
+            }
+        };
+        let target = match target {
+            "trades" => RetentionTarget::Trades,
+            "klines" => RetentionTarget::Klines,
+            time_frame if time_frame_millis(time_frame).is_some() => {
+                RetentionTarget::TimeFrame(time_frame.to_string())
+            }
+            other => return Err(format!("unknown retention target '{}'", other)),
+        };
+        let keep = parse_age(age)?;
+
+        if let Some(to) = &downsample {
+            let RetentionTarget::TimeFrame(from) = &target else {
+                return Err(format!("only a time frame can be downsampled, not '{}'", s));
+            };
+            let (from_millis, to_millis) = (
+                time_frame_millis(from).unwrap_or_default(),
+                time_frame_millis(to).unwrap_or_default(),
+            );
+            // Weeks and months are not aligned on the epoch
+            if to == "WEEK_1"
+                || to == "MONTH_1"
+                || to_millis <= from_millis
+                || to_millis % from_millis != 0
+            {
+                return Err(format!("{} candles can't be rolled into {}", from, to));
+            }
+            if keep.is_none() {
+                return Err(format!(
+                    "'{}' keeps forever, there is nothing to downsample",
+                    s
+                ));
+            }
+        }
+        Ok(RetentionRule {
+            target,
+            keep,
+            downsample,
+        })
+    }
+}
+
+/// Parses a `;`-separated list such as `trades:7d;MINUTE_1:90d:HOUR_1;HOUR_1:forever`
+pub fn parse_rules(list: &str) -> Result<Vec<RetentionRule>, String> {
+    list.split(';')
+        .filter(|s| !s.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// The rules and what happens to the freed space
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    pub rules: Vec<RetentionRule>,
+    pub vacuum: VacuumMode,
+}
+
+/// What one pass deleted and wrote
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RetentionReport {
+    pub trades_deleted: u64,
+    pub klines_deleted: u64,
+    pub klines_downsampled: u64, // higher time frame candles written
+}
+
+/// The candle of `time_frame` starting at `utc_begin` made of consecutive smaller ones
+fn roll_up(candles: &[Kline], time_frame: &str, utc_begin: i64) -> Option<Kline> {
+    let (first, last) = (candles.first()?, candles.last()?);
+    let mut kline = Kline {
+        pair: first.pair.clone(),
+        time_frame: time_frame.to_string(),
+        o: first.o,
+        h: first.h,
+        l: first.l,
+        c: last.c,
+        utc_begin,
+        volume_bs: VBS {
+            buy_base: 0.0,
+            sell_base: 0.0,
+            buy_quote: 0.0,
+            sell_quote: 0.0,
+        },
+    };
+    for candle in candles {
+        kline.h = kline.h.max(candle.h);
+        kline.l = kline.l.min(candle.l);
+        kline.volume_bs.buy_base += candle.volume_bs.buy_base;
+        kline.volume_bs.sell_base += candle.volume_bs.sell_base;
+        kline.volume_bs.buy_quote += candle.volume_bs.buy_quote;
+        kline.volume_bs.sell_quote += candle.volume_bs.sell_quote;
+    }
+    Some(kline)
+}
+
+/*
+    Rolls the candles of one series before `before` (a multiple of the target length) into the
+    target time frame. Only the last stored state of a candle counts, and target candles that
+    are already stored (e.g. collected from the exchange) are left as they are. Returns how many
+    were written.
+*/
+async fn downsample(
+    store: &dyn KlineStore,
+    pair: &str,
+    from: &str,
+    to: &str,
+    before: i64,
+) -> Result<u64, Error> {
+    let step = time_frame_millis(to).unwrap_or(i64::MAX);
+    let existing: HashSet<i64> = store
+        .load_klines(&KlineFilter {
+            pair: Some(pair.to_string()),
+            time_frame: Some(to.to_string()),
+            from: None,
+            to: Some(before - 1),
+        })
+        .await?
+        .into_iter()
+        .map(|k| k.utc_begin)
+        .collect();
+
+    let filter = KlineFilter {
+        pair: Some(pair.to_string()),
+        time_frame: Some(from.to_string()),
+        from: None,
+        to: Some(before - 1),
+    };
+    let mut rows = store.stream_klines(&filter);
+    let mut bucket: Vec<Kline> = Vec::new(); // final states of the candles of the target candle
+    let mut rolled: Vec<Kline> = Vec::new();
+    let mut written = 0;
+    let flush = |bucket: &mut Vec<Kline>, rolled: &mut Vec<Kline>| {
+        let Some(first) = bucket.first() else {
+            return;
+        };
+        let utc_begin = first.utc_begin - first.utc_begin.rem_euclid(step);
+        if !existing.contains(&utc_begin) {
+            rolled.extend(roll_up(bucket, to, utc_begin));
+        }
+        bucket.clear();
+    };
+
+    // Rows come ordered by (utc_begin, id): a later row of the same candle is a later state
+    while let Some(kline) = rows.try_next().await? {
+        match bucket.last_mut() {
+            Some(last) if last.utc_begin == kline.utc_begin => *last = kline,
+            Some(last) if last.utc_begin.div_euclid(step) == kline.utc_begin.div_euclid(step) => {
+                bucket.push(kline)
+            }
+            _ => {
+                flush(&mut bucket, &mut rolled);
+                bucket.push(kline);
+            }
+        }
+        if rolled.len() >= DOWNSAMPLE_BATCH {
+            store.upsert_klines(&rolled).await?;
+            written += rolled.len() as u64;
+            rolled.clear();
+        }
+    }
+    flush(&mut bucket, &mut rolled);
+    store.upsert_klines(&rolled).await?;
+    Ok(written + rolled.len() as u64)
+}
+
+/// The rule of a time frame: its own, else the `klines` one
+fn kline_rule<'a>(rules: &'a [RetentionRule], time_frame: &str) -> Option<&'a RetentionRule> {
+    rules
+        .iter()
+        .find(|r| r.target == RetentionTarget::TimeFrame(time_frame.to_string()))
+        .or_else(|| rules.iter().find(|r| r.target == RetentionTarget::Klines))
+}
+
+/// One pass of the rules, as of `now` (ms); the space is reclaimed when anything was deleted
+pub async fn apply_retention(
+    store: &dyn Store,
+    policy: &RetentionPolicy,
+    now: i64,
+) -> Result<RetentionReport, Error> {
+    let mut report = RetentionReport::default();
+
+    for rule in &policy.rules {
+        if let (RetentionTarget::Trades, Some(keep)) = (&rule.target, rule.keep) {
+            let filter = TradeFilter {
+                to: Some(now - keep - 1),
+                ..TradeFilter::default()
+            };
+            report.trades_deleted += store.delete_trades(&filter).await?;
+        }
+    }
+
+    for (pair, time_frame) in store.load_series().await? {
+        let Some(rule) = kline_rule(&policy.rules, &time_frame) else {
+            continue;
+        };
+        let Some(keep) = rule.keep else {
+            continue;
+        };
+        let mut before = now - keep;
+        if let Some(to) = &rule.downsample {
+            let step = time_frame_millis(to).unwrap_or(1);
+            before -= before.rem_euclid(step); // a partly expired target candle waits for the rest
+            report.klines_downsampled += downsample(store, &pair, &time_frame, to, before).await?;
+        }
+        let filter = KlineFilter {
+            pair: Some(pair),
+            time_frame: Some(time_frame),
+            from: None,
+            to: Some(before - 1),
+        };
+        report.klines_deleted += store.delete_klines(&filter).await?;
+    }
+
+    if report.trades_deleted + report.klines_deleted > 0 {
+        store.reclaim_space(policy.vacuum).await?;
+    }
+    Ok(report)
+}
+
+/*
+    Applies the rules every `interval`, the first time right away. Runs until the task is
+    dropped; a failed pass is logged and the next one tries again.
+*/
+pub async fn run_retention(store: Arc<dyn Store>, policy: RetentionPolicy, interval: Duration) {
+    let mut timer = time::interval(interval);
+    timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        timer.tick().await;
+        match apply_retention(store.as_ref(), &policy, unix_millis()).await {
+            Ok(report) => info!(
+                "Retention: {} trades and {} klines deleted, {} klines downsampled",
+                report.trades_deleted, report.klines_deleted, report.klines_downsampled
+            ),
+            Err(err) => error!("Retention failed: {}", err),
+        }
+    }
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{open_store, SqliteTuning};
+    use crate::parser::recent_trade::RecentTrade;
+
+    const DAY: i64 = 86_400_000;
+
+    fn minute(utc_begin: i64, close: f64) -> Kline {
+        Kline {
+            o: close - 1.0,
+            h: close + 1.0,
+            l: close - 2.0,
+            c: close,
+            ..Kline::sample("BTC_USDT", "MINUTE_1", utc_begin)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retention() {
+        assert!("MINUTE_1:90d:WEEK_1".parse::<RetentionRule>().is_err());
+        assert!("trades:7d:HOUR_1".parse::<RetentionRule>().is_err());
+        assert!("MINUTE_15:1d:HOUR_1".parse::<RetentionRule>().is_ok());
+        let rules = parse_rules("trades:7d; MINUTE_1:2d:HOUR_1; HOUR_1:forever").unwrap();
+        assert_eq!(rules[1].keep, Some(2 * DAY));
+
+        let path = std::env::temp_dir().join(format!("retention_{}.db", uuid::Uuid::new_v4()));
+        let store = open_store(&path.display().to_string(), &SqliteTuning::default())
+            .await
+            .unwrap();
+        let now = 10 * DAY + 90 * 60_000; // 1:30 on the 10th day
+
+        // Two states of the first minute, three minutes of the first hour, one of the next;
+        // the hour of the 8th day is partly kept, the collected hour stays as it is
+        let hour = 8 * DAY;
+        let mut stale = minute(hour + 60_000, 100.0);
+        stale.c = 90.0;
+        store
+            .save_klines(&[
+                stale,
+                minute(hour + 60_000, 101.0),
+                minute(hour + 120_000, 105.0),
+                minute(hour + 180_000, 99.0),
+                minute(hour + 3_600_000, 110.0),
+                minute(hour + 5_400_000, 120.0),
+                minute(now, 130.0),
+            ])
+            .await
+            .unwrap();
+        let mut collected = minute(hour + 3_600_000, 1.0);
+        collected.time_frame = "HOUR_1".to_string();
+        store.save_klines(&[collected.clone()]).await.unwrap();
+        let trade = |tid: &str, timestamp: i64| RecentTrade {
+            tid: tid.to_string(),
+            pair: "BTC_USDT".to_string(),
+            price: "1".to_string(),
+            amount: "1".to_string(),
+            side: "buy".to_string(),
+            timestamp,
+        };
+        store
+            .save_trades(&[trade("1", DAY), trade("2", 9 * DAY)])
+            .await
+            .unwrap();
+
+        let policy = RetentionPolicy {
+            rules,
+            vacuum: VacuumMode::Incremental,
+        };
+        let report = apply_retention(store.as_ref(), &policy, now).await.unwrap();
+        assert_eq!(
+            report,
+            RetentionReport {
+                trades_deleted: 1,
+                klines_deleted: 4, // both states of the first minute and the other two of its hour
+                klines_downsampled: 1,
+            }
+        );
+
+        let hours = store
+            .load_klines(&KlineFilter {
+                time_frame: Some("HOUR_1".to_string()),
+                ..KlineFilter::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(hours.len(), 2);
+        let rolled = &hours[0];
+        assert_eq!((rolled.utc_begin, rolled.o, rolled.c), (hour, 100.0, 99.0));
+        assert_eq!((rolled.h, rolled.l), (106.0, 97.0));
+        assert_eq!(rolled.volume_bs.sell_quote, 12.0);
+        assert_eq!((hours[1].utc_begin, hours[1].c), (collected.utc_begin, 1.0));
+
+        let minutes = store
+            .load_klines(&KlineFilter {
+                time_frame: Some("MINUTE_1".to_string()),
+                ..KlineFilter::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(minutes.len(), 3);
+        let trades = store.load_trades(&TradeFilter::default()).await.unwrap();
+        assert_eq!(trades.len(), 1);
+
+        // Nothing left to do
+        let report = apply_retention(store.as_ref(), &policy, now).await.unwrap();
+        assert_eq!(report, RetentionReport::default());
+
+        drop(store);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,197 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use super::{KlineSink, SinkFuture};
+use crate::export::records::KlineRecord;
+use crate::metrics::unix_millis;
+use crate::parser::{kline::Kline, utc_date};
+
+const HOUR_MILLIS: i64 = 3_600_000;
+
+/// The file being written: its UTC hour, its index within the hour and its size
+struct Current {
+    hour: i64,
+    index: u32,
+    written: u64,
+    file: BufWriter<File>,
+}
+
+/*
+    `jsonl`: the klines as JSON Lines (rows shaped like the export's) in `{dir}`, in files
+    `klines-{YYYY-MM-DD}T{HH}-{n}.jsonl`: a new file every UTC hour and whenever one reaches
+    `max_file_bytes`. Every batch is written on a blocking thread and flushed before the future
+    of `send` completes, so a reader tailing the files sees whole lines.
+*/
+#[derive(Clone)]
+pub struct JsonlSink {
+    dir: PathBuf,
+    max_file_bytes: u64,
+    current: Arc<Mutex<Option<Current>>>,
+}
+
+impl JsonlSink {
+    pub fn new(dir: impl Into<PathBuf>, max_file_bytes: u64) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(JsonlSink {
+            dir,
+            max_file_bytes,
+            current: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    fn path(&self, hour: i64, index: u32) -> PathBuf {
+        let (year, month, day) = utc_date(hour * HOUR_MILLIS);
+        self.dir.join(format!(
+            "klines-{:04}-{:02}-{:02}T{:02}-{}.jsonl",
+            year,
+            month,
+            day,
+            hour.rem_euclid(24),
+            index
+        ))
+    }
+
+    /// The first index from `index` on whose file of `hour` is missing or not full, e.g. after a restart
+    fn open_index(&self, hour: i64, mut index: u32) -> io::Result<u32> {
+        loop {
+            match fs::metadata(self.path(hour, index)) {
+                Ok(metadata) if metadata.len() >= self.max_file_bytes => index += 1,
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => return Ok(index),
+            }
+        }
+    }
+
+    /// Appends to the file of `now` (ms), rotating first when the hour changed or the file is full
+    fn write(&self, klines: &[Kline], now: i64) -> io::Result<()> {
+        let mut lines = Vec::new();
+        for kline in klines {
+            serde_json::to_writer(&mut lines, &KlineRecord::from(kline))?;
+            lines.push(b'\n');
+        }
+        let hour = now.div_euclid(HOUR_MILLIS);
+        let mut current = self.current.lock().unwrap();
+        let index = match current.as_ref() {
+            Some(c) if c.hour == hour && c.written < self.max_file_bytes => None,
+            Some(c) if c.hour == hour => Some(self.open_index(hour, c.index + 1)?),
+            _ => Some(self.open_index(hour, 0)?),
+        };
+        if let Some(index) = index {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.path(hour, index))?;
+            let written = file.metadata()?.len(); // appending to a file of an earlier run
+            *current = Some(Current {
+                hour,
+                index,
+                written,
+                file: BufWriter::new(file),
+            });
+        }
+        let Some(current) = current.as_mut() else {
+            return Ok(());
+        };
+        current.file.write_all(&lines)?;
+        current.file.flush()?;
+        current.written += lines.len() as u64;
+        Ok(())
+    }
+}
+
+impl KlineSink for JsonlSink {
+    fn name(&self) -> &str {
+        "jsonl"
+    }
+
+    fn send<'a>(&'a self, klines: &'a [Kline]) -> SinkFuture<'a> {
+        Box::pin(async move {
+            let sink = self.clone();
+            let klines = klines.to_vec();
+            tokio::task::spawn_blocking(move || sink.write(&klines, unix_millis()))
+                .await
+                .map_err(io::Error::other)??;
+            Ok(())
+        })
+    }
+}
+
+/// `stdout`: the klines as JSON Lines on the standard output, e.g. piped into another program
+pub struct StdoutSink;
+
+impl KlineSink for StdoutSink {
+    fn name(&self) -> &str {
+        "stdout"
+    }
+
+    fn send<'a>(&'a self, klines: &'a [Kline]) -> SinkFuture<'a> {
+        Box::pin(async move {
+            let records: Vec<KlineRecord> = klines.iter().map(KlineRecord::from).collect();
+            tokio::task::spawn_blocking(move || -> io::Result<()> {
+                let mut out = io::stdout().lock();
+                for record in &records {
+                    serde_json::to_writer(&mut out, record)?;
+                    writeln!(out)?;
+                }
+                out.flush()
+            })
+            .await
+            .map_err(io::Error::other)??;
+            Ok(())
+        })
+    }
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jsonl_rotation() {
+        let dir = std::env::temp_dir().join(format!("jsonl-sink-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let kline = Kline::sample("BTC_USDT", "MINUTE_1", 1737709980000);
+        let sink = JsonlSink::new(&dir, 1).unwrap();
+        let now = 1737709991000; // 2025-01-24 09:13:11
+        sink.write(std::slice::from_ref(&kline), now).unwrap();
+        sink.write(&[kline.clone(), kline.clone()], now).unwrap(); // the first file is full
+        sink.write(std::slice::from_ref(&kline), now + HOUR_MILLIS)
+            .unwrap();
+
+        let mut names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "klines-2025-01-24T09-0.jsonl",
+                "klines-2025-01-24T09-1.jsonl",
+                "klines-2025-01-24T10-0.jsonl"
+            ]
+        );
+        let second = fs::read_to_string(dir.join(&names[1])).unwrap();
+        let rows: Vec<serde_json::Value> = second
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            (rows[0]["pair"].as_str(), rows[0]["close"].as_f64()),
+            (Some("BTC_USDT"), Some(1.5))
+        );
+
+        // After a restart the full files of the hour are skipped
+        let sink = JsonlSink::new(&dir, 1).unwrap();
+        sink.write(std::slice::from_ref(&kline), now).unwrap();
+        assert!(dir.join("klines-2025-01-24T09-2.jsonl").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
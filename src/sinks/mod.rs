@@ -0,0 +1,209 @@
+mod files;
+mod redis;
+
+pub use files::{JsonlSink, StdoutSink};
+pub use redis::RedisSink;
+
+use std::{fmt, future::Future, pin::Pin, str::FromStr, sync::Arc};
+
+use crate::{
+    database::KlineWriteBuffer,
+    error::Error,
+    indicators::IndicatorEngine,
+    live::LiveFeed,
+    parser::{kline::Kline, KlineKey},
+};
+
+/// The work of a sink with a batch of klines
+pub type SinkFuture<'a> = Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+
+/*
+    Where the klines go once they have passed through the aggregator, per series: `KLINE_SINKS`
+    rules name the sinks of the series they match, the others go to the default ones (the live
+    feed, the indicators and the database). A sink that fails doesn't keep the others from
+    getting their klines.
+*/
+pub trait KlineSink: Send + Sync {
+    /// The name the sink is configured by, e.g. in `KLINE_SINKS`
+    fn name(&self) -> &str;
+
+    /// Takes the klines of one series, in one batch
+    fn send<'a>(&'a self, klines: &'a [Kline]) -> SinkFuture<'a>;
+}
+
+/// `store`: the database, through the write buffer
+pub struct StoreSink(pub KlineWriteBuffer);
+
+impl KlineSink for StoreSink {
+    fn name(&self) -> &str {
+        "store"
+    }
+
+    fn send<'a>(&'a self, klines: &'a [Kline]) -> SinkFuture<'a> {
+        Box::pin(self.0.push(klines.to_vec()))
+    }
+}
+
+/// `live`: the subscribers of the live feed
+pub struct FeedSink(pub LiveFeed);
+
+impl KlineSink for FeedSink {
+    fn name(&self) -> &str {
+        "live"
+    }
+
+    fn send<'a>(&'a self, klines: &'a [Kline]) -> SinkFuture<'a> {
+        self.0.publish_klines(klines);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// `indicators`: the indicator engine, which saves its points
+pub struct IndicatorSink(pub IndicatorEngine);
+
+impl KlineSink for IndicatorSink {
+    fn name(&self) -> &str {
+        "indicators"
+    }
+
+    fn send<'a>(&'a self, klines: &'a [Kline]) -> SinkFuture<'a> {
+        Box::pin(async move {
+            self.0.process(klines).await?;
+            Ok(())
+        })
+    }
+}
+
+/// Sink names a rule may use
+pub const SINK_NAMES: [&str; 6] = ["store", "live", "indicators", "stdout", "jsonl", "redis"];
+
+/// The series a rule applies to; `None` matches any pair or time frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeriesPattern {
+    pub pair: Option<String>,
+    pub time_frame: Option<String>,
+}
+
+impl SeriesPattern {
+    pub fn matches(&self, key: &KlineKey) -> bool {
+        self.pair.as_ref().is_none_or(|pair| *pair == key.0)
+            && self.time_frame.as_ref().is_none_or(|tf| *tf == key.1)
+    }
+}
+
+impl fmt::Display for SeriesPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}",
+            self.pair.as_deref().unwrap_or("*"),
+            self.time_frame.as_deref().unwrap_or("*")
+        )
+    }
+}
+
+/// The sinks of the series matching a pattern, e.g. `*:SECOND_1=live`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkRule {
+    pub series: SeriesPattern,
+    pub sinks: Vec<String>, // names from `SINK_NAMES`; none drops the series
+}
+
+impl FromStr for SinkRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid rule '{}', expected pair:timeframe=sink,...", s);
+        let (series, sinks) = s.trim().split_once('=').ok_or_else(invalid)?;
+        let (pair, time_frame) = series.trim().split_once(':').ok_or_else(invalid)?;
+        let pattern = |part: &str| match part.trim() {
+            "" => Err(invalid()),
+            "*" => Ok(None),
+            part => Ok(Some(part.to_string())),
+        };
+        let sinks: Vec<String> = sinks
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+        if let Some(unknown) = sinks
+            .iter()
+            .find(|name| !SINK_NAMES.contains(&name.as_str()))
+        {
+            return Err(format!(
+                "unknown sink '{}', expected one of {}",
+                unknown,
+                SINK_NAMES.join(", ")
+            ));
+        }
+        Ok(SinkRule {
+            series: SeriesPattern {
+                pair: pattern(pair)?,
+                time_frame: pattern(time_frame)?,
+            },
+            sinks,
+        })
+    }
+}
+
+/// Parses a `;`-separated list such as `*:MINUTE_1=store,live;*:SECOND_1=live`
+pub fn parse_sink_rules(list: &str) -> Result<Vec<SinkRule>, String> {
+    list.split(';')
+        .filter(|s| !s.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Which sinks the klines of a series go to: those of the first matching route, the default ones otherwise
+#[derive(Clone, Default)]
+pub(crate) struct SinkRoutes {
+    routes: Vec<(SeriesPattern, Vec<Arc<dyn KlineSink>>)>,
+    default: Vec<Arc<dyn KlineSink>>,
+}
+
+impl SinkRoutes {
+    pub(crate) fn add_default(&mut self, sink: Arc<dyn KlineSink>) {
+        self.default.push(sink);
+    }
+
+    pub(crate) fn add_route(&mut self, series: SeriesPattern, sinks: Vec<Arc<dyn KlineSink>>) {
+        self.routes.push((series, sinks));
+    }
+
+    pub(crate) fn route(&self, key: &KlineKey) -> Vec<Arc<dyn KlineSink>> {
+        self.routes
+            .iter()
+            .find(|(series, _)| series.matches(key))
+            .map_or(&self.default, |(_, sinks)| sinks)
+            .clone()
+    }
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sink_rules() {
+        let rules =
+            parse_sink_rules("BTC_USDT:MINUTE_1=store, live; *:SECOND_1=live;*:*=").unwrap();
+        assert_eq!(rules.len(), 3);
+        assert_eq!(rules[0].sinks, vec!["store", "live"]);
+        assert_eq!(rules[1].series.to_string(), "*:SECOND_1");
+        assert!(rules[1]
+            .series
+            .matches(&("ETH_USDT".to_string(), "SECOND_1".to_string())));
+        assert!(!rules[1]
+            .series
+            .matches(&("ETH_USDT".to_string(), "MINUTE_1".to_string())));
+        assert!(rules[2].sinks.is_empty());
+
+        assert!("*:MINUTE_1=kafka".parse::<SinkRule>().is_err());
+        assert!("MINUTE_1=store".parse::<SinkRule>().is_err());
+        assert!(":MINUTE_1=store".parse::<SinkRule>().is_err());
+    }
+}
@@ -0,0 +1,281 @@
+use std::io;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufStream};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time::timeout;
+
+use super::{KlineSink, SinkFuture};
+use crate::export::records::KlineRecord;
+use crate::parser::kline::Kline;
+
+/// Longest a batch may take to be published, connecting included
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Writes a command as a RESP array of bulk strings
+fn encode_command(out: &mut Vec<u8>, args: &[&[u8]]) {
+    out.extend_from_slice(format!("*{}\r\n", args.len()).as_bytes());
+    for arg in args {
+        out.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        out.extend_from_slice(arg);
+        out.extend_from_slice(b"\r\n");
+    }
+}
+
+/// Reads a status or integer reply; an error reply becomes an error
+async fn read_reply(conn: &mut BufStream<TcpStream>) -> io::Result<()> {
+    let mut line = String::new();
+    if conn.read_line(&mut line).await? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    match line.as_bytes().first() {
+        Some(b'+') | Some(b':') => Ok(()),
+        Some(b'-') => Err(io::Error::other(format!("redis: {}", line.trim_end()))),
+        _ => Err(io::Error::other(format!(
+            "redis: unexpected reply {}",
+            line.trim_end()
+        ))),
+    }
+}
+
+/// `%xx` escapes of a URL's user info replaced by the bytes they stand for
+fn percent_decode(text: &str) -> Result<String, String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = text
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| format!("bad escape in '{}'", text))?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| format!("'{}' is not UTF-8 once decoded", text))
+}
+
+/*
+    `redis`: every kline `PUBLISH`ed as JSON (rows shaped like the export's) to a channel named
+    after `channel` with `{pair}` and `{timeframe}` replaced, e.g. `klines.BTC_USDT.MINUTE_1`, on a
+    Redis server or anything speaking its protocol. Subscribers get candles as they arrive instead
+    of polling the database. The connection is opened on the first batch and opened again after
+    a failure; a batch goes in one round trip.
+*/
+pub struct RedisSink {
+    addr: String,             // host:port
+    username: Option<String>, // an ACL user, the default user when absent
+    password: Option<String>,
+    channel: String,
+    conn: Mutex<Option<BufStream<TcpStream>>>,
+}
+
+impl RedisSink {
+    /*
+        From `redis://[[username]:password@]host[:port]`, the user info percent-encoded; the
+        database number, if any, doesn't matter to `PUBLISH`
+    */
+    pub fn new(url: &str, channel: &str) -> Result<Self, String> {
+        let rest = url
+            .strip_prefix("redis://")
+            .ok_or_else(|| format!("'{}' is not a redis:// URL", url))?;
+        let rest = rest.split('/').next().unwrap_or_default();
+        let (username, password, host) = match rest.rsplit_once('@') {
+            Some((user_info, host)) => {
+                let (username, password) = user_info.split_once(':').unwrap_or((user_info, ""));
+                let decoded = |part: &str| -> Result<Option<String>, String> {
+                    Ok(Some(percent_decode(part)?).filter(|p| !p.is_empty()))
+                };
+                (decoded(username)?, decoded(password)?, host)
+            }
+            None => (None, None, rest),
+        };
+        if host.is_empty() {
+            return Err(format!("'{}' has no host", url));
+        }
+        let addr = if host.contains(':') {
+            host.to_string()
+        } else {
+            format!("{}:6379", host)
+        };
+        Ok(RedisSink {
+            addr,
+            username,
+            password,
+            channel: channel.to_string(),
+            conn: Mutex::new(None),
+        })
+    }
+
+    fn channel(&self, kline: &Kline) -> String {
+        self.channel
+            .replace("{pair}", &kline.pair)
+            .replace("{timeframe}", &kline.time_frame)
+    }
+
+    async fn connect(&self) -> io::Result<BufStream<TcpStream>> {
+        let mut conn = BufStream::new(TcpStream::connect(&self.addr).await?);
+        if let Some(password) = &self.password {
+            let mut command = Vec::new();
+            match &self.username {
+                Some(username) => encode_command(
+                    &mut command,
+                    &[b"AUTH", username.as_bytes(), password.as_bytes()],
+                ),
+                None => encode_command(&mut command, &[b"AUTH", password.as_bytes()]),
+            }
+            conn.write_all(&command).await?;
+            conn.flush().await?;
+            read_reply(&mut conn).await?;
+        }
+        Ok(conn)
+    }
+
+    async fn publish(&self, klines: &[Kline]) -> io::Result<()> {
+        let mut commands = Vec::new();
+        for kline in klines {
+            let payload = serde_json::to_vec(&KlineRecord::from(kline))?;
+            encode_command(
+                &mut commands,
+                &[b"PUBLISH", self.channel(kline).as_bytes(), &payload],
+            );
+        }
+        let mut guard = self.conn.lock().await;
+        let result = async {
+            if guard.is_none() {
+                *guard = Some(self.connect().await?);
+            }
+            let Some(conn) = guard.as_mut() else {
+                return Ok(());
+            };
+            conn.write_all(&commands).await?;
+            conn.flush().await?;
+            for _ in klines {
+                read_reply(conn).await?;
+            }
+            Ok(())
+        };
+        let result = match timeout(SEND_TIMEOUT, result).await {
+            Ok(result) => result,
+            Err(_) => Err(io::ErrorKind::TimedOut.into()),
+        };
+        if result.is_err() {
+            *guard = None; // the replies are out of step, or the server is gone
+        }
+        result
+    }
+}
+
+impl KlineSink for RedisSink {
+    fn name(&self) -> &str {
+        "redis"
+    }
+
+    fn send<'a>(&'a self, klines: &'a [Kline]) -> SinkFuture<'a> {
+        Box::pin(async move {
+            if !klines.is_empty() {
+                self.publish(klines).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    /// Reads one RESP array of bulk strings
+    async fn read_command(conn: &mut BufStream<TcpStream>) -> Option<Vec<String>> {
+        let mut line = String::new();
+        conn.read_line(&mut line).await.ok().filter(|n| *n > 0)?;
+        let count: usize = line.trim_end().strip_prefix('*')?.parse().ok()?;
+        let mut args = Vec::new();
+        for _ in 0..count {
+            line.clear();
+            conn.read_line(&mut line).await.ok()?;
+            let len: usize = line.trim_end().strip_prefix('$')?.parse().ok()?;
+            let mut arg = vec![0; len + 2];
+            conn.read_exact(&mut arg).await.ok()?;
+            args.push(String::from_utf8_lossy(&arg[..len]).to_string());
+        }
+        Some(args)
+    }
+
+    #[tokio::test]
+    async fn test_redis_publish() {
+        // A server that answers AUTH and PUBLISH, and passes on what it receives
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, mut received) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    let mut conn = BufStream::new(stream);
+                    while let Some(command) = read_command(&mut conn).await {
+                        let reply: &[u8] = if command[0] == "AUTH" {
+                            b"+OK\r\n"
+                        } else {
+                            b":1\r\n"
+                        };
+                        conn.write_all(reply).await.unwrap();
+                        conn.flush().await.unwrap();
+                        sender.send(command).unwrap();
+                    }
+                });
+            }
+        });
+
+        let sink = RedisSink::new(
+            &format!("redis://:secret@{}/0", addr),
+            "klines.{pair}.{timeframe}",
+        )
+        .unwrap();
+        let kline = |pair: &str| Kline::sample(pair, "MINUTE_1", 60_000);
+        sink.send(&[kline("BTC_USDT"), kline("ETH_USDT")])
+            .await
+            .unwrap();
+
+        assert_eq!(received.recv().await.unwrap(), vec!["AUTH", "secret"]);
+        let publish = received.recv().await.unwrap();
+        assert_eq!(publish[..2], ["PUBLISH", "klines.BTC_USDT.MINUTE_1"]);
+        let row: serde_json::Value = serde_json::from_str(&publish[2]).unwrap();
+        assert_eq!(row["close"], 1.5);
+        assert_eq!(
+            received.recv().await.unwrap()[1],
+            "klines.ETH_USDT.MINUTE_1"
+        );
+
+        // An ACL user, with a password that had to be escaped
+        let sink = RedisSink::new(
+            &format!("redis://collector:p%40ss%3Aword@{}", addr),
+            "klines",
+        )
+        .unwrap();
+        sink.send(&[kline("BTC_USDT")]).await.unwrap();
+        assert_eq!(
+            received.recv().await.unwrap(),
+            vec!["AUTH", "collector", "p@ss:word"]
+        );
+        assert_eq!(received.recv().await.unwrap()[1], "klines");
+
+        assert_eq!(
+            RedisSink::new("redis://localhost", "k").unwrap().addr,
+            "localhost:6379"
+        );
+        assert!(RedisSink::new("redis://:bad%zz@localhost", "k").is_err());
+        assert!(RedisSink::new("nats://localhost", "k").is_err());
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use tracing::{info, warn};
+
+use crate::config::ConfigError;
+use crate::database::SymbolStore;
+use crate::error::Error;
+use crate::http_client::http_client::RestClient;
+use crate::http_client::HttpClientError;
+use crate::parser::symbol::{
+    parse_binance_exchange_info, parse_binance_volumes, parse_poloniex_markets, SymbolInfo,
+};
+use crate::parser::ticker::parse_tickers;
+
+/// One entry of SYMBOLS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolPattern {
+    /// A pair that must exist, e.g. `BTC_USDT`
+    Exact(String),
+    /// Every trading pair the pattern matches, `*` standing for any text and `?` for one character, e.g. `*_USDT`
+    Glob(String),
+    /// The `count` trading pairs with the largest 24h quote volume, optionally among those a glob matches:
+    /// `top:10` or `top:10:*_USDT`
+    Top { count: usize, glob: Option<String> },
+}
+
+impl FromStr for SymbolPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(rest) = s.strip_prefix("top:") {
+            let (count, glob) = match rest.split_once(':') {
+                Some((count, glob)) => (count, Some(glob.to_string())),
+                None => (rest, None),
+            };
+            let count = count
+                .parse()
+                .map_err(|_| format!("'{}' is not a number of pairs", count))?;
+            return Ok(SymbolPattern::Top { count, glob });
+        }
+        if s.is_empty() {
+            return Err("empty symbol".to_string());
+        }
+        if s.contains(['*', '?']) {
+            Ok(SymbolPattern::Glob(s.to_string()))
+        } else {
+            Ok(SymbolPattern::Exact(s.to_string()))
+        }
+    }
+}
+
+/// Whether `text` matches a pattern where `*` is any run of characters and `?` any one character
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None; // (position of the last `*`, text position it was tried at)
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, tried)) = backtrack {
+            p = star + 1;
+            t = tried + 1;
+            backtrack = Some((star, tried + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/*
+    Expands the patterns into the list of pairs to collect, in the order they are listed, without repeats.
+    Globs and top-N pick trading markets only; an exact pair must exist, but is kept (with a warning)
+    when it is not trading. `volumes` are 24h quote volumes by pair, needed by top-N only.
+*/
+pub fn expand(
+    patterns: &[SymbolPattern],
+    markets: &[SymbolInfo],
+    volumes: &HashMap<String, f64>,
+) -> Result<Vec<String>, String> {
+    let mut pairs = Vec::new();
+    let mut seen = HashSet::new();
+    let mut add = |pair: &str| {
+        if seen.insert(pair.to_string()) {
+            pairs.push(pair.to_string());
+        }
+    };
+
+    for pattern in patterns {
+        match pattern {
+            SymbolPattern::Exact(pair) => {
+                let market = markets
+                    .iter()
+                    .find(|m| m.pair == *pair)
+                    .ok_or_else(|| format!("the exchange has no market {}", pair))?;
+                if !market.trading {
+                    warn!("{} is not trading ({})", pair, market.status);
+                }
+                add(pair);
+            }
+            SymbolPattern::Glob(glob) => {
+                let matched: Vec<&SymbolInfo> = markets
+                    .iter()
+                    .filter(|m| m.trading && glob_matches(glob, &m.pair))
+                    .collect();
+                if matched.is_empty() {
+                    warn!("{} matches no trading market", glob);
+                }
+                matched.iter().for_each(|m| add(&m.pair));
+            }
+            SymbolPattern::Top { count, glob } => {
+                let mut candidates: Vec<(&str, f64)> = markets
+                    .iter()
+                    .filter(|m| m.trading)
+                    .filter(|m| glob.as_ref().is_none_or(|g| glob_matches(g, &m.pair)))
+                    .filter_map(|m| volumes.get(&m.pair).map(|v| (m.pair.as_str(), *v)))
+                    .collect();
+                candidates.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+                candidates
+                    .iter()
+                    .take(*count)
+                    .for_each(|(pair, _)| add(pair));
+            }
+        }
+    }
+    Ok(pairs)
+}
+
+/// Where an exchange publishes its markets metadata and 24h volumes
+struct MarketsEndpoints {
+    exchange: &'static str,
+    markets_url: String,
+    volumes_url: String,
+}
+
+impl MarketsEndpoints {
+    fn new(exchange: &str, rest_url: &str) -> Option<Self> {
+        let base = rest_url.trim_end_matches('/');
+        match exchange.to_lowercase().as_str() {
+            "poloniex" => Some(MarketsEndpoints {
+                exchange: "poloniex",
+                markets_url: format!("{}/markets", base),
+                volumes_url: format!("{}/markets/ticker24h", base),
+            }),
+            "binance" => Some(MarketsEndpoints {
+                exchange: "binance",
+                markets_url: format!("{}/api/v3/exchangeInfo", base),
+                volumes_url: format!("{}/api/v3/ticker/24hr", base),
+            }),
+            _ => None,
+        }
+    }
+
+    async fn fetch_markets(&self, client: &dyn RestClient) -> Result<Vec<SymbolInfo>, String> {
+        let response = client
+            .get(&self.markets_url)
+            .await
+            .map_err(|e| e.to_string())?;
+        match self.exchange {
+            "binance" => parse_binance_exchange_info(&response),
+            _ => parse_poloniex_markets(&response),
+        }
+    }
+
+    /// 24h quote volumes by pair
+    async fn fetch_volumes(
+        &self,
+        client: &dyn RestClient,
+        markets: &[SymbolInfo],
+    ) -> Result<HashMap<String, f64>, String> {
+        let response = client
+            .get(&self.volumes_url)
+            .await
+            .map_err(|e| e.to_string())?;
+        match self.exchange {
+            "binance" => {
+                let by_symbol = parse_binance_volumes(&response)?;
+                Ok(markets
+                    .iter()
+                    .filter_map(|m| {
+                        let volume = by_symbol.get(&m.exchange_symbol)?;
+                        Some((m.pair.clone(), *volume))
+                    })
+                    .collect())
+            }
+            _ => Ok(parse_tickers(&response)?
+                .into_iter()
+                .map(|t| (t.pair, t.amount))
+                .collect()),
+        }
+    }
+}
+
+/*
+    Resolves SYMBOLS at startup. With `sync` the exchange's markets are fetched and stored in the
+    `symbols` table; without it, or when the exchange can't be reached, the last stored sync is used.
+    With nothing stored either, exact pairs are taken as configured and patterns fail.
+*/
+pub async fn resolve_symbols(
+    client: &dyn RestClient,
+    store: &dyn SymbolStore,
+    exchange: &str,
+    rest_url: &str,
+    configured: &[String],
+    sync: bool,
+) -> Result<Vec<String>, Error> {
+    let invalid = |reason: String| ConfigError::Invalid {
+        name: "SYMBOLS",
+        value: configured.join(","),
+        reason,
+    };
+    let patterns = configured
+        .iter()
+        .map(|s| s.parse::<SymbolPattern>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(invalid)?;
+    let Some(endpoints) = MarketsEndpoints::new(exchange, rest_url) else {
+        return Err(invalid(format!("no markets metadata for the exchange {}", exchange)).into());
+    };
+
+    let mut markets = Vec::new();
+    if sync {
+        match endpoints.fetch_markets(client).await {
+            Ok(fetched) => {
+                store.save_symbols(&fetched).await?;
+                info!("Synced {} markets of {}", fetched.len(), endpoints.exchange);
+                markets = fetched;
+            }
+            Err(err) => warn!("Failed to fetch the markets of {}: {}", exchange, err),
+        }
+    }
+    if markets.is_empty() {
+        markets = store.load_symbols(endpoints.exchange).await?;
+    }
+    if markets.is_empty() {
+        if patterns
+            .iter()
+            .all(|p| matches!(p, SymbolPattern::Exact(_)))
+        {
+            warn!("No markets metadata, the symbols are not checked");
+            return Ok(configured.iter().map(|s| s.trim().to_string()).collect());
+        }
+        return Err(HttpClientError::new(&format!(
+            "the symbol patterns need the markets of {}, which could not be fetched",
+            exchange
+        ))
+        .into());
+    }
+
+    let needs_volumes = patterns
+        .iter()
+        .any(|p| matches!(p, SymbolPattern::Top { .. }));
+    let volumes = if needs_volumes {
+        endpoints
+            .fetch_volumes(client, &markets)
+            .await
+            .map_err(|e| HttpClientError::new(&format!("Failed to fetch 24h volumes: {}", e)))?
+    } else {
+        HashMap::new()
+    };
+
+    let symbols = expand(&patterns, &markets, &volumes).map_err(invalid)?;
+    if symbols.is_empty() {
+        return Err(invalid("no trading market matches".to_string()).into());
+    }
+    info!("Symbols: {}", symbols.join(","));
+    Ok(symbols)
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn market(pair: &str, trading: bool) -> SymbolInfo {
+        let (base, quote) = pair.split_once('_').unwrap();
+        SymbolInfo {
+            exchange: "poloniex".to_string(),
+            pair: pair.to_string(),
+            exchange_symbol: pair.to_string(),
+            base: base.to_string(),
+            quote: quote.to_string(),
+            status: if trading { "NORMAL" } else { "OFFLINE" }.to_string(),
+            trading,
+            price_precision: 2,
+            quantity_precision: 6,
+            min_quantity: 0.0001,
+            min_amount: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_expand_patterns() {
+        assert!(glob_matches("*_USDT", "BTC_USDT"));
+        assert!(glob_matches("?TC_*", "BTC_USDC"));
+        assert!(!glob_matches("*_USDT", "BTC_USDC"));
+        assert!(glob_matches("*T*T", "BTC_USDT"));
+
+        let markets = vec![
+            market("BTC_USDT", true),
+            market("ETH_USDT", true),
+            market("TRX_USDT", false),
+            market("ETH_BTC", true),
+            market("DOGE_USDT", true),
+        ];
+        let volumes: HashMap<String, f64> = [
+            ("BTC_USDT", 900.0),
+            ("ETH_USDT", 500.0),
+            ("TRX_USDT", 9999.0),
+            ("ETH_BTC", 700.0),
+            ("DOGE_USDT", 100.0),
+        ]
+        .into_iter()
+        .map(|(p, v)| (p.to_string(), v))
+        .collect();
+        let patterns =
+            |s: &str| -> Vec<SymbolPattern> { s.split(',').map(|p| p.parse().unwrap()).collect() };
+
+        assert_eq!(
+            expand(&patterns("ETH_BTC,*_USDT"), &markets, &volumes).unwrap(),
+            vec!["ETH_BTC", "BTC_USDT", "ETH_USDT", "DOGE_USDT"]
+        );
+        assert_eq!(
+            expand(&patterns("top:2"), &markets, &volumes).unwrap(),
+            vec!["BTC_USDT", "ETH_BTC"]
+        );
+        assert_eq!(
+            expand(&patterns("top:2:*_USDT,BTC_USDT"), &markets, &volumes).unwrap(),
+            vec!["BTC_USDT", "ETH_USDT"]
+        );
+        assert!(expand(&patterns("XRP_USDT"), &markets, &volumes).is_err());
+        assert!("top:many".parse::<SymbolPattern>().is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::{self, MissedTickBehavior};
+use tracing::{debug, error, warn};
+
+use crate::database::TickerStore;
+use crate::http_client::http_client::RestClient;
+use crate::parser::ticker::{parse_tickers, Ticker};
+
+/// The newest ticker of every pair, fed by the REST poller and the WebSocket `ticker` channel
+#[derive(Clone, Default)]
+pub struct LatestTickers {
+    tickers: Arc<Mutex<HashMap<String, Ticker>>>,
+}
+
+impl LatestTickers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps the ticker unless a newer one of the pair is already there
+    pub fn update(&self, ticker: Ticker) {
+        let mut tickers = self.tickers.lock().unwrap();
+        match tickers.get(&ticker.pair) {
+            Some(latest) if latest.timestamp >= ticker.timestamp => {}
+            _ => {
+                tickers.insert(ticker.pair.clone(), ticker);
+            }
+        }
+    }
+
+    pub fn get(&self, pair: &str) -> Option<Ticker> {
+        self.tickers.lock().unwrap().get(pair).cloned()
+    }
+
+    pub fn all(&self) -> Vec<Ticker> {
+        self.tickers.lock().unwrap().values().cloned().collect()
+    }
+}
+
+/*
+    Fetches `markets/{symbol}/ticker24h` for every (pair, url) every `interval`.
+    Runs until the task is dropped; a failed request is logged and retried with the next tick.
+*/
+pub async fn poll_tickers(
+    client: Box<dyn RestClient>,
+    urls: Vec<(String, String)>,
+    tickers: LatestTickers,
+    interval: Duration,
+) {
+    let mut timer = time::interval(interval);
+    timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        timer.tick().await;
+        for (pair, url) in &urls {
+            let response = match client.get(url).await {
+                Ok(response) => response,
+                Err(err) => {
+                    warn!("Failed to fetch the {} ticker from {}: {}", pair, url, err);
+                    continue;
+                }
+            };
+            match parse_tickers(&response) {
+                Ok(parsed) => parsed.into_iter().for_each(|t| tickers.update(t)),
+                Err(err) => warn!("Failed to parse the {} ticker: {}", pair, err),
+            }
+        }
+    }
+}
+
+/*
+    Stores every ticker that is newer than the one last stored for its pair, every `interval`.
+    Runs until the task is dropped; a failed write is logged and retried with the next tick.
+*/
+pub async fn record_tickers(
+    tickers: LatestTickers,
+    store: Arc<dyn TickerStore>,
+    interval: Duration,
+) {
+    let mut timer = time::interval(interval);
+    timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut stored: HashMap<String, i64> = HashMap::new(); // timestamp of the last stored ticker, by pair
+
+    loop {
+        timer.tick().await;
+        let fresh: Vec<Ticker> = tickers
+            .all()
+            .into_iter()
+            .filter(|t| stored.get(&t.pair) != Some(&t.timestamp))
+            .collect();
+        if fresh.is_empty() {
+            continue;
+        }
+        match store.save_tickers(&fresh).await {
+            Ok(_) => {
+                debug!("Stored {} tickers", fresh.len());
+                for t in fresh {
+                    stored.insert(t.pair, t.timestamp);
+                }
+            }
+            Err(e) => error!("Failed to store tickers: {}", e),
+        }
+    }
+}
@@ -1 +1,83 @@
-pub struct WebSocketMessage {}
+use serde::Deserialize;
+
+use crate::parser::recent_trade::TradeData;
+use crate::parser::ticker::TickerData;
+
+/// One symbol's part of a `book_lv2` message; levels are (price, quantity) as sent
+#[derive(Debug, Clone, Deserialize)]
+pub struct BookData {
+    pub symbol: String,
+    pub asks: Vec<(String, String)>,
+    pub bids: Vec<(String, String)>,
+    #[serde(rename = "lastId")]
+    pub last_id: i64, // id of the previous message of the symbol
+    pub id: i64,
+    pub ts: i64,
+}
+
+/// The messages of the Poloniex public WebSocket the client acts on
+#[derive(Debug, Clone)]
+pub enum WebSocketMessage {
+    BookSnapshot(Vec<BookData>),
+    BookUpdate(Vec<BookData>),
+    Ticker(Vec<TickerData>),
+    Trades(Vec<TradeData>),
+    Subscribed(String), // channel
+    Pong,
+    Error(String),
+    Other, // channels and events nobody subscribed to
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    event: Option<String>,
+    channel: Option<String>,
+    action: Option<String>,
+    message: Option<String>,
+    data: Option<serde_json::Value>,
+}
+
+impl WebSocketMessage {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let envelope: Envelope =
+            serde_json::from_str(text).map_err(|e| format!("Failed to parse message: {}", e))?;
+
+        match envelope.event.as_deref() {
+            Some("subscribe") => {
+                return Ok(WebSocketMessage::Subscribed(
+                    envelope.channel.unwrap_or_default(),
+                ))
+            }
+            Some("pong") => return Ok(WebSocketMessage::Pong),
+            Some("error") => {
+                return Ok(WebSocketMessage::Error(
+                    envelope.message.unwrap_or_default(),
+                ))
+            }
+            Some(_) => return Ok(WebSocketMessage::Other),
+            None => {}
+        }
+
+        match envelope.channel.as_deref() {
+            Some("book_lv2") => {}
+            Some("ticker") => {
+                let data = serde_json::from_value(envelope.data.unwrap_or_default())
+                    .map_err(|e| format!("Failed to parse ticker data: {}", e))?;
+                return Ok(WebSocketMessage::Ticker(data));
+            }
+            Some("trades") => {
+                let data = serde_json::from_value(envelope.data.unwrap_or_default())
+                    .map_err(|e| format!("Failed to parse trades data: {}", e))?;
+                return Ok(WebSocketMessage::Trades(data));
+            }
+            _ => return Ok(WebSocketMessage::Other),
+        }
+        let data: Vec<BookData> = serde_json::from_value(envelope.data.unwrap_or_default())
+            .map_err(|e| format!("Failed to parse book data: {}", e))?;
+        match envelope.action.as_deref() {
+            Some("snapshot") => Ok(WebSocketMessage::BookSnapshot(data)),
+            Some("update") => Ok(WebSocketMessage::BookUpdate(data)),
+            other => Err(format!("Unknown book action {:?}", other)),
+        }
+    }
+}
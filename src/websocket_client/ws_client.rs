@@ -1,3 +1,289 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use tokio::time::{self, MissedTickBehavior};
+use tokio_tungstenite::tungstenite::{self, Message};
+use tracing::{debug, info, info_span, warn, Instrument};
+
+use super::message::WebSocketMessage;
+use crate::aggregator::CandleAggregator;
+use crate::capture::{CaptureSource, RawCapture};
+use crate::metrics::metrics;
+use crate::order_book::OrderBooks;
+use crate::parser::{recent_trade::RecentTrade, ticker::Ticker};
+use crate::ticker::LatestTickers;
+
+/*
+    Client of the Poloniex public WebSocket, subscribed for the symbols to
+        `book_lv2` - when it keeps order books: a book whose sequence breaks is dropped and
+                     resubscribed, which makes the exchange send a new snapshot
+        `ticker`   - when it keeps the latest tickers
+        `trades`   - when it passes trades to an aggregator, through `trades_process`
+    A lost connection is re-established after `reconnect_delay`, with every book starting over from a snapshot.
+    With a capture, every text frame is archived as received, before it is parsed.
+*/
 pub struct WebSocketClient {
-    // Тут будут войства клиента WebSocket
+    url: String,
+    symbols: Vec<String>,
+    books: Option<OrderBooks>,
+    tickers: Option<LatestTickers>,
+    trades: Option<Arc<CandleAggregator>>,
+    capture: Option<RawCapture>,
+    reconnect_delay: Duration,
+    ping_interval: Duration, // the server drops connections that stay silent for 30 s
+}
+
+fn request(event: &str, channels: &[&str], symbols: &[String]) -> Message {
+    Message::text(json!({ "event": event, "channel": channels, "symbols": symbols }).to_string())
+}
+
+impl WebSocketClient {
+    pub fn new(url: &str, symbols: &[String]) -> Self {
+        WebSocketClient {
+            url: url.to_string(),
+            symbols: symbols.to_vec(),
+            books: None,
+            tickers: None,
+            trades: None,
+            capture: None,
+            reconnect_delay: Duration::from_secs(5),
+            ping_interval: Duration::from_secs(20),
+        }
+    }
+
+    pub fn with_order_books(mut self, books: OrderBooks) -> Self {
+        self.books = Some(books);
+        self
+    }
+
+    pub fn with_tickers(mut self, tickers: LatestTickers) -> Self {
+        self.tickers = Some(tickers);
+        self
+    }
+
+    pub fn with_trades(mut self, aggregator: Arc<CandleAggregator>) -> Self {
+        self.trades = Some(aggregator);
+        self
+    }
+
+    /// Captures every text frame received
+    pub fn with_capture(mut self, capture: RawCapture) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    pub fn with_reconnect_delay(mut self, reconnect_delay: Duration) -> Self {
+        self.reconnect_delay = reconnect_delay;
+        self
+    }
+
+    /// Keeps the books and tickers up to date and passes on the trades until the task is dropped
+    pub async fn run(&self) {
+        loop {
+            let span = info_span!("websocket", url = %self.url);
+            match self.session().instrument(span).await {
+                Ok(()) => info!("WebSocket closed by the server"),
+                Err(err) => warn!("WebSocket failed: {}", err),
+            }
+            time::sleep(self.reconnect_delay).await;
+            metrics().websocket_reconnects.inc();
+        }
+    }
+
+    /// One connection, from subscribing until it is closed
+    async fn session(&self) -> Result<(), tungstenite::Error> {
+        let (socket, _) = tokio_tungstenite::connect_async(&self.url).await?;
+        let (mut sink, mut incoming) = socket.split();
+        let mut channels = Vec::new();
+        if let Some(books) = &self.books {
+            for symbol in &self.symbols {
+                books.reset(symbol); // whatever happened while disconnected is lost
+            }
+            channels.push("book_lv2");
+        }
+        if self.tickers.is_some() {
+            channels.push("ticker");
+        }
+        if self.trades.is_some() {
+            channels.push("trades");
+        }
+        sink.send(request("subscribe", &channels, &self.symbols))
+            .await?;
+        info!(
+            "Subscribed to {} for {}",
+            channels.join(","),
+            self.symbols.join(",")
+        );
+
+        let mut ping = time::interval(self.ping_interval);
+        ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ping.tick().await; // the first tick is immediate
+
+        loop {
+            tokio::select! {
+                _ = ping.tick() => {
+                    sink.send(Message::text(json!({ "event": "ping" }).to_string())).await?;
+                }
+                message = incoming.next() => {
+                    let text = match message {
+                        Some(Ok(Message::Text(text))) => text,
+                        Some(Ok(Message::Close(_))) | None => return Ok(()),
+                        Some(Ok(_)) => continue,
+                        Some(Err(err)) => return Err(err),
+                    };
+                    if let Some(capture) = &self.capture {
+                        capture.record(CaptureSource::Ws, &self.url, text.as_str());
+                    }
+                    for symbol in self.handle(text.as_str()).await {
+                        metrics().order_book_resyncs.with_label_values(&[symbol.as_str()]).inc();
+                        let symbols = [symbol];
+                        sink.send(request("unsubscribe", &["book_lv2"], &symbols)).await?;
+                        sink.send(request("subscribe", &["book_lv2"], &symbols)).await?;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Applies a message to the books, tickers or trades, returns the symbols whose books have to be resynchronized
+    async fn handle(&self, text: &str) -> Vec<String> {
+        let message = match WebSocketMessage::parse(text) {
+            Ok(message) => message,
+            Err(err) => {
+                warn!("{}: {}", err, text);
+                return Vec::new();
+            }
+        };
+        let mut resync = Vec::new();
+        match message {
+            WebSocketMessage::BookSnapshot(data) => {
+                let Some(books) = &self.books else {
+                    return resync;
+                };
+                for book in data {
+                    if let Err(err) = books.apply_snapshot(&book) {
+                        warn!("Bad {} snapshot: {}", book.symbol, err);
+                        resync.push(book.symbol);
+                    }
+                }
+            }
+            WebSocketMessage::BookUpdate(data) => {
+                let Some(books) = &self.books else {
+                    return resync;
+                };
+                for book in data {
+                    if let Err(err) = books.apply_update(&book) {
+                        warn!("Resynchronizing the {} book: {}", book.symbol, err);
+                        books.reset(&book.symbol);
+                        if !resync.contains(&book.symbol) {
+                            resync.push(book.symbol);
+                        }
+                    }
+                }
+            }
+            WebSocketMessage::Ticker(data) => {
+                let Some(tickers) = &self.tickers else {
+                    return resync;
+                };
+                for data in data {
+                    match Ticker::try_from(data) {
+                        Ok(ticker) => tickers.update(ticker),
+                        Err(err) => warn!("Bad ticker: {}", err),
+                    }
+                }
+            }
+            WebSocketMessage::Subscribed(channel) => {
+                debug!("Subscription to {} confirmed", channel)
+            }
+            WebSocketMessage::Trades(data) => {
+                let Some(aggregator) = &self.trades else {
+                    return resync;
+                };
+                let trades: Vec<RecentTrade> = data.into_iter().map(RecentTrade::from).collect();
+                aggregator.trades_process(&trades).await;
+            }
+            WebSocketMessage::Pong | WebSocketMessage::Other => {}
+            WebSocketMessage::Error(message) => warn!("WebSocket error message: {}", message),
+        }
+        resync
+    }
+}
+
+/*
+ *  Test module
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{
+        KlineWriteBuffer, MemoryStore, TradeFilter, TradeStore, WriteBufferConfig,
+    };
+    use crate::live::{LiveEvent, LiveFeed};
+
+    /// A `trades` frame with one buy of `pair`
+    fn trades_frame(pair: &str, id: &str) -> String {
+        format!(
+            r#"{{"channel":"trades","data":[{{"symbol":"{}","amount":"101","quantity":"1","takerSide":"buy","createTime":1737709991000,"price":"101","id":"{}","ts":1737709991010}}]}}"#,
+            pair, id
+        )
+    }
+
+    fn trades_client(aggregator: CandleAggregator) -> WebSocketClient {
+        WebSocketClient::new("wss://example.invalid/ws/public", &[])
+            .with_trades(Arc::new(aggregator))
+    }
+
+    fn aggregator() -> CandleAggregator {
+        let store = Arc::new(MemoryStore::new());
+        CandleAggregator::new(KlineWriteBuffer::spawn(store, WriteBufferConfig::default()))
+    }
+
+    #[tokio::test]
+    async fn test_trades_reach_the_live_feed() {
+        let feed = LiveFeed::default();
+        let mut events = feed.subscribe();
+        let client = trades_client(aggregator().with_live_feed(feed));
+        assert!(client
+            .handle(&trades_frame("BTC_USDT", "42"))
+            .await
+            .is_empty());
+
+        let LiveEvent::Trade(trade) = events.try_recv().unwrap().as_ref().clone() else {
+            panic!("expected a trade");
+        };
+        assert_eq!(
+            (trade.pair.as_str(), trade.tid.as_str(), trade.timestamp),
+            ("BTC_USDT", "42", 1737709991000)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_live_trades_are_counted() {
+        let client = trades_client(aggregator());
+        let received = || {
+            metrics()
+                .trades_received
+                .with_label_values(&["LTC_USDT"])
+                .get()
+        };
+        let before = received();
+        client.handle(&trades_frame("LTC_USDT", "1")).await;
+        client.handle(&trades_frame("LTC_USDT", "2")).await;
+        assert_eq!(received() - before, 2);
+    }
+
+    #[tokio::test]
+    async fn test_live_trades_are_saved() {
+        let store = Arc::new(MemoryStore::new());
+        let client = trades_client(aggregator().with_trade_store(store.clone()));
+        client.handle(&trades_frame("BTC_USDT", "7")).await;
+
+        let trades = store.load_trades(&TradeFilter::default()).await.unwrap();
+        assert_eq!(
+            trades.iter().map(|t| t.tid.as_str()).collect::<Vec<_>>(),
+            vec!["7"]
+        );
+    }
 }
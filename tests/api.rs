@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use rust_kline_ws::api;
+use rust_kline_ws::database::{KlineStore, MemoryStore, Store, TradeStore};
+use rust_kline_ws::parser::recent_trade::RecentTrade;
+use rust_kline_ws::parser::KlineParser;
+use serde_json::Value;
+use tokio::net::TcpListener;
+
+/// A candle as returned by the Poloniex REST API
+fn candle(low: &str, high: &str, interval: &str, start_time: i64) -> Value {
+    serde_json::json!([
+        low,
+        high,
+        "30000",
+        "30050",
+        "9000",
+        "0.3",
+        "15000",
+        "0.5",
+        10,
+        1737709991000i64,
+        "30010",
+        interval,
+        start_time,
+        start_time + 59_999
+    ])
+}
+
+async fn store_with_data() -> Arc<dyn Store> {
+    let store = MemoryStore::new();
+    let parser = KlineParser::new();
+    for (pair, candles) in [
+        (
+            "BTC_USDT",
+            vec![
+                candle("29900", "30100", "MINUTE_1", 1737709980000),
+                candle("29900", "30100", "MINUTE_1", 1737709920000),
+                candle("29800", "30200", "HOUR_1", 1737709200000),
+            ],
+        ),
+        (
+            "ETH_USDT",
+            vec![candle("29900", "30100", "MINUTE_1", 1737709920000)],
+        ),
+    ] {
+        let response = serde_json::to_string(&candles).unwrap();
+        for klines in parser.parse(&response, pair).unwrap().values() {
+            store.save_klines(klines).await.unwrap();
+        }
+    }
+    store
+        .save_trades(&[RecentTrade {
+            tid: "1".to_string(),
+            pair: "BTC_USDT".to_string(),
+            price: "30000.5".to_string(),
+            amount: "0.01".to_string(),
+            side: "buy".to_string(),
+            timestamp: 1737709931000,
+        }])
+        .await
+        .unwrap();
+    Arc::new(store)
+}
+
+/// Serves the API on a free local port and returns its base URL
+async fn start_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let store = store_with_data().await;
+    tokio::spawn(api::serve(listener, store));
+    format!("http://{}", addr)
+}
+
+async fn get(url: String) -> Value {
+    let response = reqwest::get(url).await.unwrap();
+    assert!(response.status().is_success());
+    response.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn test_klines_endpoint() {
+    let base = start_server().await;
+
+    let klines = get(format!("{}/klines?pair=BTC_USDT&timeframe=MINUTE_1", base)).await;
+    let klines = klines.as_array().unwrap();
+    assert_eq!(klines.len(), 2);
+    assert_eq!(klines[0]["utc_begin"], 1737709920000i64);
+    assert_eq!(klines[1]["utc_begin"], 1737709980000i64);
+    assert_eq!(klines[0]["pair"], "BTC_USDT");
+    assert_eq!(klines[0]["time_frame"], "MINUTE_1");
+    assert_eq!(klines[0]["open"], 30000.0);
+    assert_eq!(klines[0]["high"], 30100.0);
+    assert_eq!(klines[0]["low"], 29900.0);
+    assert_eq!(klines[0]["close"], 30050.0);
+
+    let klines = get(format!(
+        "{}/klines?pair=BTC_USDT&from=1737709950000&limit=1",
+        base
+    ))
+    .await;
+    assert_eq!(klines.as_array().unwrap().len(), 1);
+    assert_eq!(klines[0]["utc_begin"], 1737709980000i64);
+
+    let response = reqwest::get(format!("{}/klines?from=yesterday", base))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_series_and_trades_endpoints() {
+    let base = start_server().await;
+
+    assert_eq!(
+        get(format!("{}/pairs", base)).await,
+        serde_json::json!(["BTC_USDT", "ETH_USDT"])
+    );
+    assert_eq!(
+        get(format!("{}/timeframes", base)).await,
+        serde_json::json!(["HOUR_1", "MINUTE_1"])
+    );
+    assert_eq!(
+        get(format!("{}/timeframes?pair=ETH_USDT", base)).await,
+        serde_json::json!(["MINUTE_1"])
+    );
+
+    let trades = get(format!("{}/trades?pair=BTC_USDT", base)).await;
+    assert_eq!(trades[0]["tid"], "1");
+    assert_eq!(trades[0]["price"], "30000.5");
+    assert_eq!(
+        get(format!("{}/trades?pair=ETH_USDT", base)).await,
+        serde_json::json!([])
+    );
+}
@@ -0,0 +1,26 @@
+use rust_kline_ws::health::{self, HealthConfig, HealthReport, HealthTracker};
+use tokio::net::TcpListener;
+
+#[tokio::test]
+async fn test_health_endpoints() {
+    let tracker = HealthTracker::new(HealthConfig::default());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(health::serve(listener, tracker.clone()));
+
+    // Not ready until the aggregator has built its handlers
+    let response = reqwest::get(format!("{}/ready", base)).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+
+    tracker.watch(&[("BTC_USDT".to_string(), "MINUTE_1".to_string())]);
+    let response = reqwest::get(format!("{}/ready", base)).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // No candle yet, but within the staleness threshold of the start
+    let response = reqwest::get(format!("{}/health", base)).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let report: HealthReport = response.json().await.unwrap();
+    assert!(report.healthy);
+    assert_eq!(report.series[0].pair, "BTC_USDT");
+    assert_eq!(report.series[0].last_utc_begin, None);
+}
@@ -0,0 +1,123 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use rust_kline_ws::database::{KlineWriteBuffer, MemoryStore, WriteBufferConfig};
+use rust_kline_ws::live::{server, LiveFeed};
+use rust_kline_ws::parser::recent_trade::RecentTrade;
+use rust_kline_ws::parser::KlineParser;
+use rust_kline_ws::CandleAggregator;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+/// A Poloniex REST candles response with one candle
+fn candles_response(interval: &str, start_time: i64) -> String {
+    json!([[
+        "29900",
+        "30100",
+        "30000",
+        "30050",
+        "9000",
+        "0.3",
+        "15000",
+        "0.5",
+        10,
+        1737709991000i64,
+        "30010",
+        interval,
+        start_time,
+        start_time + 59_999
+    ]])
+    .to_string()
+}
+
+fn trade(tid: &str, pair: &str) -> RecentTrade {
+    RecentTrade {
+        tid: tid.to_string(),
+        pair: pair.to_string(),
+        price: "30000.5".to_string(),
+        amount: "0.01".to_string(),
+        side: "buy".to_string(),
+        timestamp: 1737709931000,
+    }
+}
+
+async fn next_json<S>(socket: &mut S) -> Value
+where
+    S: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    let message = tokio::time::timeout(Duration::from_secs(5), socket.next())
+        .await
+        .expect("no message from the live feed")
+        .unwrap()
+        .unwrap();
+    serde_json::from_str(message.to_text().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_live_feed_fan_out() {
+    let feed = LiveFeed::default();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(server::serve(listener, feed.clone()));
+
+    let aggregator = CandleAggregator::new(KlineWriteBuffer::spawn(
+        Arc::new(MemoryStore::new()),
+        WriteBufferConfig::default(),
+    ))
+    .with_live_feed(feed);
+    aggregator.build_handlers(&[]).await;
+
+    let (mut candles_client, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+    let (mut trades_client, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+    candles_client
+        .send(Message::text(
+            json!({"op": "subscribe", "channel": "candles", "pair": "BTC_USDT", "timeframe": "MINUTE_1"})
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+    let ack = next_json(&mut candles_client).await;
+    assert_eq!(ack["event"], "subscribed");
+    assert_eq!(ack["subscription"]["timeframe"], "MINUTE_1");
+    trades_client
+        .send(Message::text(
+            json!({"op": "subscribe", "channel": "trades", "pair": "ETH_USDT"}).to_string(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(next_json(&mut trades_client).await["event"], "subscribed");
+
+    // Only the subscribed series reaches each client
+    let parser = KlineParser::new();
+    for response in [
+        candles_response("HOUR_1", 1737709200000),
+        candles_response("MINUTE_1", 1737709920000),
+    ] {
+        aggregator
+            .http_response_process(parser.parse(&response, "BTC_USDT").unwrap())
+            .await;
+    }
+    aggregator
+        .trades_process(&[trade("1", "BTC_USDT"), trade("2", "ETH_USDT")])
+        .await;
+
+    let candle = next_json(&mut candles_client).await;
+    assert_eq!(candle["channel"], "candles");
+    assert_eq!(candle["data"]["time_frame"], "MINUTE_1");
+    assert_eq!(candle["data"]["utc_begin"], 1737709920000i64);
+    assert_eq!(candle["data"]["close"], 30050.0);
+
+    let trade = next_json(&mut trades_client).await;
+    assert_eq!(trade["channel"], "trades");
+    assert_eq!(trade["data"]["tid"], "2");
+
+    candles_client
+        .send(Message::text(
+            json!({"op": "subscribe", "channel": "nope"}).to_string(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(next_json(&mut candles_client).await["event"], "error");
+}
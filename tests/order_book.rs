@@ -0,0 +1,161 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use rust_kline_ws::database::{MemoryStore, OrderBookStore, SnapshotFilter};
+use rust_kline_ws::order_book::{record_snapshots, OrderBookConfig, OrderBooks};
+use rust_kline_ws::parser::order_book::OrderBookSnapshot;
+use rust_kline_ws::websocket_client::WebSocketClient;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+fn book(action: &str, last_id: i64, id: i64, asks: Value, bids: Value) -> Message {
+    Message::text(
+        json!({
+            "channel": "book_lv2",
+            "action": action,
+            "data": [{
+                "symbol": "BTC_USDT",
+                "createTime": 1737709931000i64,
+                "asks": asks,
+                "bids": bids,
+                "lastId": last_id,
+                "id": id,
+                "ts": 1737709931000i64 + id
+            }]
+        })
+        .to_string(),
+    )
+}
+
+async fn next_json<S>(socket: &mut S) -> Value
+where
+    S: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(5), socket.next())
+            .await
+            .expect("no message from the client")
+            .unwrap()
+            .unwrap();
+        if let Message::Text(text) = message {
+            return serde_json::from_str(text.as_str()).unwrap();
+        }
+    }
+}
+
+/// Waits until the book of BTC_USDT satisfies `ready`
+async fn wait_for(
+    books: &OrderBooks,
+    ready: impl Fn(&OrderBookSnapshot) -> bool,
+) -> OrderBookSnapshot {
+    for _ in 0..100 {
+        if let Some(snapshot) = books.snapshot("BTC_USDT", 10).filter(|s| ready(s)) {
+            return snapshot;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!(
+        "the book never got there: {:?}",
+        books.snapshot("BTC_USDT", 10)
+    );
+}
+
+#[tokio::test]
+async fn test_order_book_resync_on_gap() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let books = OrderBooks::new();
+    let client =
+        WebSocketClient::new(&url, &["BTC_USDT".to_string()]).with_order_books(books.clone());
+    let client = tokio::spawn(async move { client.run().await });
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+    let request = next_json(&mut socket).await;
+    assert_eq!(request["event"], "subscribe");
+    assert_eq!(request["channel"], json!(["book_lv2"]));
+    assert_eq!(request["symbols"], json!(["BTC_USDT"]));
+    socket
+        .send(Message::text(
+            json!({ "event": "subscribe", "channel": "book_lv2", "symbols": ["BTC_USDT"] })
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+
+    // Snapshot, then an update that moves the best bid and removes an ask
+    socket
+        .send(book(
+            "snapshot",
+            0,
+            10,
+            json!([["30010", "1"], ["30020", "2"]]),
+            json!([["30000", "1.5"], ["29990", "3"]]),
+        ))
+        .await
+        .unwrap();
+    socket
+        .send(book(
+            "update",
+            10,
+            11,
+            json!([["30010", "0"]]),
+            json!([["30005", "0.5"]]),
+        ))
+        .await
+        .unwrap();
+    let snapshot = wait_for(&books, |s| s.timestamp == 1737709931011).await;
+    assert_eq!(snapshot.asks.len(), 1);
+    assert_eq!(snapshot.asks[0].price, 30020.0);
+    assert_eq!(snapshot.bids[0].price, 30005.0);
+    assert_eq!(snapshot.bids[1].quantity, 1.5);
+
+    // Update 12 is lost: the book is dropped and resubscribed
+    socket
+        .send(book("update", 12, 13, json!([]), json!([["30006", "1"]])))
+        .await
+        .unwrap();
+    let unsubscribe = next_json(&mut socket).await;
+    assert_eq!(unsubscribe["event"], "unsubscribe");
+    let subscribe = next_json(&mut socket).await;
+    assert_eq!(subscribe["event"], "subscribe");
+    assert_eq!(subscribe["symbols"], json!(["BTC_USDT"]));
+    assert!(books.snapshot("BTC_USDT", 10).is_none());
+
+    socket
+        .send(book(
+            "snapshot",
+            0,
+            20,
+            json!([["30030", "4"]]),
+            json!([["30001", "2"]]),
+        ))
+        .await
+        .unwrap();
+    let snapshot = wait_for(&books, |s| s.timestamp == 1737709931020).await;
+    assert_eq!(snapshot.asks[0].price, 30030.0);
+    assert_eq!(snapshot.bids[0].price, 30001.0);
+
+    // The recorder stores the book once, it doesn't change afterwards
+    let store = Arc::new(MemoryStore::new());
+    let recorder = tokio::spawn(record_snapshots(
+        books.clone(),
+        store.clone(),
+        OrderBookConfig {
+            depth: 5,
+            snapshot_interval: Duration::from_millis(20),
+        },
+    ));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    recorder.abort();
+    client.abort();
+
+    let stored = store
+        .load_order_book_snapshots(&SnapshotFilter::default())
+        .await
+        .unwrap();
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0], snapshot);
+}
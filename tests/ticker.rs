@@ -0,0 +1,104 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use rust_kline_ws::database::{MemoryStore, TickerFilter, TickerStore};
+use rust_kline_ws::http_client::http_client::{RestClient, RestResponse};
+use rust_kline_ws::ticker::{poll_tickers, record_tickers, LatestTickers};
+use rust_kline_ws::websocket_client::WebSocketClient;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+fn ticker_json(symbol: &str, close: &str, ts: i64) -> Value {
+    json!({
+        "symbol": symbol,
+        "startTime": ts - 86_400_000,
+        "open": "30000",
+        "high": "30600",
+        "low": "29500",
+        "close": close,
+        "quantity": "120.5",
+        "amount": "3630000",
+        "tradeCount": 4210,
+        "dailyChange": "0.01",
+        "markPrice": close,
+        "closeTime": ts,
+        "ts": ts
+    })
+}
+
+/// Answers every request with the same body
+struct ScriptedRest(String);
+
+impl RestClient for ScriptedRest {
+    fn get<'a>(&'a self, _url: &'a str) -> RestResponse<'a> {
+        Box::pin(async move { Ok(self.0.clone()) })
+    }
+}
+
+#[tokio::test]
+async fn test_tickers_from_rest_and_websocket() {
+    let tickers = LatestTickers::new();
+
+    // REST first: the poller fills in the ticker right away
+    let rest = ScriptedRest(ticker_json("BTC_USDT", "30300", 1737709931000).to_string());
+    let poller = tokio::spawn(poll_tickers(
+        Box::new(rest),
+        vec![("BTC_USDT".to_string(), "http://rest/ticker24h".to_string())],
+        tickers.clone(),
+        Duration::from_secs(3600),
+    ));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let symbols = ["BTC_USDT".to_string(), "ETH_USDT".to_string()];
+    let client = WebSocketClient::new(&url, &symbols).with_tickers(tickers.clone());
+    let client = tokio::spawn(async move { client.run().await });
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+    let request = socket.next().await.unwrap().unwrap();
+    let request: Value = serde_json::from_str(request.to_text().unwrap()).unwrap();
+    assert_eq!(request["event"], "subscribe");
+    assert_eq!(request["channel"], json!(["ticker"]));
+    socket
+        .send(Message::text(
+            json!({
+                "channel": "ticker",
+                "data": [
+                    ticker_json("BTC_USDT", "30400", 1737709932000),
+                    ticker_json("ETH_USDT", "3300", 1737709932000)
+                ]
+            })
+            .to_string(),
+        ))
+        .await
+        .unwrap();
+
+    for _ in 0..100 {
+        if tickers.get("ETH_USDT").is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let btc = tickers.get("BTC_USDT").unwrap();
+    assert_eq!(btc.close, 30400.0); // the newer WebSocket ticker replaced the polled one
+    assert_eq!(tickers.get("ETH_USDT").unwrap().close, 3300.0);
+
+    let store = Arc::new(MemoryStore::new());
+    let recorder = tokio::spawn(record_tickers(
+        tickers.clone(),
+        store.clone(),
+        Duration::from_millis(20),
+    ));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    recorder.abort();
+    poller.abort();
+    client.abort();
+
+    let stored = store.load_tickers(&TickerFilter::default()).await.unwrap();
+    assert_eq!(stored.len(), 2); // stored once each, they didn't change afterwards
+    assert_eq!(stored[0], btc);
+    assert_eq!(stored[1].pair, "ETH_USDT");
+}